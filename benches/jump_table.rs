@@ -0,0 +1,76 @@
+// Compares bracket resolution through the jump table against the linear
+// lookup-table scan it replaced, on programs with more and more loops. Run
+// with `cargo bench --bench jump_table`; the table should show the linear
+// scan growing quadratically while the jump table stays linear.
+
+use brainfuck_rs::{Extensions, Interpreter, StepResult};
+use std::time::{Duration, Instant};
+
+// The old scheme: (open, close) pairs searched on every jump
+fn run_linear_scan(source: &[u8]) {
+    let mut pairs = Vec::new();
+    let mut stack = Vec::new();
+    for (index, command) in source.iter().enumerate() {
+        match command {
+            b'[' => stack.push(index),
+            b']' => pairs.push((stack.pop().unwrap(), index)),
+            _ => {}
+        }
+    }
+    let (mut memory, mut pointer, mut position) = ([0u8; 256], 0usize, 0);
+    while position < source.len() {
+        match source[position] {
+            b'+' => memory[pointer] = memory[pointer].wrapping_add(1),
+            b'-' => memory[pointer] = memory[pointer].wrapping_sub(1),
+            b'>' => pointer = (pointer + 1) % 256,
+            b'<' => pointer = (pointer + 255) % 256,
+            b'[' if memory[pointer] == 0 => {
+                position = pairs.iter().find(|(open, _)| *open == position).unwrap().1
+            }
+            b']' if memory[pointer] != 0 => {
+                position = pairs
+                    .iter()
+                    .find(|(_, close)| *close == position)
+                    .unwrap()
+                    .0
+            }
+            _ => {}
+        }
+        position += 1;
+    }
+    std::hint::black_box(memory);
+}
+
+fn run_jump_table(source: &str) {
+    let mut interpreter = Interpreter::new(source, Extensions::default()).unwrap();
+    while interpreter.step().unwrap() != StepResult::Halted {}
+    std::hint::black_box(interpreter.memory());
+}
+
+fn time(runs: u32, mut run: impl FnMut()) -> Duration {
+    let started = Instant::now();
+    for _ in 0..runs {
+        run();
+    }
+    started.elapsed() / runs
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>16} {:>16} {:>8}",
+        "loops", "linear scan", "jump table", "speedup"
+    );
+    for loops in [500, 1_000, 2_000, 4_000, 8_000] {
+        // Each loop runs a few times so both directions of jump are exercised
+        let source = "+++[-]".repeat(loops);
+        let linear = time(5, || run_linear_scan(source.as_bytes()));
+        let table = time(5, || run_jump_table(&source));
+        println!(
+            "{:>8} {:>16?} {:>16?} {:>7.1}x",
+            loops,
+            linear,
+            table,
+            linear.as_secs_f64() / table.as_secs_f64()
+        );
+    }
+}
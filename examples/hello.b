@@ -0,0 +1,3 @@
+Hello world: prints the classic greeting and a newline
+
+++++++++++[>+++++++>++++++++++>+++>+<<<<-]>++.>+.+++++++..+++.>++.<<+++++++++++++++.>.+++.------.--------.>+.>.
@@ -0,0 +1,96 @@
+Mandelbrot set as ASCII art
+
+Draws 22 rows of 52 characters; the denser the character the more of the 12
+iterations the point survived
+Numbers are fixed point with four fractional bits and keep their magnitude and
+sign in separate cells; squares are built from the two nibbles so that nothing
+overflows a byte
+Generated by a script rather than written by hand
+
+>>>>>>+++++++++++++++++++++>>>>>>>>>>>>>>>>>>>>>>>>++++++++++++++++++++++[<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<[-]>>>[-]<<<++++++++++++++++++++++++++++++++++++++++>>>+
+>>>>>>>>>>>>>>>>>>>>>>>>>>>>++++++++++++++++++++++++++++++++++++++++++++++++++++
+[<<<<<<<++++++++++++>>>+[<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>>>>>>>+>>>>>>+<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<++++++++++++++++<[->-[>+>>]>[+[
+-<+>]>+>>]<<<<<]>[-]>>[->>>>+>>>+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<<>+<[->-]>[<
+>->]<<>+<[->-]>[<>->]<<>+<[->-]>[<>->]<<>+<[<<<<<<<<<<[-]+>>>>>>>>>>[-]>-]>[<>->
+]<<<<<<<[->>>>>+>>>+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<<[-<<<<<[->>>>+>>>>+<
+<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<<]<[->>>>+>>>>>>+<<<<<<<<<<]>>>>>>>>>>[-<<
+<<<<<<<<+>>>>>>>>>>]<<<<<++++++++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[-
+<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>]<[-]<<<<<<[-]<<<[->>>>+>>>+<<<<<<<]>>>>>>>
+[-<<<<<<<+>>>>>>>]<<<[-<<<<<[-<<<<<<<++>>>>>>>>>>>>>>>+<<<<<<<<]>>>>>>>>[-<<<<<<
+<<+>>>>>>>>]<<<<<<<[-<<<<<<<<++++++++++++++++>>>>>>>>>>>>>>>+<<<<<<<]>>>>>>>[-<<
+<<<<<+>>>>>>>]<<<]<<<<<[-]>[-]<<<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>+>>>>>>
++<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<+
+>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<++++++++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[
+-]>>[->>>>+>>>+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<<>+<[->-]>[<>->]<<>+<[->-]>[<>
+->]<<>+<[->-]>[<>->]<<>+<[<<<<<<<<<<[-]+>>>>>>>>>>[-]>-]>[<>->]<<<<<<<[->>>>>+>>
+>+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<<[-<<<<<[->>>>+>>>>+<<<<<<<<]>>>>>>>>[-
+<<<<<<<<+>>>>>>>>]<<<]<[->>>>+>>>>>>+<<<<<<<<<<]>>>>>>>>>>[-<<<<<<<<<<+>>>>>>>>>
+>]<<<<<++++++++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[-<<<<<<<<<<<<<<<<<+
+>>>>>>>>>>>>>>>>>]<[-]<<<<<<[-]<<<[->>>>+>>>+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<
+<[-<<<<<[-<<<<<<++>>>>>>>>>>>>>>+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<<<<<<[-<
+<<<<<<++++++++++++++++>>>>>>>>>>>>>>+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<<]<<<<<[
+-]>[-]<<<<<<>+<[>-]>[<>>>+++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++<<<<<[->>>>>>>>+>+<<<<<<<<<]>>>>>>>>>[-<<<<<<<<<+>>>>>>>>>]<[-<<<>+<[-
+>-]>[<>->]<<>>>]<<<<<<<[->>>>>>>+>+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<[-<<<>+
+<[->-]>[<>->]<<>>>]<<<>+<[[-]>-]>[<<<<+>>>>->]<<<<<>->]<<>+<[<<<<<<<[-]>>>>>>>>-
+]>[<<<<<<<<<<<<<<<<<<<<>+<[<<<[->>>>>>>>>>>>>>>>>>>>>>>>>>>>+>>>>>>+<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+>-]>[<<<<[->>>>>>>>>>>>>>>>>>>>>>>>>+>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<]>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>
+>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<>->]<<>>>>>>>+<[<<<[-
+>>>>>>>>>>>>>>>>>>>>>>+>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>
+>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<
+<<<<<<<<<<<<>-]>[<<<<[->>>>>>>>>>>>>>>>>>>+>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<]>>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>
+>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<>->]<<>>>>>>>>>>>>>>>>>>>[-<<<>+<[->-]>[<>>>>
+>>>>>+<<<<<<<<<>->]<<>>>]>>>>>>>+<[[-<<<+>>>]<<+>>>-]>[<<<<<<<<<<[->>>>>>+<<<<<<
+]>>>>>>>>>>->]<<<<<[->>>+>>>>>>+<<<<<<<<<]>>>>>>>>>[-<<<<<<<<<+>>>>>>>>>]<<<<<++
+++++++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>[->>>>>+>>>+<<<<<<<<]>>>>>>>>[
+-<<<<<<<<+>>>>>>>>]<<<[-<<<<<[->>>>+>>>>+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<
+<]<[->>>>+>>>>>>+<<<<<<<<<<]>>>>>>>>>>[-<<<<<<<<<<+>>>>>>>>>>]<<<<<+++++++++++++
++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[-<<<<<<<<<<<<<<+>>>>>>>>>>>>>>]<[-]<<<<
+<<[-]<<<[->>>>+>>>+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<<[-<<<<<[-<<<++>>>>>>>>>>>
++<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<<<<<<[-<<<<++++++++++++++++>>>>>>>>>>>+<
+<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<<]<<<<<[-]>[-]<<<<<<[-]>[-]<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<[-]>>>[-]>>>[-]>>>[-]>>>>>>>>>>>[->>>>>+>>>>>>>>>+<<<<<<<<<<<<<<]>>
+>>>>>>>>>>>>[-<<<<<<<<<<<<<<+>>>>>>>>>>>>>>]<<<<<<<<<<<<<[->>>>>>>+>>>>>>+<<<<<<
+<<<<<<<]>>>>>>>>>>>>>[-<<<<<<<<<<<<<+>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<>+<[<<<[->>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>+>>>>>>+<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>
+>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>
+>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<>-]>[<<<<[->>>>>>
+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>+>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<>->]<<>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<
+<<>+<[->-]>[<>>>>>>>>>+<<<<<<<<<>->]<<>>>]>>>>>>>+<[[-<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>
+>>>>>>>>>>>>>>>>>>>>>>>>>>>>-]>[<<<<<<<<<<[-<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>
+>>>>>>>>>>>>>>>]>>>>>>>>>>->]<<<[-<<<<<<<<+>>>>>>>>]<<<<<<<<<<<<<[->>>>>>>>+>>>>
+>>+<<<<<<<<<<<<<<]>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<+>>>>>>>>>>>>>>]<<<<<<<<<<<<<[->
+>>>>>>+>>>>>>+<<<<<<<<<<<<<]>>>>>>>>>>>>>[-<<<<<<<<<<<<<+>>>>>>>>>>>>>]<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<>+<[<<<[->>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>+>>>>>>+<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>
+[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>
+>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<>-]>[<<<<[->>>>>>>>>>>>>>>>>>>>>>>>>>>>
+>>>+>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>>
+>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>
+>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<>->]<<>>>>>>>>>>>>>>>>>>
+>>>>>>>>>>>>>[-<<<>+<[->-]>[<>>>>>>>>>+<<<<<<<<<>->]<<>>>]>>>>>>>+<[[-<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>
+>>>>>>>>>>>>>>>>>>>>-]>[<<<<<<<<<<[-<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>]>>>>
+>>>>>>->]<<<<<<<<<<<<<<<<<<<<<<<<->+<[>-]>[<>>>[-]<<<>->]<<>>>>>>>>>>>->]<<<<[-]
+>[-]>[-]<<<<<<<]>>>>>++++++++++++<<<<<<<<[->>>>>>>>-<<<<<<<<]>>>>>>>>>>>++++++++
+++++++++++++++++++++++++<<<>+<[->>>++++++++++++++<<<>-]>[<>->]<<>+<[->>>--<<<>-]
+>[<>->]<<>+<[->>>+<<<>-]>[<>->]<<>+<[->>>+++++++++++++<<<>-]>[<>->]<<>+<[->>>+<<
+<>-]>[<>->]<<>+<[->>>++<<<>-]>[<>->]<<>+<[->>>------------------<<<>-]>[<>->]<<>
++<[->>>-<<<>-]>[<>->]<<>+<[->>>-----<<<>-]>[<>->]<<>+<[->>>-<<<>-]>[<>->]<<>+<[-
+>>>++<<<>-]>[<>->]<<>+<[->>>---<<<>-]>[<>->]<<>>>.[-]<<<[-]<<<<<<<<<<<<<<<<<<<<[
+-]>>>[-]>>>[-]>>>[-]<<<<<<<<<<<<<<<<<<>+<[<<<->+<[>-]>[<>>>[-]<<<>->]<<>>>>-]>[<
+<<<+>>>>->]<<>>>>>>>>>>>>>>>>>>>>>>>>>>>>-]>++++++++++.[-]<<<<<<<<<<<<<<<<<<<<<<
+<>+<[<<<+>>>>-]>[<<<<>+<[->-]>[<+>>>+<<<>->]<<>>>>->]<<>+<[<<<+>>>>-]>[<<<<>+<[-
+>-]>[<+>>>+<<<>->]<<>>>>->]<<>>>>>>>>>>>>>>>>>>>>>-]
@@ -0,0 +1,51 @@
+/* C API for brainfuck-rs, built with `cargo build --release --features ffi`
+ * (link against the resulting libbrainfuck_rs shared library).
+ *
+ * Keep in sync with src/ffi.rs, whose tests check the two agree. */
+
+#ifndef BRAINFUCK_RS_H
+#define BRAINFUCK_RS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define BF_OK 0
+#define BF_ERROR_MISMATCHED_BRACKETS 1
+#define BF_ERROR_IO 2
+#define BF_ERROR_INVALID_ARGUMENT 3
+#define BF_ERROR_INTERNAL 4
+#define BF_ERROR_LIMIT 5
+
+typedef struct bf_error {
+    int32_t code;
+    size_t position; /* Index of the offending command, if any */
+    char message[128]; /* NUL-terminated description */
+} bf_error;
+
+typedef struct bf_program bf_program;
+
+/* Checks and stores a program. Returns NULL and fills in `error` (which may
+ * be NULL) if it can't be run. */
+bf_program *bf_compile(const char *source, size_t source_len, bf_error *error);
+
+/* Runs a compiled program over `input` for at most `max_steps` instructions
+ * (0 for no limit); end of input leaves the cell unchanged. On success
+ * `*output` receives `*output_len` bytes that must be released with
+ * bf_free_output. Returns the error code, BF_ERROR_LIMIT if the program ran
+ * out of steps. */
+int32_t bf_run(const bf_program *program, const uint8_t *input, size_t input_len,
+               uint64_t max_steps, uint8_t **output, size_t *output_len,
+               bf_error *error);
+
+void bf_free(bf_program *program);
+void bf_free_output(uint8_t *output, size_t output_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BRAINFUCK_RS_H */
@@ -0,0 +1,335 @@
+// Abstract interpretation over the IR: follows the program once, tracking
+// where the pointer is and which cells hold a value known at compile time.
+// A loop is entered in the state it could be in on any iteration: the cells
+// its body may change become unknown and everything else is left as it was,
+// since the loop can't change it. That's enough to find loops that can never
+// run, counting loops whose trip count is fixed, and output that's always
+// the same, without running anything.
+
+use crate::ir::{matching_loop_end, Instruction};
+use crate::MEMORY_SIZE;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Something that holds every time the instruction it's attached to runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fact {
+    NeverRuns,      // A loop whose cell is always zero when it's reached
+    RunsTimes(u16), // A loop of adds and moves that always runs this many times
+    Prints(u8),     // An output whose value is always the same
+}
+
+impl fmt::Display for Fact {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fact::NeverRuns => write!(formatter, "loop never runs: its cell is always 0 here"),
+            Fact::RunsTimes(1) => write!(formatter, "loop always runs once"),
+            Fact::RunsTimes(times) => write!(formatter, "loop always runs {} times", times),
+            Fact::Prints(byte) if byte.is_ascii_graphic() || *byte == b' ' => {
+                write!(formatter, "always prints {} ({:?})", byte, *byte as char)
+            }
+            Fact::Prints(byte) => write!(formatter, "always prints {}", byte),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    pointer: Option<usize>,           // Absolute, while it's known
+    cells: [Option<u8>; MEMORY_SIZE], // Known values, wherever the pointer is
+    current: Option<u8>,              // The cell under the pointer, while the pointer isn't known
+}
+
+impl State {
+    fn current(&self) -> Option<u8> {
+        match self.pointer {
+            Some(pointer) => self.cells[pointer],
+            None => self.current,
+        }
+    }
+
+    fn add(&mut self, offset: isize, amount: u8) {
+        match self.pointer {
+            Some(pointer) => {
+                let cell = wrap(pointer as isize + offset);
+                self.cells[cell] = self.cells[cell].map(|value| value.wrapping_add(amount));
+            }
+            // Some cell changed, but there's no telling which
+            None => {
+                self.cells = [None; MEMORY_SIZE];
+                if wrap(offset) == 0 {
+                    self.current = self.current.map(|value| value.wrapping_add(amount));
+                }
+            }
+        }
+    }
+
+    fn set_current(&mut self, value: Option<u8>) {
+        match self.pointer {
+            Some(pointer) => self.cells[pointer] = value,
+            None => {
+                self.cells = [None; MEMORY_SIZE];
+                self.current = value;
+            }
+        }
+    }
+
+    fn forget(&mut self) {
+        self.pointer = None;
+        self.cells = [None; MEMORY_SIZE];
+        self.current = None;
+    }
+}
+
+fn wrap(cell: isize) -> usize {
+    cell.rem_euclid(MEMORY_SIZE as isize) as usize
+}
+
+/// Everything that can be inferred about the program, as (instruction index,
+/// fact) pairs in program order.
+pub fn analyze(program: &[Instruction]) -> Vec<(usize, Fact)> {
+    let mut facts = Vec::new();
+    let mut state = State {
+        pointer: Some(0),
+        cells: [Some(0); MEMORY_SIZE],
+        current: None,
+    };
+    block(program, 0, &mut state, &mut facts);
+    facts
+}
+
+// Follows `program[start..]`, which must end where its enclosing loop does
+fn block(program: &[Instruction], start: usize, state: &mut State, facts: &mut Vec<(usize, Fact)>) {
+    let mut index = start;
+    while index < program.len() {
+        match program[index] {
+            Instruction::Add(amount) => state.add(0, amount),
+            Instruction::AddAt(offset, amount) => state.add(offset, amount),
+            Instruction::Move(amount) => {
+                state.pointer = state.pointer.map(|pointer| wrap(pointer as isize + amount));
+                state.current = None;
+            }
+            Instruction::Output => {
+                if let Some(value) = state.current() {
+                    facts.push((index, Fact::Prints(value)));
+                }
+            }
+            Instruction::Input => state.set_current(None),
+            Instruction::LoopStart => {
+                let end = matching_loop_end(program, index);
+                let body = &program[index + 1..end];
+                let counted = state
+                    .current()
+                    .zip(simple_loop(body))
+                    .and_then(|(value, (step, adds))| Some((trip_count(value, step)?, adds)));
+                match (state.current(), counted) {
+                    (Some(0), _) => facts.push((index, Fact::NeverRuns)),
+                    (_, Some((times, adds))) => {
+                        facts.push((index, Fact::RunsTimes(times)));
+                        for (offset, amount) in adds {
+                            state.add(offset, amount.wrapping_mul(times as u8));
+                        }
+                        state.set_current(Some(0));
+                    }
+                    _ => {
+                        match (state.pointer, written_offsets(body)) {
+                            (Some(pointer), Some(offsets)) => {
+                                for offset in offsets {
+                                    state.cells[wrap(pointer as isize + offset)] = None;
+                                }
+                            }
+                            _ => state.forget(),
+                        }
+                        let mut inside = state.clone();
+                        inside.set_current(None);
+                        block(&program[..end], index + 1, &mut inside, facts);
+                        state.set_current(Some(0));
+                    }
+                }
+                index = end;
+            }
+            // A procedure's body runs wherever it's called from, which this
+            // doesn't follow
+            Instruction::ProcedureStart => index = matching_loop_end(program, index),
+            Instruction::Call | Instruction::SelectTape(_) | Instruction::Fork => state.forget(),
+            Instruction::LoopEnd | Instruction::ProcedureEnd | Instruction::Debug => {}
+        }
+        index += 1;
+    }
+}
+
+/// How far from where it starts the pointer can get. `reach` is the range of
+/// offsets the program gets to before anything that moves the pointer by a
+/// varying amount: a loop that doesn't put it back where it was, a procedure
+/// call, another tape or a fork. When there's nothing like that `bounded` is
+/// set and `reach` covers every cell the program can touch.
+///
+/// Nothing sizes the tape from this yet: it's always the fixed ring of
+/// `MEMORY_SIZE` cells, so the bound is only reported and warned about.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TapeBound {
+    pub reach: (isize, isize),
+    pub bounded: bool,
+    pub wraps_at: Option<usize>, // The instruction that first makes `span` more than the tape has
+}
+
+impl TapeBound {
+    /// Cells between the leftmost and rightmost ones reached.
+    pub fn span(&self) -> usize {
+        (self.reach.1 - self.reach.0) as usize + 1
+    }
+}
+
+pub fn tape_bound(program: &[Instruction]) -> TapeBound {
+    let mut bound = TapeBound {
+        reach: (0, 0),
+        bounded: true,
+        wraps_at: None,
+    };
+    bound.bounded = reach(program, 0, 0, &mut bound).is_some();
+    bound
+}
+
+// Follows the pointer through `program[start..]`, running each loop body
+// once, and widens `bound` to the offsets it gets to. Returns where the
+// pointer ends up, or None from the first thing that doesn't leave it
+// somewhere fixed.
+fn reach(
+    program: &[Instruction],
+    start: usize,
+    mut offset: isize,
+    bound: &mut TapeBound,
+) -> Option<isize> {
+    let mut index = start;
+    while index < program.len() {
+        let cell = match program[index] {
+            Instruction::Move(amount) => {
+                offset += amount;
+                offset
+            }
+            Instruction::AddAt(at, _) => offset + at,
+            Instruction::LoopStart => {
+                let end = matching_loop_end(program, index);
+                if reach(&program[..end], index + 1, offset, bound)? != offset {
+                    return None;
+                }
+                index = end + 1;
+                continue;
+            }
+            // A procedure's body runs wherever it's called from
+            Instruction::ProcedureStart => {
+                index = matching_loop_end(program, index) + 1;
+                continue;
+            }
+            Instruction::Call | Instruction::SelectTape(_) | Instruction::Fork => return None,
+            _ => offset,
+        };
+        bound.reach = (bound.reach.0.min(cell), bound.reach.1.max(cell));
+        if bound.wraps_at.is_none() && bound.span() > MEMORY_SIZE {
+            bound.wraps_at = Some(index);
+        }
+        index += 1;
+    }
+    Some(offset)
+}
+
+/// For a loop body of adds and moves that returns to where it started, how
+/// much each pass adds to the loop's own cell and to each of the others.
+pub fn simple_loop(body: &[Instruction]) -> Option<(u8, Vec<(isize, u8)>)> {
+    let mut adds: Vec<(isize, u8)> = Vec::new();
+    let mut offset = 0;
+    for instruction in body {
+        let (cell, amount) = match *instruction {
+            Instruction::Add(amount) => (offset, amount),
+            Instruction::AddAt(at, amount) => (offset + at, amount),
+            Instruction::Move(amount) => {
+                offset += amount;
+                continue;
+            }
+            _ => return None,
+        };
+        match adds.iter_mut().find(|(at, _)| wrap(*at) == wrap(cell)) {
+            Some((_, total)) => *total = total.wrapping_add(amount),
+            None => adds.push((cell, amount)),
+        }
+    }
+    if wrap(offset) != 0 {
+        return None;
+    }
+    let step = adds.iter().find(|(at, _)| wrap(*at) == 0)?.1;
+    adds.retain(|(at, _)| wrap(*at) != 0);
+    Some((step, adds))
+}
+
+/// How many passes it takes a counter starting at `value` to reach zero when
+/// each adds `step`, if it ever does.
+pub fn trip_count(value: u8, step: u8) -> Option<u16> {
+    (0..256u16).find(|times| value.wrapping_add(step.wrapping_mul(*times as u8)) == 0)
+}
+
+// The offsets from the loop's cell that its body may change, if the body
+// always returns the pointer to where it started
+fn written_offsets(body: &[Instruction]) -> Option<Vec<isize>> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    let mut index = 0;
+    while index < body.len() {
+        match body[index] {
+            Instruction::Add(_) | Instruction::Input => offsets.push(offset),
+            Instruction::AddAt(at, _) => offsets.push(offset + at),
+            Instruction::Move(amount) => offset += amount,
+            Instruction::LoopStart => {
+                let end = matching_loop_end(body, index);
+                let inner = written_offsets(&body[index + 1..end])?;
+                offsets.extend(inner.into_iter().map(|at| offset + at));
+                index = end;
+            }
+            Instruction::ProcedureStart => index = matching_loop_end(body, index),
+            Instruction::Call | Instruction::SelectTape(_) | Instruction::Fork => return None,
+            Instruction::Output
+            | Instruction::LoopEnd
+            | Instruction::ProcedureEnd
+            | Instruction::Debug => {}
+        }
+        index += 1;
+    }
+    (wrap(offset) == 0).then_some(offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse;
+
+    #[test]
+    fn test_analyze() {
+        // Eight passes leave cell 1 at 72: `H`. Cell 2 is never touched
+        let program = parse("++++++++[>+++++++++<-]>.>[-]<[.-]");
+        assert_eq!(
+            analyze(&program),
+            [
+                (8, Fact::RunsTimes(8)),
+                (23, Fact::Prints(72)),
+                (25, Fact::NeverRuns),
+            ]
+        );
+
+        // Input makes the cell unknown, but the loop can't touch cell 1
+        let program = parse(",[>>+<<-]>.>.");
+        assert_eq!(analyze(&program), [(10, Fact::Prints(0))]);
+        assert_eq!(Fact::Prints(72).to_string(), "always prints 72 ('H')");
+    }
+
+    #[test]
+    fn test_tape_bound() {
+        let bound = tape_bound(&parse(">>[-<<+>>]<<<"));
+        assert_eq!(
+            (bound.reach, bound.bounded, bound.span()),
+            ((-1, 2), true, 4)
+        );
+        // Where the inner loop leaves the pointer depends on the tape
+        let bound = tape_bound(&parse("+[>+[-<]>>]"));
+        assert_eq!((bound.reach, bound.bounded), ((0, 1), false));
+        assert_eq!(tape_bound(&parse(&">".repeat(300))).wraps_at, Some(255));
+    }
+}
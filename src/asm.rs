@@ -0,0 +1,192 @@
+// `brainfuck-rs asm`: a small macro language over named cells, compiled to
+// brainfuck through the IR. One statement per line, `;` starts a comment:
+//
+//     cell counter        ; give the next free cell a name
+//     set counter 3       ; clear it, then add 3 (numbers or 'c' literals)
+//     add counter -1      ; add a (wrapping) amount
+//     while counter       ; loop until the cell is zero
+//       print counter     ; output the cell
+//       read counter      ; input into the cell
+//     end
+
+use crate::ir::{self, Instruction, Program};
+use crate::optimize::fold_runs;
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+struct Assembler {
+    cells: HashMap<String, usize>,
+    program: Program,
+    pointer: usize,             // Cell the generated code is at
+    loops: Vec<(usize, usize)>, // Line and cell of each open `while`
+}
+
+impl Assembler {
+    fn cell(&self, name: &str) -> Result<usize, String> {
+        self.cells
+            .get(name)
+            .copied()
+            .ok_or(format!("unknown cell '{}'", name))
+    }
+
+    fn move_to(&mut self, cell: usize) {
+        self.program
+            .push(Instruction::Move(cell as isize - self.pointer as isize));
+        self.pointer = cell;
+    }
+
+    fn statement(&mut self, line: usize, words: &[&str]) -> Result<(), String> {
+        match words {
+            ["cell", name] => {
+                if self.cells.contains_key(*name) {
+                    return Err(format!("cell '{}' is already declared", name));
+                }
+                if self.cells.len() == crate::MEMORY_SIZE {
+                    return Err("out of cells".to_string());
+                }
+                self.cells.insert(name.to_string(), self.cells.len());
+            }
+            ["set", name, value] => {
+                let (cell, value) = (self.cell(name)?, parse_value(value)?);
+                self.move_to(cell);
+                self.program.extend([
+                    Instruction::LoopStart,
+                    Instruction::Add(255),
+                    Instruction::LoopEnd,
+                    Instruction::Add(value),
+                ]);
+            }
+            ["add", name, value] => {
+                let (cell, value) = (self.cell(name)?, parse_value(value)?);
+                self.move_to(cell);
+                self.program.push(Instruction::Add(value));
+            }
+            ["print", name] => {
+                self.move_to(self.cell(name)?);
+                self.program.push(Instruction::Output);
+            }
+            ["read", name] => {
+                self.move_to(self.cell(name)?);
+                self.program.push(Instruction::Input);
+            }
+            ["while", name] => {
+                let cell = self.cell(name)?;
+                self.move_to(cell);
+                self.program.push(Instruction::LoopStart);
+                self.loops.push((line, cell));
+            }
+            ["end"] => {
+                let (_, cell) = self.loops.pop().ok_or("'end' without 'while'")?;
+                // Back to the loop's cell, so the test (and the code after the
+                // loop) sees the pointer where it expects it
+                self.move_to(cell);
+                self.program.push(Instruction::LoopEnd);
+            }
+            [command, ..] => {
+                let known = ["cell", "set", "add", "print", "read", "while", "end"];
+                if known.contains(command) {
+                    return Err(format!("wrong number of arguments to '{}'", command));
+                }
+                return Err(format!("unknown statement '{}'", command));
+            }
+            [] => {}
+        }
+        Ok(())
+    }
+}
+
+// A wrapping amount: a decimal number or a character literal like 'A'
+fn parse_value(value: &str) -> Result<u8, String> {
+    let mut characters = value.chars();
+    if let (Some('\''), Some(character), Some('\''), None) = (
+        characters.next(),
+        characters.next(),
+        characters.next(),
+        characters.next(),
+    ) {
+        return u8::try_from(character).map_err(|_| format!("'{}' is not a byte", character));
+    }
+    let number: i64 = value
+        .parse()
+        .map_err(|_| format!("invalid value '{}'", value))?;
+    Ok(number.rem_euclid(256) as u8)
+}
+
+/// Compiles a program in the macro language to brainfuck source.
+pub fn assemble(source: &str) -> Result<String, AsmError> {
+    let mut assembler = Assembler {
+        cells: HashMap::new(),
+        program: Program::new(),
+        pointer: 0,
+        loops: Vec::new(),
+    };
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split(';').next().unwrap_or("");
+        let words: Vec<&str> = code.split_whitespace().collect();
+        assembler
+            .statement(index + 1, &words)
+            .map_err(|message| AsmError {
+                line: index + 1,
+                message,
+            })?;
+    }
+    if let Some((line, _)) = assembler.loops.last() {
+        return Err(AsmError {
+            line: *line,
+            message: "'while' without 'end'".to_string(),
+        });
+    }
+    Ok(ir::to_source(&fold_runs(&assembler.program)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzz::execute;
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            cell count ; loop counter
+            cell letter
+            set count 3
+            set letter 'a'
+            while count
+              print letter
+              add letter 1
+              add count -1
+            end
+            print count
+        ";
+        let program = assemble(source).unwrap();
+        let outcome = execute(&program, b"", 10_000).unwrap();
+        assert_eq!(outcome.output, b"abc\0");
+        assert_eq!(
+            assemble("cell x\ncell y\nadd y 2\nprint y").unwrap(),
+            ">++."
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = assemble("cell x\nadd y 1").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: unknown cell 'y'");
+        assert_eq!(assemble("cell x\nwhile x").unwrap_err().line, 2);
+        assert_eq!(
+            assemble("end").unwrap_err().message,
+            "'end' without 'while'"
+        );
+        assert!(assemble("cell x\nset x 1 2").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+// Non-blocking execution for embedding in async services. The traits here
+// mirror the small part of tokio's `AsyncRead`/`AsyncWrite` that we need, so
+// any runtime's streams can be plugged in with a thin adapter and the crate
+// itself stays free of runtime dependencies.
+
+use crate::{Error, Extensions, Interpreter, StepResult};
+use std::future::Future;
+use std::io;
+
+/// Source of the bytes consumed by `,`.
+pub trait AsyncInput {
+    /// Reads one byte, or `None` at end of input.
+    fn read_byte(&mut self) -> impl Future<Output = io::Result<Option<u8>>>;
+}
+
+/// Destination of the bytes written by `.`.
+pub trait AsyncOutput {
+    fn write_all(&mut self, bytes: &[u8]) -> impl Future<Output = io::Result<()>>;
+    fn flush(&mut self) -> impl Future<Output = io::Result<()>>;
+}
+
+impl AsyncInput for &[u8] {
+    async fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let Some((byte, rest)) = self.split_first() else {
+            return Ok(None);
+        };
+        *self = rest;
+        Ok(Some(*byte))
+    }
+}
+
+impl AsyncOutput for Vec<u8> {
+    async fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Like `run`, but awaits on I/O instead of blocking the thread on `,`.
+pub async fn run_async<I: AsyncInput, O: AsyncOutput>(
+    source_code: &str,
+    extensions: Extensions,
+    input: &mut I,
+    output: &mut O,
+) -> Result<(), Error> {
+    let mut interpreter = Interpreter::new(source_code, extensions)?;
+    loop {
+        match interpreter.step()? {
+            StepResult::Output(byte) => {
+                let mut encoded = [0; 4];
+                let encoded = (byte as char).encode_utf8(&mut encoded);
+                output.write_all(encoded.as_bytes()).await?;
+            }
+            StepResult::NeedsInput => {
+                // Let the reader see everything printed so far, e.g. a prompt
+                output.flush().await?;
+                interpreter.provide_input(input.read_byte().await?);
+            }
+            StepResult::Continue => {}
+            StepResult::Halted => break,
+        }
+    }
+    output.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    // The in-memory I/O never pends, so polling once is enough
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut context = Context::from_waker(Waker::noop());
+        match pin!(future).poll(&mut context) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future was not ready"),
+        }
+    }
+
+    #[test]
+    fn test_run_async() {
+        let mut input: &[u8] = b"hi";
+        let mut output = Vec::new();
+        let result = block_on(run_async(
+            ",.,.",
+            Extensions::default(),
+            &mut input,
+            &mut output,
+        ));
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, b"hi");
+    }
+}
@@ -0,0 +1,204 @@
+// `brainfuck-rs run-all`: every program in a directory, each on its own
+// interpreter and under its own `Sandbox`, spread over a few threads. Meant
+// for grading a pile of submissions, so one program failing, going over a
+// limit or even panicking only affects its own row. Programs are the files
+// whose extension belongs to a dialect; `name.in` next to one is its input,
+// and `,` gets end of input after that rather than waiting on stdin.
+
+use crate::dialect;
+use crate::limits::{Limits, Sandbox};
+use crate::report::base64;
+use crate::{json, run, sanitize_input, Extensions, ProgramInput, EXIT_RUNTIME_ERROR};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{io, panic, thread};
+
+/// How one program's run went.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub name: String,
+    pub error: Option<String>,
+    pub exit_code: i32,
+    pub output: Vec<u8>,
+    pub steps: u64,
+    pub tape_bytes: usize,
+    pub elapsed: Duration,
+}
+
+impl Outcome {
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![
+            ("name", json::string(&self.name)),
+            ("success", self.error.is_none().to_string()),
+            ("exit_code", self.exit_code.to_string()),
+        ];
+        if let Some(error) = &self.error {
+            fields.push(("error", json::string(error)));
+        }
+        match std::str::from_utf8(&self.output) {
+            Ok(text) => fields.push(("output", json::string(text))),
+            Err(_) => fields.push(("output_base64", json::string(&base64(&self.output)))),
+        }
+        fields.extend([
+            ("steps", self.steps.to_string()),
+            ("tape_bytes", self.tape_bytes.to_string()),
+            ("time_ms", json::number(self.elapsed.as_secs_f64() * 1000.0)),
+        ]);
+        json::object(&fields)
+    }
+}
+
+/// The programs in `dir`, sorted by file name.
+pub fn find_programs(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut programs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && dialect::from_path(&path).is_some() {
+            programs.push(path);
+        }
+    }
+    programs.sort();
+    Ok(programs)
+}
+
+/// Runs the program at `path` to completion, or until it goes over `limits`.
+pub fn run_program(path: &Path, extensions: Extensions, limits: Limits) -> Outcome {
+    let start = Instant::now();
+    let mut outcome = Outcome {
+        name: path
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned()),
+        error: None,
+        exit_code: 0,
+        output: Vec::new(),
+        steps: 0,
+        tape_bytes: 0,
+        elapsed: Duration::ZERO,
+    };
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|error| format!("could not read {}: {}", path.display(), error))
+    };
+    let loaded = read(path).and_then(|source| {
+        let input = path.with_extension("in");
+        let input = if input.is_file() {
+            read(&input)?
+        } else {
+            Vec::new()
+        };
+        Ok((String::from_utf8_lossy(&source).into_owned(), input))
+    });
+    let (source, input) = match loaded {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            outcome.error = Some(message);
+            outcome.exit_code = EXIT_RUNTIME_ERROR;
+            return outcome;
+        }
+    };
+    let dialect = dialect::from_path(path).unwrap_or(&dialect::Brainfuck);
+    let extensions = dialect.extensions(extensions);
+    let buffer = sanitize_input(&dialect.translate(&source), extensions);
+    let mut input = ProgramInput::new(&input).with_reader(Box::new(io::empty()));
+    let mut sandbox = Sandbox::new(limits);
+    // Nothing a program does should panic, but if it does, it's that
+    // program's failure and not the batch's
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        run(
+            &buffer,
+            extensions,
+            &mut input,
+            &mut outcome.output,
+            &mut sandbox,
+        )
+    }));
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(error)) => {
+            outcome.exit_code = error.exit_code();
+            outcome.error = Some(error.to_string());
+        }
+        Err(_) => {
+            outcome.exit_code = EXIT_RUNTIME_ERROR;
+            outcome.error = Some("the interpreter panicked".to_string());
+        }
+    }
+    outcome.steps = sandbox.steps;
+    outcome.tape_bytes = sandbox.tape_bytes;
+    outcome.elapsed = start.elapsed();
+    outcome
+}
+
+/// Runs every program on `jobs` threads (at least one), returning their
+/// outcomes in the order of `programs`.
+pub fn run_all(
+    programs: &[PathBuf],
+    extensions: Extensions,
+    limits: Limits,
+    jobs: usize,
+) -> Vec<Outcome> {
+    let next = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<Option<Outcome>>> = Mutex::new(programs.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, programs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = programs.get(index) else {
+                    break;
+                };
+                let outcome = run_program(path, extensions, limits);
+                outcomes
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())[index] = Some(outcome);
+            });
+        }
+    });
+    outcomes
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .into_iter()
+        .map(|outcome| outcome.expect("every program is run once"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EXIT_PARSE_ERROR, EXIT_RESOURCE_LIMIT};
+
+    #[test]
+    fn test_run_all() {
+        let dir = std::env::temp_dir().join(format!("bf-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("echo.bf"), ",[.[-],]").unwrap();
+        std::fs::write(dir.join("echo.in"), "hi").unwrap();
+        std::fs::write(dir.join("forever.b"), "+[]").unwrap();
+        std::fs::write(dir.join("broken.bf"), "+]").unwrap();
+        std::fs::write(dir.join("notes.txt"), "+.").unwrap();
+
+        let programs = find_programs(&dir).unwrap();
+        let limits = Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        };
+        let outcomes = run_all(&programs, Extensions::default(), limits, 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = outcomes
+            .iter()
+            .map(|outcome| outcome.name.as_str())
+            .collect();
+        assert_eq!(names, ["broken.bf", "echo.bf", "forever.b"]);
+        assert_eq!(outcomes[0].exit_code, EXIT_PARSE_ERROR);
+        assert_eq!(
+            (outcomes[1].error.as_deref(), &outcomes[1].output[..]),
+            (None, &b"hi"[..])
+        );
+        assert_eq!(outcomes[2].exit_code, EXIT_RESOURCE_LIMIT);
+        assert_eq!(
+            outcomes[2].error.as_deref(),
+            Some("step limit of 1000 exceeded")
+        );
+    }
+}
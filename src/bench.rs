@@ -0,0 +1,140 @@
+// Measurements behind `brainfuck-rs bench`: how long a program takes, how
+// many instructions it executes and how much of the tape it touches.
+
+use crate::ir::{self, Program};
+use crate::{optimize, Error, Extensions, Interpreter, StepResult, MEMORY_SIZE};
+use std::time::{Duration, Instant};
+
+/// How the program is executed, so backends can be compared.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Backend {
+    #[default]
+    Interpreter,
+    Optimizer, // The interpreter running the output of `optimize`
+}
+
+impl Backend {
+    pub fn by_name(name: &str) -> Option<Backend> {
+        match name {
+            "interpreter" => Some(Backend::Interpreter),
+            "optimizer" => Some(Backend::Optimizer),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Interpreter => "interpreter",
+            Backend::Optimizer => "optimizer",
+        }
+    }
+
+    /// The program this backend runs for sanitized source.
+    pub fn compile(&self, source: &str) -> Result<Program, Error> {
+        match self {
+            Backend::Interpreter => Ok(ir::parse(source)),
+            Backend::Optimizer => {
+                crate::generate_jump_table(source)?;
+                Ok(optimize::optimize(&ir::parse(source)).0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub elapsed: Duration,
+    pub instructions: u64,
+    pub cells_touched: usize, // Distinct cells the pointer visited
+}
+
+impl Measurement {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Runs sanitized source once over `input`, discarding its output.
+pub fn measure(
+    source: &str,
+    extensions: Extensions,
+    input: &[u8],
+    backend: Backend,
+) -> Result<Measurement, Error> {
+    let program = backend.compile(source)?;
+    let started = Instant::now();
+    let mut interpreter = Interpreter::from_program(program, extensions)?;
+    let mut input = input.iter().copied();
+    let mut touched = [false; MEMORY_SIZE];
+    let mut instructions = 0;
+    loop {
+        touched[interpreter.memory_pointer()] = true;
+        match interpreter.step()? {
+            StepResult::NeedsInput => interpreter.provide_input(input.next()),
+            StepResult::Continue | StepResult::Output(_) => instructions += 1,
+            StepResult::Halted => break,
+        }
+    }
+    Ok(Measurement {
+        elapsed: started.elapsed(),
+        instructions,
+        cells_touched: touched.iter().filter(|touched| **touched).count(),
+    })
+}
+
+/// The mean and (population) standard deviation of some samples.
+pub fn mean_and_stddev(samples: &[f64]) -> (f64, f64) {
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+    let count = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / count;
+    let variance = samples
+        .iter()
+        .map(|sample| (sample - mean).powi(2))
+        .sum::<f64>()
+        / count;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EofMode;
+
+    #[test]
+    fn test_measure() {
+        let measurement = measure(
+            "+>+>,<[-]",
+            Extensions::default(),
+            b"",
+            Backend::Interpreter,
+        )
+        .unwrap();
+        assert_eq!(measurement.instructions, 9);
+        assert_eq!(measurement.cells_touched, 3);
+        let unoptimized =
+            measure("[+++]+>>", Extensions::default(), b"", Backend::Interpreter).unwrap();
+        assert_eq!(unoptimized.instructions, 4);
+        // The dead loop goes and `>>` becomes a single move
+        let optimized =
+            measure("[+++]+>>", Extensions::default(), b"", Backend::Optimizer).unwrap();
+        assert_eq!(optimized.instructions, 2);
+        // Reading 255 at the end of input keeps the loop going
+        let extensions = Extensions {
+            eof: EofMode::Max,
+            ..Extensions::default()
+        };
+        let measurement = measure(",[-]", extensions, b"", Backend::Interpreter).unwrap();
+        assert_eq!(measurement.instructions, 512);
+    }
+
+    #[test]
+    fn test_mean_and_stddev() {
+        assert_eq!(
+            mean_and_stddev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]),
+            (5.0, 2.0)
+        );
+        assert_eq!(mean_and_stddev(&[]), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,427 @@
+// Static analysis for `brainfuck-rs check`. Nothing here executes the
+// program; every diagnostic is derived from the source alone.
+
+use crate::analysis::tape_bound;
+use crate::ir::{self, matching_loop_end, Instruction};
+use crate::{is_command, json, Extensions, MEMORY_SIZE};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str, // Stable identifier for editor integrations
+    pub index: usize,       // Index of the offending command in the sanitized source
+    pub message: String,
+    pub repair: Option<Repair>, // A likely fix, for unbalanced brackets
+}
+
+/// A one-bracket edit that balances every loop. Indices are into the
+/// sanitized source; `before` may be its length, for the very end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repair {
+    Close { opened: usize, before: usize }, // Insert a `]` for the `[` at `opened`
+    Delete { index: usize, bracket: char },
+}
+
+impl Repair {
+    /// The suggestion, given the (line, column) of each command.
+    pub fn describe(&self, positions: &[(usize, usize)]) -> String {
+        let at = |index: usize| match positions.get(index) {
+            Some((line, column)) => format!("line {}, col {}", line, column),
+            None => "the end".to_string(),
+        };
+        let before = |index: usize| match index < positions.len() {
+            true => format!("before {}", at(index)),
+            false => "at the end".to_string(),
+        };
+        match *self {
+            Repair::Close {
+                opened,
+                before: index,
+            } => format!(
+                "did you mean to close the loop opened at {} {}?",
+                at(opened),
+                before(index)
+            ),
+            Repair::Delete { index, bracket } => {
+                format!("did you mean to delete the `{}` at {}?", bracket, at(index))
+            }
+        }
+    }
+}
+
+/// Analyzes sanitized source, returning diagnostics in source order.
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = unbalanced_brackets(source);
+    if let Some(first) = diagnostics
+        .iter_mut()
+        .min_by_key(|diagnostic| diagnostic.index)
+    {
+        first.repair = repair(source);
+    }
+    if diagnostics.is_empty() {
+        let program = ir::parse(source);
+        diagnostics.extend(loops_without_progress(&program));
+        diagnostics.extend(straight_line_issues(&program));
+        diagnostics.extend(tape_wraps(&program));
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.index);
+    diagnostics
+}
+
+fn unbalanced_brackets(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut bracket_stack: Vec<(usize, char)> = Vec::new();
+    for (index, character) in source.chars().enumerate() {
+        let opening = match character {
+            '[' | '(' => {
+                bracket_stack.push((index, character));
+                continue;
+            }
+            ']' => '[',
+            ')' => '(', // pbrain procedures nest with loops
+            _ => continue,
+        };
+        if bracket_stack.last().map(|(_, open)| *open) == Some(opening) {
+            bracket_stack.pop();
+        } else {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                code: "unbalanced-bracket",
+                index,
+                message: "this closing bracket has no matching opening bracket".to_string(),
+                repair: None,
+            });
+        }
+    }
+    diagnostics.extend(bracket_stack.into_iter().map(|(index, _)| Diagnostic {
+        severity: Severity::Error,
+        code: "unbalanced-bracket",
+        index,
+        message: "this opening bracket is never closed".to_string(),
+        repair: None,
+    }));
+    diagnostics
+}
+
+/// Looks for a single bracket to add or remove that would balance the loops.
+/// A missing `]` goes after the first stretch that reads like a finished loop
+/// body, one that returns the pointer and changes the loop's cell, and failing
+/// that the `[` left open is taken to be a typo. An extra `]` is always best
+/// deleted: wherever a `[` could go to match it, deleting it works too. Each
+/// edit is checked against the depth after every command, so this is linear.
+/// Programs with pbrain procedures don't get a suggestion.
+pub fn repair(source: &str) -> Option<Repair> {
+    let commands: Vec<char> = source.chars().collect();
+    if commands.iter().any(|command| matches!(command, '(' | ')')) {
+        return None;
+    }
+    // depth[k] is how many loops are open after the first k commands, and
+    // `lowest_before`/`lowest_after` the least of depth[..=k] and depth[k..]
+    let mut depth = vec![0isize];
+    for command in &commands {
+        let change = match command {
+            '[' => 1,
+            ']' => -1,
+            _ => 0,
+        };
+        depth.push(depth[depth.len() - 1] + change);
+    }
+    let mut lowest_before = depth.clone();
+    for k in 1..depth.len() {
+        lowest_before[k] = lowest_before[k].min(lowest_before[k - 1]);
+    }
+    let mut lowest_after = depth.clone();
+    for k in (0..depth.len() - 1).rev() {
+        lowest_after[k] = lowest_after[k].min(lowest_after[k + 1]);
+    }
+    let total = depth[commands.len()];
+    // Whether adding `change` to the depth from command `from` on, with
+    // everything up to `until` left alone, balances it
+    let balances = |until: usize, from: usize, change: isize| {
+        total + change == 0 && lowest_before[until] >= 0 && lowest_after[from] + change >= 0
+    };
+    let mut open = Vec::new();
+    for index in 0..=commands.len() {
+        if let Some(&opened) = open.last() {
+            if balances(index, index, -1) && settles(&commands[opened + 1..index]) {
+                return Some(Repair::Close {
+                    opened,
+                    before: index,
+                });
+            }
+        }
+        match commands.get(index) {
+            Some('[') => open.push(index),
+            Some(']') if open.pop().is_none() => {
+                return balances(index, index + 1, 1).then_some(Repair::Delete {
+                    index,
+                    bracket: ']',
+                });
+            }
+            _ => {}
+        }
+    }
+    let index = open.pop()?;
+    balances(index, index + 1, -1).then_some(Repair::Delete {
+        index,
+        bracket: '[',
+    })
+}
+
+// Whether a loop body puts the pointer back and changes the loop's own cell,
+// as one that's meant to end does
+fn settles(body: &[char]) -> bool {
+    let mut offset = 0;
+    let mut changes_cell = false;
+    for command in body {
+        match command {
+            '>' => offset += 1,
+            '<' => offset -= 1,
+            '+' | '-' | ',' | '[' if offset == 0 => changes_cell = true,
+            _ => {}
+        }
+    }
+    offset == 0 && changes_cell
+}
+
+// Loops with a straight-line body that never touches the cell they test can
+// only terminate if they are never entered.
+fn loops_without_progress(program: &[Instruction]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (index, instruction) in program.iter().enumerate() {
+        if *instruction != Instruction::LoopStart {
+            continue;
+        }
+        let body = &program[index + 1..matching_loop_end(program, index)];
+        let mut offset: isize = 0;
+        let mut changes_cell = false;
+        for instruction in body {
+            match instruction {
+                Instruction::Move(amount) => offset += amount,
+                Instruction::Add(_) | Instruction::Input if offset == 0 => changes_cell = true,
+                Instruction::AddAt(at, _) if offset + at == 0 => changes_cell = true,
+                // Too hard to reason about
+                Instruction::LoopStart
+                | Instruction::Call
+                | Instruction::SelectTape(_)
+                | Instruction::Fork => changes_cell = true,
+                _ => {}
+            }
+        }
+        if offset == 0 && !changes_cell {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                code: "infinite-loop",
+                index,
+                message: "this loop never changes the cell it tests, so it never ends once entered"
+                    .to_string(),
+                repair: None,
+            });
+        }
+    }
+    diagnostics
+}
+
+// Follows the program from the start for as long as every cell value and the
+// pointer are known, reporting underflows and moves off the tape.
+fn straight_line_issues(program: &[Instruction]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut cells: HashMap<isize, Option<u8>> = HashMap::new(); // Missing cells are zero
+    let mut pointer: isize = 0;
+    let mut index = 0;
+    while index < program.len() {
+        let cell = cells.get(&pointer).copied().unwrap_or(Some(0));
+        match program[index] {
+            Instruction::Add(amount) => {
+                if cell == Some(0) && amount == u8::MAX {
+                    diagnostics.push(Diagnostic {
+                        severity: Severity::Warning,
+                        code: "underflow",
+                        index,
+                        message: format!(
+                            "cell {} is always zero here, so this underflows",
+                            pointer
+                        ),
+                        repair: None,
+                    });
+                }
+                cells.insert(pointer, cell.map(|value| value.wrapping_add(amount)));
+            }
+            Instruction::AddAt(at, amount) => {
+                let cell = cells.get(&(pointer + at)).copied().unwrap_or(Some(0));
+                cells.insert(pointer + at, cell.map(|value| value.wrapping_add(amount)));
+            }
+            Instruction::Move(amount) => {
+                pointer += amount;
+                if pointer < 0 || pointer >= MEMORY_SIZE as isize {
+                    diagnostics.push(Diagnostic {
+                        severity: Severity::Warning,
+                        code: "tape-bounds",
+                        index,
+                        message: format!(
+                            "the pointer always leaves the {}-cell tape here",
+                            MEMORY_SIZE
+                        ),
+                        repair: None,
+                    });
+                    break;
+                }
+            }
+            Instruction::Input => {
+                cells.insert(pointer, None);
+            }
+            Instruction::LoopStart if cell == Some(0) => index = matching_loop_end(program, index),
+            // Defining a procedure doesn't run it
+            Instruction::ProcedureStart => index = matching_loop_end(program, index),
+            // From here on the state depends on the loop or procedure, or
+            // is on a tape or thread this doesn't track
+            Instruction::LoopStart
+            | Instruction::Call
+            | Instruction::SelectTape(_)
+            | Instruction::Fork => break,
+            Instruction::Output
+            | Instruction::LoopEnd
+            | Instruction::Debug
+            | Instruction::ProcedureEnd => {}
+        }
+        index += 1;
+    }
+    diagnostics
+}
+
+// Programs whose pointer can get further apart than the tape is long, which
+// only work if they mean the tape to wrap around
+fn tape_wraps(program: &[Instruction]) -> Option<Diagnostic> {
+    Some(Diagnostic {
+        severity: Severity::Warning,
+        code: "tape-wraps",
+        index: tape_bound(program).wraps_at?,
+        message: format!(
+            "by here the pointer can span more than the {}-cell tape, so it wraps around",
+            MEMORY_SIZE
+        ),
+        repair: None,
+    })
+}
+
+/// One-based (line, column) of every brainfuck command in the unsanitized
+/// source, indexed like the sanitized source.
+pub fn command_positions(source: &str, extensions: Extensions) -> Vec<(usize, usize)> {
+    let mut positions = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    for character in source.chars() {
+        if is_command(character, extensions) {
+            positions.push((line, column));
+        }
+        if character == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    positions
+}
+
+/// The diagnostics as a JSON array, each with its line and column.
+pub fn to_json(diagnostics: &[Diagnostic], positions: &[(usize, usize)]) -> String {
+    let diagnostics: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let (line, column) = positions[diagnostic.index];
+            let mut fields = vec![
+                ("severity", json::string(diagnostic.severity.name())),
+                ("code", json::string(diagnostic.code)),
+                ("index", diagnostic.index.to_string()),
+                ("line", line.to_string()),
+                ("column", column.to_string()),
+                ("message", json::string(&diagnostic.message)),
+            ];
+            if let Some(repair) = diagnostic.repair {
+                fields.push(("help", json::string(&repair.describe(positions))));
+            }
+            json::object(&fields)
+        })
+        .collect();
+    json::array(&diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let codes = |source: &str| -> Vec<&'static str> {
+            check(source)
+                .iter()
+                .map(|diagnostic| diagnostic.code)
+                .collect()
+        };
+        assert_eq!(codes("[]]["), ["unbalanced-bracket", "unbalanced-bracket"]);
+        assert_eq!(codes("+[>+<]"), ["infinite-loop"]);
+        assert_eq!(codes("-"), ["underflow"]);
+        assert_eq!(codes("<"), ["tape-bounds"]);
+        assert!(codes("++[>+<-],[-]-").is_empty());
+        assert_eq!(
+            codes(&format!("[{}+{}-]", ">".repeat(256), "<".repeat(256))),
+            ["tape-wraps"]
+        );
+    }
+
+    #[test]
+    fn test_repair() {
+        let help = |source: &str| {
+            let diagnostics = check(source);
+            let positions: Vec<(usize, usize)> = (1..=source.len()).map(|at| (1, at)).collect();
+            diagnostics[0]
+                .repair
+                .map(|repair| repair.describe(&positions))
+        };
+        assert_eq!(
+            help("++[>+<-."),
+            Some(
+                "did you mean to close the loop opened at line 1, col 3 before line 1, col 8?"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            help("+[-"),
+            Some("did you mean to close the loop opened at line 1, col 2 at the end?".to_string())
+        );
+        assert_eq!(
+            help("+[>,"),
+            Some("did you mean to delete the `[` at line 1, col 2?".to_string())
+        );
+        assert_eq!(
+            help("+[-]]>"),
+            Some("did you mean to delete the `]` at line 1, col 5?".to_string())
+        );
+        // No one bracket fixes this
+        assert_eq!(help("]]["), None);
+    }
+
+    #[test]
+    fn test_command_positions() {
+        assert_eq!(
+            command_positions("a+\n [-", Extensions::default()),
+            [(1, 2), (2, 2), (2, 3)]
+        );
+    }
+}
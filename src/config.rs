@@ -0,0 +1,363 @@
+// Defaults for the CLI, read from `~/.config/brainfuck-rs.toml` (or
+// `$XDG_CONFIG_HOME`, or wherever `BRAINFUCK_RS_CONFIG` points). Flags given
+// on the command line always win over the file.
+//
+// Only the subset of TOML a flat settings file needs is understood: one
+// `key = value` per line, with strings, integers, booleans and arrays of
+// those, and `#` comments. Tables aren't, since there's nothing to nest.
+
+use crate::debugger::DEFAULT_JOURNAL_SIZE;
+use crate::output::Encoding;
+use crate::{EofMode, Extensions, IoMode, MEMORY_SIZE};
+use std::path::PathBuf;
+
+pub const FILE_NAME: &str = "brainfuck-rs.toml";
+
+/// What `brainfuck-rs config init` writes: every setting, commented out at
+/// its default.
+pub const TEMPLATE: &str = r#"# Defaults for brainfuck-rs. Flags given on the command line override these.
+
+# Language extensions to enable, as for --extensions: "debug", "pbrain",
+# "dualtape"
+# extensions = []
+
+# How `.` and `,` exchange cells, as for --io-mode: "bytes" or "numeric"
+# io_mode = "bytes"
+
+# What `,` does to the cell at end of input, as for --eof: "unchanged",
+# "zero" or "max" (255)
+# eof = "unchanged"
+
+# The tape is a ring of 256 cells of 8 bits each. Other sizes and widths
+# aren't supported yet; these are here so a file that sets them says so
+# rather than being ignored
+# tape_size = 256
+# cell_width = 8
+
+# How `run` treats the program, as for --optimization: 0 runs it as written,
+# 1 runs the output of `brainfuck-rs optimize`. Runs that need to know which
+# command is which (coverage, --snapshot, --max-steps and the like) stay at 0
+# optimization = 0
+
+# How printed bytes become output, as for --output-encoding:
+# "latin1", "utf8" or "raw"
+# output_encoding = "latin1"
+
+# Flush output after this many bytes, as for --flush-every (0 flushes only
+# when the program reads input and when it ends)
+# flush_every = 0
+
+# Steps `brainfuck-rs debug` can go back, as for --journal
+# journal = 10000
+"#;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub extensions: Extensions, // Including the I/O mode and EOF behavior
+    pub output_encoding: Encoding,
+    pub flush_every: Option<usize>,
+    pub journal: usize,
+    pub optimization: u8,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            extensions: Extensions::default(),
+            output_encoding: Encoding::default(),
+            flush_every: None,
+            journal: DEFAULT_JOURNAL_SIZE,
+            optimization: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+/// Where the config file is looked for.
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("BRAINFUCK_RS_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let directory = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(directory) if !directory.is_empty() => PathBuf::from(directory),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(directory.join(FILE_NAME))
+}
+
+/// Reads the config file, if there is one.
+pub fn load() -> Result<Config, String> {
+    let Some(path) = default_path() else {
+        return Ok(Config::default());
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(text) => parse(&text).map_err(|error| format!("{}:{}", path.display(), error)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(error) => Err(format!("could not read {}: {}", path.display(), error)),
+    }
+}
+
+/// Parses a config file. Errors start with the line number they're on.
+pub fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    for (number, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        setting(&mut config, line).map_err(|error| format!("{}: {}", number + 1, error))?;
+    }
+    Ok(config)
+}
+
+fn setting(config: &mut Config, line: &str) -> Result<(), String> {
+    if line.starts_with('[') {
+        return Err("tables aren't supported".to_string());
+    }
+    let (key, value) = line
+        .split_once('=')
+        .ok_or("expected `key = value`".to_string())?;
+    let key = key.trim();
+    let value = parse_value(&mut value.trim().chars().peekable())?;
+    let mismatch = |expected: &str, value: &Value| {
+        format!("{} should be {}, not {}", key, expected, value.type_name())
+    };
+    match (key, &value) {
+        ("extensions", Value::Array(names)) => {
+            config.extensions = config.extensions.io_only();
+            for name in names {
+                let Value::String(name) = name else {
+                    return Err(mismatch("an array of strings", name));
+                };
+                if !config.extensions.enable(name) {
+                    return Err(format!("unknown extension '{}'", name));
+                }
+            }
+        }
+        ("io_mode", Value::String(name)) => {
+            config.extensions.io_mode =
+                IoMode::by_name(name).ok_or(format!("unknown I/O mode '{}'", name))?;
+        }
+        ("eof", Value::String(name)) => {
+            config.extensions.eof =
+                EofMode::by_name(name).ok_or(format!("unknown EOF mode '{}'", name))?;
+        }
+        ("tape_size", Value::Integer(cells)) => {
+            if *cells != MEMORY_SIZE as i64 {
+                return Err(format!(
+                    "tape_size = {} is unsupported: the tape is always a ring of {} cells",
+                    cells, MEMORY_SIZE
+                ));
+            }
+        }
+        ("cell_width", Value::Integer(bits)) => {
+            if *bits != 8 {
+                return Err(format!(
+                    "cell_width = {} is unsupported: cells are always 8 bits",
+                    bits
+                ));
+            }
+        }
+        ("optimization", Value::Integer(level)) => {
+            config.optimization = match *level {
+                0 | 1 => *level as u8,
+                _ => return Err("optimization should be 0 or 1".to_string()),
+            };
+        }
+        ("output_encoding", Value::String(name)) => {
+            config.output_encoding =
+                Encoding::by_name(name).ok_or(format!("unknown encoding '{}'", name))?;
+        }
+        ("flush_every", Value::Integer(bytes)) if *bytes >= 0 => {
+            config.flush_every = Some(*bytes as usize).filter(|bytes| *bytes > 0);
+        }
+        ("journal", Value::Integer(steps)) if *steps >= 0 => config.journal = *steps as usize,
+        ("flush_every" | "journal", Value::Integer(_)) => {
+            return Err(format!("{} can't be negative", key));
+        }
+        ("extensions", value) => return Err(mismatch("an array", value)),
+        ("io_mode" | "eof" | "output_encoding", value) => return Err(mismatch("a string", value)),
+        ("tape_size" | "cell_width" | "optimization" | "flush_every" | "journal", value) => {
+            return Err(mismatch("an integer", value))
+        }
+        (other, _) => return Err(format!("unknown setting '{}'", other)),
+    }
+    Ok(())
+}
+
+// Drops a trailing `#` comment, leaving any `#` inside a string alone
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars
+        .next_if(|character| character.is_whitespace())
+        .is_some()
+    {}
+}
+
+fn parse_value(chars: &mut Chars) -> Result<Value, String> {
+    let value = value(chars)?;
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(character) => Err(format!("unexpected '{}' after the value", character)),
+        None => Ok(value),
+    }
+}
+
+fn value(chars: &mut Chars) -> Result<Value, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Value::String(string)),
+                    Some('\\') => match chars.next() {
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        Some(character @ ('"' | '\\')) => string.push(character),
+                        _ => return Err("unsupported escape in string".to_string()),
+                    },
+                    Some(character) => string.push(character),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut values = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                if chars.next_if_eq(&']').is_some() {
+                    return Ok(Value::Array(values));
+                }
+                values.push(value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Value::Array(values)),
+                    _ => return Err("expected ',' or ']' in array".to_string()),
+                }
+            }
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(character) = chars
+                .next_if(|character| !matches!(character, ',' | ']') && !character.is_whitespace())
+            {
+                word.push(character);
+            }
+            match word.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                number => number
+                    .replace('_', "")
+                    .parse()
+                    .map(Value::Integer)
+                    .map_err(|_| format!("unsupported value '{}'", number)),
+            }
+        }
+        None => Err("missing value".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(TEMPLATE), Ok(Config::default()));
+
+        let config = parse(
+            "extensions = [\"debug\", \"pbrain\"]  # comment\n\
+             io_mode = \"numeric\"\n\
+             eof = \"zero\"\n\
+             tape_size = 256\n\
+             output_encoding = \"utf8\"\n\
+             flush_every = 1_024\n\
+             journal = 5\n\
+             optimization = 1\n",
+        )
+        .unwrap();
+        assert!(config.extensions.debug && config.extensions.pbrain);
+        assert_eq!(config.extensions.io_mode, IoMode::Numeric);
+        assert_eq!(config.extensions.eof, EofMode::Zero);
+        assert_eq!(config.output_encoding, Encoding::Utf8);
+        assert_eq!(config.flush_every, Some(1024));
+        assert_eq!(config.journal, 5);
+        assert_eq!(config.optimization, 1);
+
+        // Listing the extensions keeps the I/O settings from before
+        let config =
+            parse("eof = \"max\"\nio_mode = \"numeric\"\nextensions = [\"debug\"]\n").unwrap();
+        assert_eq!(config.extensions.eof, EofMode::Max);
+        assert_eq!(config.extensions.io_mode, IoMode::Numeric);
+        assert!(config.extensions.debug);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("\ntape = 3\n"),
+            Err("2: unknown setting 'tape'".to_string())
+        );
+        assert_eq!(
+            parse("journal = \"long\""),
+            Err("1: journal should be an integer, not a string".to_string())
+        );
+        assert_eq!(
+            parse("extensions = [\"bf++\"]"),
+            Err("1: unknown extension 'bf++'".to_string())
+        );
+        assert_eq!(
+            parse("cell_width = 16"),
+            Err("1: cell_width = 16 is unsupported: cells are always 8 bits".to_string())
+        );
+        assert_eq!(
+            parse("tape_size = 30000"),
+            Err(
+                "1: tape_size = 30000 is unsupported: the tape is always a ring of 256 cells"
+                    .to_string()
+            )
+        );
+        assert!(parse("optimization = 2").is_err());
+        assert!(parse("[run]").is_err());
+        assert!(parse("io_mode = \"bytes").is_err());
+        assert!(parse("journal = 1 2").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+// Coverage for `--coverage`: how many times each instruction ran, mapped
+// back onto the original source so unexecuted code stands out. `--profile`
+// saves the raw counts, one per line, for `graph` to annotate loops with.
+
+use crate::{is_command, ExecutionObserver, Extensions, Memory};
+
+const UNEXECUTED: &str = "\x1b[41m"; // Red background
+const EXECUTED: &str = "\x1b[32m";
+const COMMENT: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+pub struct Coverage {
+    pub counts: Vec<u64>, // Executions per sanitized source position
+}
+
+impl Coverage {
+    pub fn new(sanitized_len: usize) -> Coverage {
+        Coverage {
+            counts: vec![0; sanitized_len],
+        }
+    }
+
+    pub fn executed(&self) -> usize {
+        self.counts.iter().filter(|count| **count > 0).count()
+    }
+
+    /// Pairs every character of the unsanitized source with the execution
+    /// count of the instruction it became, if it's a command.
+    fn annotate<'a>(
+        &'a self,
+        source: &'a str,
+        extensions: Extensions,
+    ) -> impl Iterator<Item = (char, Option<u64>)> + 'a {
+        let mut counts = self.counts.iter();
+        source.chars().map(move |character| {
+            let count = if is_command(character, extensions) {
+                counts.next().copied()
+            } else {
+                None
+            };
+            (character, count)
+        })
+    }
+
+    /// The source with executed commands in green, unexecuted ones on red
+    /// and comments dimmed, followed by a summary line.
+    pub fn render(&self, source: &str, extensions: Extensions) -> String {
+        let mut rendered = String::new();
+        for (character, count) in self.annotate(source, extensions) {
+            let color = match count {
+                _ if character == '\n' => None,
+                Some(0) => Some(UNEXECUTED),
+                Some(_) => Some(EXECUTED),
+                None => Some(COMMENT),
+            };
+            match color {
+                Some(color) => rendered.push_str(&format!("{}{}{}", color, character, RESET)),
+                None => rendered.push(character),
+            }
+        }
+        if !rendered.ends_with('\n') {
+            rendered.push('\n');
+        }
+        let total = self.counts.len();
+        let percent = if total == 0 {
+            100.0
+        } else {
+            self.executed() as f64 * 100.0 / total as f64
+        };
+        rendered.push_str(&format!(
+            "coverage: {} of {} instructions executed ({:.1}%)\n",
+            self.executed(),
+            total,
+            percent
+        ));
+        rendered
+    }
+
+    /// lcov tracefile data, one `DA` record per source line holding commands
+    /// (counting the most executed command on the line).
+    pub fn to_lcov(&self, source: &str, extensions: Extensions, source_name: &str) -> String {
+        let mut lines: Vec<(usize, u64)> = Vec::new();
+        let mut line = 1;
+        for (character, count) in self.annotate(source, extensions) {
+            if let Some(count) = count {
+                match lines.last_mut() {
+                    Some((last, most)) if *last == line => *most = (*most).max(count),
+                    _ => lines.push((line, count)),
+                }
+            }
+            if character == '\n' {
+                line += 1;
+            }
+        }
+
+        let mut lcov = format!("TN:\nSF:{}\n", source_name);
+        for (line, count) in &lines {
+            lcov.push_str(&format!("DA:{},{}\n", line, count));
+        }
+        let hit = lines.iter().filter(|(_, count)| *count > 0).count();
+        lcov.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), hit));
+        lcov
+    }
+}
+
+impl Coverage {
+    /// The counts as `--profile` saves them.
+    pub fn to_profile(&self) -> String {
+        self.counts
+            .iter()
+            .map(|count| format!("{}\n", count))
+            .collect()
+    }
+
+    /// Reads back what `to_profile` saved.
+    pub fn from_profile(profile: &str) -> Result<Coverage, String> {
+        let counts = profile
+            .lines()
+            .enumerate()
+            .map(|(line, count)| {
+                count
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("line {}: '{}' isn't a count", line + 1, count))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Coverage { counts })
+    }
+}
+
+impl ExecutionObserver for Coverage {
+    fn on_instruction(&mut self, source_pointer: usize, _: &Memory, _: usize) {
+        self.counts[source_pointer] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run, sanitize_input, ProgramInput};
+
+    #[test]
+    fn test_coverage() {
+        let source = "+[-]\nx[+]\n";
+        let sanitized = sanitize_input(source, Extensions::default());
+        let mut coverage = Coverage::new(sanitized.len());
+        let mut output = Vec::new();
+        let result = run(
+            &sanitized,
+            Extensions::default(),
+            &mut ProgramInput::new(&[]),
+            &mut output,
+            &mut coverage,
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(coverage.counts, [1, 1, 1, 1, 1, 0, 0]);
+        assert!(coverage
+            .render(source, Extensions::default())
+            .ends_with("coverage: 5 of 7 instructions executed (71.4%)\n"));
+        assert_eq!(
+            coverage.to_lcov(source, Extensions::default(), "prog.bf"),
+            "TN:\nSF:prog.bf\nDA:1,1\nDA:2,1\nLF:2\nLH:2\nend_of_record\n"
+        );
+        let profile = coverage.to_profile();
+        assert_eq!(
+            Coverage::from_profile(&profile).unwrap().counts,
+            coverage.counts
+        );
+        assert!(Coverage::from_profile("1\nx\n").is_err());
+    }
+}
@@ -0,0 +1,587 @@
+// `brainfuck-rs dap`: a Debug Adapter Protocol server over stdio, so editors
+// like VS Code can debug programs through their generic debugger UI. It
+// drives a `debugger::Debugger`: line breakpoints stop before the first
+// command on their line, stepping goes one command at a time (backwards
+// too), and the tape is shown as a scope of variables. Since stdio carries
+// the protocol, the program's output is sent as `output` events and its
+// input comes from the launch configuration's `input` string.
+//
+// Messages are read on their own thread, so a running program can still be
+// paused.
+
+use crate::framing::{read_message, write_message};
+use brainfuck_rs::debugger::{Debugger, Io};
+use brainfuck_rs::json::{self, Value};
+use brainfuck_rs::{check, dialect, sanitize_input, Extensions, Interpreter, ProgramInput};
+use std::collections::BTreeSet;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+const THREAD_ID: u64 = 1; // Programs only ever have the one
+const TAPE_REFERENCE: u64 = 1;
+const STATE_REFERENCE: u64 = 2;
+const STEPS_PER_POLL: usize = 10_000; // Instructions run between checks for new requests
+
+// Why execution stopped, as named by the protocol
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stop {
+    Entry,
+    Step,
+    Breakpoint,
+    Pause,
+}
+
+impl Stop {
+    fn reason(self) -> &'static str {
+        match self {
+            Stop::Entry => "entry",
+            Stop::Step => "step",
+            Stop::Breakpoint => "breakpoint",
+            Stop::Pause => "pause",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Running {
+    Forward,
+    Backward,
+}
+
+struct Program {
+    debugger: Debugger,
+    input: ProgramInput,
+    path: String,
+    source: Vec<char>,              // Sanitized
+    positions: Vec<(usize, usize)>, // Line and column of each command
+    breakpoints: BTreeSet<usize>,   // Command indices
+}
+
+pub struct Session<W: Write> {
+    writer: W,
+    seq: u64,
+    extensions: Extensions,
+    journal: usize, // Steps each program's debugger can go back
+    program: Option<Program>,
+    stop_on_entry: bool,
+    running: Option<Running>,
+    finished: bool, // Set once the client disconnects
+}
+
+impl<W: Write> Session<W> {
+    pub fn new(writer: W, extensions: Extensions, journal: usize) -> Session<W> {
+        Session {
+            writer,
+            extensions,
+            journal,
+            seq: 0,
+            program: None,
+            stop_on_entry: false,
+            running: None,
+            finished: false,
+        }
+    }
+
+    fn send(&mut self, mut fields: Vec<(&str, String)>) {
+        self.seq += 1;
+        fields.insert(0, ("seq", self.seq.to_string()));
+        // There's nobody left to report a broken connection to
+        let _ = write_message(&mut self.writer, &json::object(&fields));
+    }
+
+    fn respond(&mut self, request: &Value, body: Result<String, String>) {
+        let command = request.get("command").and_then(Value::as_str).unwrap_or("");
+        let request_seq = request.get("seq").and_then(Value::as_u64).unwrap_or(0);
+        let mut fields = vec![
+            ("type", json::string("response")),
+            ("request_seq", request_seq.to_string()),
+            ("command", json::string(command)),
+            ("success", body.is_ok().to_string()),
+        ];
+        match body {
+            Ok(body) => fields.push(("body", body)),
+            Err(message) => fields.push(("message", json::string(&message))),
+        }
+        self.send(fields);
+    }
+
+    fn event(&mut self, event: &str, body: String) {
+        self.send(vec![
+            ("type", json::string("event")),
+            ("event", json::string(event)),
+            ("body", body),
+        ]);
+    }
+
+    fn stopped(&mut self, stop: Stop) {
+        self.running = None;
+        let body = json::object(&[
+            ("reason", json::string(stop.reason())),
+            ("threadId", THREAD_ID.to_string()),
+            ("allThreadsStopped", "true".to_string()),
+        ]);
+        self.event("stopped", body);
+    }
+
+    fn output(&mut self, category: &str, text: &str) {
+        let body = json::object(&[
+            ("category", json::string(category)),
+            ("output", json::string(text)),
+        ]);
+        self.event("output", body);
+    }
+
+    fn terminate(&mut self) {
+        self.running = None;
+        self.program = None;
+        self.event("exited", json::object(&[("exitCode", "0".to_string())]));
+        self.event("terminated", json::object(&[]));
+    }
+
+    /// Whether a continue (or reverse continue) is in progress, in which case
+    /// `run` should be called until it isn't.
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn handle(&mut self, request: &Value) {
+        let command = request.get("command").and_then(Value::as_str).unwrap_or("");
+        let arguments = request.get("arguments").cloned().unwrap_or(Value::Null);
+        match command {
+            "initialize" => {
+                let capabilities = json::object(&[
+                    ("supportsConfigurationDoneRequest", "true".to_string()),
+                    ("supportsStepBack", "true".to_string()),
+                ]);
+                self.respond(request, Ok(capabilities));
+                self.event("initialized", json::object(&[]));
+            }
+            "launch" => {
+                let result = self.launch(&arguments);
+                self.respond(request, result.map(|()| json::object(&[])));
+            }
+            "setBreakpoints" => {
+                let body = self.set_breakpoints(&arguments);
+                self.respond(request, body);
+            }
+            "configurationDone" => {
+                self.respond(request, Ok(json::object(&[])));
+                if self.stop_on_entry {
+                    self.stopped(Stop::Entry);
+                } else {
+                    self.running = Some(Running::Forward);
+                }
+            }
+            "threads" => {
+                let thread = json::object(&[
+                    ("id", THREAD_ID.to_string()),
+                    ("name", json::string("main")),
+                ]);
+                let body = json::object(&[("threads", json::array(&[thread]))]);
+                self.respond(request, Ok(body));
+            }
+            "stackTrace" => {
+                let body = self.stack_trace();
+                self.respond(request, body);
+            }
+            "scopes" => {
+                let scope = |name: &str, reference: u64| {
+                    json::object(&[
+                        ("name", json::string(name)),
+                        ("variablesReference", reference.to_string()),
+                        ("expensive", "false".to_string()),
+                    ])
+                };
+                let scopes = [
+                    scope("State", STATE_REFERENCE),
+                    scope("Tape", TAPE_REFERENCE),
+                ];
+                let body = json::object(&[("scopes", json::array(&scopes))]);
+                self.respond(request, Ok(body));
+            }
+            "variables" => {
+                let reference = arguments.get("variablesReference").and_then(Value::as_u64);
+                let body = self.variables(reference.unwrap_or(0));
+                self.respond(request, body);
+            }
+            "continue" => {
+                self.respond(request, Ok(json::object(&[])));
+                self.running = Some(Running::Forward);
+            }
+            "reverseContinue" => {
+                self.respond(request, Ok(json::object(&[])));
+                self.running = Some(Running::Backward);
+            }
+            "next" | "stepIn" | "stepOut" => {
+                self.respond(request, Ok(json::object(&[])));
+                self.step_forward(1);
+                if self.program.is_some() {
+                    self.stopped(Stop::Step);
+                }
+            }
+            "stepBack" => {
+                self.respond(request, Ok(json::object(&[])));
+                if let Some(program) = &mut self.program {
+                    program.debugger.step_back();
+                }
+                self.stopped(Stop::Step);
+            }
+            "pause" => {
+                self.respond(request, Ok(json::object(&[])));
+                self.stopped(Stop::Pause);
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json::object(&[])));
+                if command == "terminate" {
+                    self.terminate();
+                }
+                self.finished = command == "disconnect";
+            }
+            other => self.respond(request, Err(format!("unsupported request '{}'", other))),
+        }
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<(), String> {
+        let path = arguments
+            .get("program")
+            .and_then(Value::as_str)
+            .ok_or("launch expects a program")?;
+        self.stop_on_entry = arguments.get("stopOnEntry") == Some(&Value::Bool(true));
+        let input = arguments.get("input").and_then(Value::as_str).unwrap_or("");
+        let raw = std::fs::read_to_string(path)
+            .map_err(|error| format!("could not read {}: {}", path, error))?;
+        let dialect = dialect::from_path(Path::new(path)).unwrap_or(&dialect::Brainfuck);
+        let raw = dialect.translate(&raw);
+        let extensions = dialect.extensions(self.extensions);
+        let source = sanitize_input(&raw, extensions);
+        let interpreter =
+            Interpreter::new(&source, extensions).map_err(|error| error.to_string())?;
+        self.program = Some(Program {
+            debugger: Debugger::new(interpreter, self.journal),
+            // Stdin carries the protocol, so there's nothing to fall back to
+            input: ProgramInput::new(input.as_bytes()).with_reader(Box::new(io::empty())),
+            path: path.to_string(),
+            source: source.chars().collect(),
+            positions: check::command_positions(&raw, extensions),
+            breakpoints: BTreeSet::new(),
+        });
+        Ok(())
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<String, String> {
+        let program = self
+            .program
+            .as_mut()
+            .ok_or("no program has been launched")?;
+        let lines: Vec<u64> = match arguments.get("breakpoints") {
+            Some(Value::Array(breakpoints)) => breakpoints
+                .iter()
+                .filter_map(|breakpoint| breakpoint.get("line").and_then(Value::as_u64))
+                .collect(),
+            _ => Vec::new(),
+        };
+        program.breakpoints.clear();
+        let mut verified = Vec::new();
+        for line in lines {
+            let command = program
+                .positions
+                .iter()
+                .position(|(command_line, _)| *command_line as u64 == line);
+            if let Some(command) = command {
+                program.breakpoints.insert(command);
+            }
+            verified.push(json::object(&[
+                ("verified", command.is_some().to_string()),
+                ("line", line.to_string()),
+            ]));
+        }
+        Ok(json::object(&[("breakpoints", json::array(&verified))]))
+    }
+
+    fn stack_trace(&self) -> Result<String, String> {
+        let program = self
+            .program
+            .as_ref()
+            .ok_or("no program has been launched")?;
+        let pointer = program.debugger.interpreter().source_pointer();
+        // Past the last command once halted
+        let (line, column) = program
+            .positions
+            .get(pointer)
+            .or(program.positions.last())
+            .copied()
+            .unwrap_or((1, 1));
+        let command = program.source.get(pointer).copied().unwrap_or(' ');
+        let frame = json::object(&[
+            ("id", "1".to_string()),
+            (
+                "name",
+                json::string(&format!("'{}' at {}", command, pointer)),
+            ),
+            ("line", line.to_string()),
+            ("column", column.to_string()),
+            (
+                "source",
+                json::object(&[("path", json::string(&program.path))]),
+            ),
+        ]);
+        Ok(json::object(&[
+            ("stackFrames", json::array(&[frame])),
+            ("totalFrames", "1".to_string()),
+        ]))
+    }
+
+    fn variables(&self, reference: u64) -> Result<String, String> {
+        let program = self
+            .program
+            .as_ref()
+            .ok_or("no program has been launched")?;
+        let interpreter = program.debugger.interpreter();
+        let variable = |name: String, value: String| {
+            json::object(&[
+                ("name", json::string(&name)),
+                ("value", json::string(&value)),
+                ("variablesReference", "0".to_string()),
+            ])
+        };
+        let variables: Vec<String> = match reference {
+            TAPE_REFERENCE => interpreter
+                .memory()
+                .iter()
+                .enumerate()
+                .map(|(cell, value)| variable(format!("cell[{}]", cell), value.to_string()))
+                .collect(),
+            STATE_REFERENCE => vec![
+                variable(
+                    "pointer".to_string(),
+                    interpreter.memory_pointer().to_string(),
+                ),
+                variable(
+                    "instruction".to_string(),
+                    interpreter.source_pointer().to_string(),
+                ),
+                variable("steps".to_string(), program.debugger.steps().to_string()),
+                variable(
+                    "output".to_string(),
+                    json::string(&String::from_utf8_lossy(program.debugger.output())),
+                ),
+            ],
+            _ => return Err(format!("unknown variables reference {}", reference)),
+        };
+        Ok(json::object(&[("variables", json::array(&variables))]))
+    }
+
+    // Runs up to `count` instructions, stopping early at a breakpoint.
+    // Returns whether it stopped at one.
+    fn step_forward(&mut self, count: usize) -> bool {
+        let Some(program) = &mut self.program else {
+            return false;
+        };
+        let mut printed = Vec::new();
+        let mut result = Ok(false);
+        for _ in 0..count {
+            match program.debugger.step(&mut program.input) {
+                Ok(Some(entry)) => {
+                    if let Some(Io::Output(byte)) = entry.io {
+                        printed.push(byte);
+                    }
+                }
+                Ok(None) => break,
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+            let pointer = program.debugger.interpreter().source_pointer();
+            if program.breakpoints.contains(&pointer) {
+                result = Ok(true);
+                break;
+            }
+        }
+        let halted = program
+            .debugger
+            .interpreter()
+            .current_instruction()
+            .is_none();
+        if !printed.is_empty() {
+            self.output("stdout", &String::from_utf8_lossy(&printed));
+        }
+        match result {
+            Ok(at_breakpoint) if !halted || at_breakpoint => at_breakpoint,
+            Ok(_) => {
+                self.terminate();
+                false
+            }
+            Err(error) => {
+                self.output("stderr", &format!("{}\n", error));
+                self.terminate();
+                false
+            }
+        }
+    }
+
+    // Undoes up to `count` instructions, stopping early at a breakpoint or
+    // once the journal runs out.
+    fn step_backward(&mut self, count: usize) -> Option<Stop> {
+        let program = self.program.as_mut()?;
+        for _ in 0..count {
+            if program.debugger.step_back().is_none() {
+                return Some(Stop::Entry);
+            }
+            let pointer = program.debugger.interpreter().source_pointer();
+            if program.breakpoints.contains(&pointer) {
+                return Some(Stop::Breakpoint);
+            }
+        }
+        None
+    }
+
+    /// Makes progress on a continue or reverse continue, a bounded number of
+    /// instructions at a time so requests are still answered in between.
+    pub fn run(&mut self) {
+        let stop = match self.running {
+            Some(Running::Forward) => self
+                .step_forward(STEPS_PER_POLL)
+                .then_some(Stop::Breakpoint),
+            Some(Running::Backward) => self.step_backward(STEPS_PER_POLL),
+            None => None,
+        };
+        if let Some(stop) = stop {
+            self.stopped(stop);
+        }
+    }
+}
+
+fn read_requests(input: impl Read + Send + 'static) -> Receiver<Value> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            // Malformed messages can't be answered without a sequence number
+            if let Ok(request) = json::parse(&message) {
+                if sender.send(request).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+pub fn serve(
+    input: impl Read + Send + 'static,
+    output: impl Write,
+    extensions: Extensions,
+    journal: usize,
+) {
+    let requests = read_requests(input);
+    let mut session = Session::new(output, extensions, journal);
+    while !session.is_finished() {
+        let request = if session.is_running() {
+            match requests.try_recv() {
+                Ok(request) => request,
+                Err(TryRecvError::Empty) => {
+                    session.run();
+                    continue;
+                }
+                Err(TryRecvError::Disconnected) => break,
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => request,
+                Err(_) => break,
+            }
+        };
+        session.handle(&request);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(seq: u64, command: &str, arguments: String) -> Value {
+        let request = json::object(&[
+            ("seq", seq.to_string()),
+            ("type", json::string("request")),
+            ("command", json::string(command)),
+            ("arguments", arguments),
+        ]);
+        json::parse(&request).unwrap()
+    }
+
+    fn messages(output: &[u8]) -> Vec<Value> {
+        let mut reader = output;
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            messages.push(json::parse(&message).unwrap());
+        }
+        messages
+    }
+
+    #[test]
+    fn test_session() {
+        let path = std::env::temp_dir().join(format!("bf-dap-{}.bf", std::process::id()));
+        std::fs::write(&path, "++\n>+.\n<.\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut session = Session::new(
+            Vec::new(),
+            Extensions::default(),
+            brainfuck_rs::debugger::DEFAULT_JOURNAL_SIZE,
+        );
+        session.handle(&request(1, "initialize", json::object(&[])));
+        let launch = json::object(&[("program", json::string(path))]);
+        session.handle(&request(2, "launch", launch));
+        let breakpoints = json::object(&[(
+            "breakpoints",
+            json::array(&[json::object(&[("line", "3".to_string())])]),
+        )]);
+        session.handle(&request(3, "setBreakpoints", breakpoints));
+        session.handle(&request(4, "configurationDone", json::object(&[])));
+        while session.is_running() {
+            session.run();
+        }
+        let variables = json::object(&[("variablesReference", TAPE_REFERENCE.to_string())]);
+        session.handle(&request(5, "variables", variables));
+        session.handle(&request(6, "continue", json::object(&[])));
+        while session.is_running() {
+            session.run();
+        }
+        std::fs::remove_file(path).unwrap();
+
+        let messages = messages(&session.writer);
+        let events: Vec<&str> = messages
+            .iter()
+            .filter_map(|message| message.get("event").and_then(Value::as_str))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                "initialized",
+                "output",
+                "stopped",
+                "output",
+                "exited",
+                "terminated"
+            ]
+        );
+        assert!(messages
+            .iter()
+            .all(|message| message.get("success") != Some(&Value::Bool(false))));
+        let tape = messages
+            .iter()
+            .find(|message| message.get("command").and_then(Value::as_str) == Some("variables"))
+            .and_then(|message| message.get("body")?.get("variables").cloned());
+        let Some(Value::Array(cells)) = tape else {
+            panic!("no tape in {:?}", messages);
+        };
+        assert_eq!(cells[0].get("value").and_then(Value::as_str), Some("2"));
+        assert_eq!(cells[1].get("value").and_then(Value::as_str), Some("1"));
+    }
+}
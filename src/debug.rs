@@ -0,0 +1,188 @@
+// `brainfuck-rs debug`: an interactive shell over `debugger::Debugger`.
+// Commands are read a line at a time from stdin, as is the program's own
+// input when it reaches a `,`.
+
+use brainfuck_rs::debugger::{Debugger, Entry, Io};
+use brainfuck_rs::{Access, ProgramInput};
+use std::io::{self, BufRead, Write};
+
+const VISIBLE_CELLS: usize = 8; // Cells shown either side of the pointer
+
+const HELP: &str = "\
+commands:
+  s, step [N]      run N instructions (default 1)
+  b, back [N]      step back N instructions (default 1)
+  c, continue      run until the program halts
+  m, memory        show the cells around the pointer
+  h, history CELL  show the recorded writes to CELL
+  w, watch CELL    pause whenever CELL is read or written
+  unwatch CELL     stop watching CELL
+  o, output        show what the program has printed so far
+  q, quit";
+
+fn describe(entry: &Entry, source: &[char]) -> String {
+    let mut line = format!(
+        "step {}: '{}' at {}",
+        entry.step + 1,
+        source[entry.source_pointer],
+        entry.source_pointer
+    );
+    if let Some((cell, old, new)) = entry.write {
+        line.push_str(&format!(", cell {}: {} -> {}", cell, old, new));
+    }
+    match entry.io {
+        Some(Io::Output(byte)) => line.push_str(&format!(", printed {:?}", byte as char)),
+        Some(Io::Input(Some(byte))) => line.push_str(&format!(", read {:?}", byte as char)),
+        Some(Io::Input(None)) => line.push_str(", read end of input"),
+        None => {}
+    }
+    line
+}
+
+fn describe_watch(entry: &Entry, debugger: &Debugger, source: &[char]) -> String {
+    let Some((cell, access)) = entry.access else {
+        return String::new();
+    };
+    let command = source[entry.source_pointer];
+    let value = debugger.interpreter().memory()[cell];
+    match (access, entry.write) {
+        (Access::Read, _) => format!(
+            "watchpoint: '{}' at {} read cell {} ({})",
+            command, entry.source_pointer, cell, value
+        ),
+        (Access::Write, Some((_, old, new))) => format!(
+            "watchpoint: '{}' at {} wrote cell {}: {} -> {}",
+            command, entry.source_pointer, cell, old, new
+        ),
+        (Access::Write, None) => format!(
+            "watchpoint: '{}' at {} wrote cell {}, leaving it {}",
+            command, entry.source_pointer, cell, value
+        ),
+    }
+}
+
+fn show_position(debugger: &Debugger, source: &[char]) {
+    let interpreter = debugger.interpreter();
+    match source.get(interpreter.source_pointer()) {
+        Some(command) => println!(
+            "next: '{}' at {}, pointer {}",
+            command,
+            interpreter.source_pointer(),
+            interpreter.memory_pointer()
+        ),
+        None => println!("halted after {} steps", debugger.steps()),
+    }
+}
+
+fn show_memory(debugger: &Debugger) {
+    let interpreter = debugger.interpreter();
+    let pointer = interpreter.memory_pointer();
+    let start = pointer.saturating_sub(VISIBLE_CELLS);
+    let end = (pointer + VISIBLE_CELLS).min(interpreter.memory().len() - 1);
+    let cells: Vec<String> = (start..=end)
+        .map(|cell| match interpreter.memory()[cell] {
+            value if cell == pointer => format!("[{}]", value),
+            value => value.to_string(),
+        })
+        .collect();
+    println!("cells {}..={}: {}", start, end, cells.join(" "));
+}
+
+// Runs up to `count` instructions, stopping early if the program halts
+fn step(
+    debugger: &mut Debugger,
+    input: &mut ProgramInput,
+    source: &[char],
+    count: u64,
+    verbose: bool,
+) {
+    for _ in 0..count {
+        if debugger.needs_input() {
+            print!("input> ");
+            let _ = io::stdout().flush();
+        }
+        let entry = match debugger.step(input) {
+            Ok(Some(entry)) => entry.clone(),
+            Ok(None) => break,
+            Err(error) => {
+                println!("error: {}", error);
+                return;
+            }
+        };
+        if verbose {
+            println!("{}", describe(&entry, source));
+        }
+        if debugger.triggers_watch(&entry) {
+            println!("{}", describe_watch(&entry, debugger, source));
+            break;
+        }
+    }
+    show_position(debugger, source);
+}
+
+pub fn debug(source: &str, mut debugger: Debugger) {
+    let source: Vec<char> = source.chars().collect();
+    let mut input = ProgramInput::new(&[]);
+    println!("{}", HELP);
+    show_position(&debugger, &source);
+    let stdin = io::stdin();
+    loop {
+        print!("(bf) ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("s");
+        let argument = words.next().map(str::parse::<u64>);
+        let count = match argument {
+            Some(Ok(count)) => count,
+            Some(Err(_)) => {
+                println!("expected a number");
+                continue;
+            }
+            None => 1,
+        };
+        match command {
+            "s" | "step" => step(&mut debugger, &mut input, &source, count, true),
+            "c" | "continue" => step(&mut debugger, &mut input, &source, u64::MAX, false),
+            "b" | "back" => {
+                for _ in 0..count {
+                    match debugger.step_back() {
+                        Some(entry) => println!("undid {}", describe(&entry, &source)),
+                        None => {
+                            println!("no older steps are recorded");
+                            break;
+                        }
+                    }
+                }
+                show_position(&debugger, &source);
+            }
+            "m" | "memory" => show_memory(&debugger),
+            "h" | "history" if argument.is_none() => println!("history expects a cell"),
+            "h" | "history" => {
+                let mut writes = debugger.history(count as usize).peekable();
+                if writes.peek().is_none() {
+                    println!("no recorded writes to cell {}", count);
+                }
+                for entry in writes {
+                    println!("{}", describe(entry, &source));
+                }
+            }
+            "w" | "watch" | "unwatch" if argument.is_none() => {
+                let watches: Vec<String> =
+                    debugger.watches().map(|cell| cell.to_string()).collect();
+                println!("watching: {}", watches.join(" "));
+            }
+            "w" | "watch" => debugger.watch(count as usize),
+            "unwatch" if !debugger.unwatch(count as usize) => {
+                println!("cell {} isn't watched", count)
+            }
+            "unwatch" => {}
+            "o" | "output" => println!("{:?}", String::from_utf8_lossy(debugger.output())),
+            "q" | "quit" => break,
+            _ => println!("{}", HELP),
+        }
+    }
+}
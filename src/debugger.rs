@@ -0,0 +1,258 @@
+// Stepping debugger with time travel. Every step is recorded in a bounded
+// journal of undo records, so the most recent instructions can be stepped
+// back through and the writes that gave a cell its value looked up. Cells
+// can also be watched, so a run pauses whenever they're read or written.
+
+use crate::interpreter::Undo;
+use crate::ir::Instruction;
+use crate::{Access, Error, Interpreter, ProgramInput, StepResult};
+use std::collections::{BTreeSet, VecDeque};
+
+pub const DEFAULT_JOURNAL_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Io {
+    Output(u8),
+    Input(Option<u8>), // `None` at end of input
+}
+
+/// One executed instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub step: u64,                       // How many steps had run before this one
+    pub source_pointer: usize,           // The instruction that ran
+    pub write: Option<(usize, u8, u8)>,  // Cell changed, its old and its new value
+    pub access: Option<(usize, Access)>, // Cell read or written, even if unchanged
+    pub io: Option<Io>,
+    undo: Undo,
+}
+
+pub struct Debugger {
+    interpreter: Interpreter,
+    journal: VecDeque<Entry>, // Oldest first
+    capacity: usize,
+    unjournaled: Option<Entry>, // The last step, when the journal has no room for it
+    steps: u64,
+    replay: Vec<Option<u8>>, // Input stepped back over, to be read again (last first)
+    output: Vec<u8>,
+    watches: BTreeSet<usize>,
+}
+
+impl Debugger {
+    /// Keeps the last `capacity` steps for stepping back through, so with 0
+    /// there are none.
+    pub fn new(interpreter: Interpreter, capacity: usize) -> Debugger {
+        Debugger {
+            interpreter,
+            journal: VecDeque::new(),
+            capacity,
+            unjournaled: None,
+            steps: 0,
+            replay: Vec::new(),
+            output: Vec::new(),
+            watches: BTreeSet::new(),
+        }
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    /// Instructions run so far, less those stepped back over.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Everything printed up to this point of the run.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Whether the next step will read fresh input.
+    pub fn needs_input(&self) -> bool {
+        self.interpreter.current_instruction() == Some(Instruction::Input) && self.replay.is_empty()
+    }
+
+    /// Runs one instruction, reading from `input` if it's a `,` that hasn't
+    /// already been read before stepping back. Returns the entry recorded,
+    /// or `None` if the program has halted.
+    pub fn step(&mut self, input: &mut ProgramInput) -> Result<Option<&Entry>, Error> {
+        let source_pointer = self.interpreter.source_pointer();
+        if self.interpreter.current_instruction().is_none() {
+            return Ok(None);
+        }
+        let undo = self.interpreter.checkpoint();
+        let access = self.interpreter.next_access();
+        // The step may switch tapes or threads, so the cell is looked up
+        // again by where it is rather than in whatever is current after
+        let (thread, tape) = (self.interpreter.thread_id(), self.interpreter.tape());
+        let memory_pointer = self.interpreter.memory_pointer();
+        let before = self.interpreter.memory()[memory_pointer];
+        let mut io = None;
+        let mut result = self.interpreter.step()?;
+        if result == StepResult::NeedsInput {
+            let byte = self.replay.pop().unwrap_or_else(|| input.read_byte());
+            io = Some(Io::Input(byte));
+            self.interpreter.provide_input(byte);
+            result = self.interpreter.step()?;
+        }
+        if let StepResult::Output(byte) = result {
+            io = Some(Io::Output(byte));
+            self.output.push(byte);
+        }
+        let after = self.interpreter.tape_of(thread, tape)[memory_pointer];
+        let entry = Entry {
+            step: self.steps,
+            source_pointer,
+            write: (after != before).then_some((memory_pointer, before, after)),
+            access,
+            io,
+            undo,
+        };
+        self.steps += 1;
+        if self.capacity == 0 {
+            return Ok(Some(self.unjournaled.insert(entry)));
+        }
+        if self.journal.len() == self.capacity {
+            self.journal.pop_front();
+        }
+        self.journal.push_back(entry);
+        Ok(self.journal.back())
+    }
+
+    /// Undoes the last step, returning it, or `None` once the journal has
+    /// nothing older.
+    pub fn step_back(&mut self) -> Option<Entry> {
+        let entry = self.journal.pop_back()?;
+        self.interpreter.restore(entry.undo.clone());
+        match entry.io {
+            Some(Io::Output(_)) => {
+                self.output.pop();
+            }
+            Some(Io::Input(byte)) => self.replay.push(byte),
+            None => {}
+        }
+        self.steps -= 1;
+        Some(entry)
+    }
+
+    /// Pauses runs whenever `cell` is read or written.
+    pub fn watch(&mut self, cell: usize) {
+        self.watches.insert(cell);
+    }
+
+    /// Stops watching `cell`, returning false if it wasn't watched.
+    pub fn unwatch(&mut self, cell: usize) -> bool {
+        self.watches.remove(&cell)
+    }
+
+    pub fn watches(&self) -> impl Iterator<Item = usize> + '_ {
+        self.watches.iter().copied()
+    }
+
+    /// Whether the step recorded in `entry` touched a watched cell.
+    pub fn triggers_watch(&self, entry: &Entry) -> bool {
+        matches!(entry.access, Some((cell, _)) if self.watches.contains(&cell))
+    }
+
+    /// The journalled writes to `cell`, oldest first.
+    pub fn history(&self, cell: usize) -> impl Iterator<Item = &Entry> {
+        self.journal
+            .iter()
+            .filter(move |entry| matches!(entry.write, Some((written, _, _)) if written == cell))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Extensions;
+
+    #[test]
+    fn test_step_back() {
+        let interpreter = Interpreter::new(",+.>++[-]", Extensions::default()).unwrap();
+        let mut debugger = Debugger::new(interpreter, 100);
+        let mut input = ProgramInput::new(b"a");
+        while debugger.step(&mut input).unwrap().is_some() {}
+        assert_eq!(debugger.output(), b"b");
+        assert_eq!(debugger.interpreter().memory()[1], 0);
+        let writes: Vec<_> = debugger.history(1).map(|entry| entry.write).collect();
+        assert_eq!(
+            writes,
+            [
+                Some((1, 0, 1)),
+                Some((1, 1, 2)),
+                Some((1, 2, 1)),
+                Some((1, 1, 0))
+            ]
+        );
+
+        // All the way back to the start, then forward again replaying the input
+        while debugger.step_back().is_some() {}
+        assert_eq!(debugger.steps(), 0);
+        assert_eq!(debugger.interpreter().memory()[0], 0);
+        assert!(debugger.output().is_empty());
+        let entry = debugger.step(&mut input).unwrap().unwrap();
+        assert_eq!(entry.io, Some(Io::Input(Some(b'a'))));
+    }
+
+    #[test]
+    fn test_watch() {
+        let interpreter = Interpreter::new("+>+.<.", Extensions::default()).unwrap();
+        let mut debugger = Debugger::new(interpreter, 100);
+        debugger.watch(1);
+        let mut input = ProgramInput::new(&[]);
+        let mut hits = Vec::new();
+        while let Some(entry) = debugger.step(&mut input).unwrap() {
+            let entry = entry.clone();
+            if debugger.triggers_watch(&entry) {
+                hits.push((entry.source_pointer, entry.access));
+            }
+        }
+        assert_eq!(
+            hits,
+            [(2, Some((1, Access::Write))), (3, Some((1, Access::Read)))]
+        );
+        assert!(debugger.unwatch(1));
+        assert!(!debugger.unwatch(1));
+    }
+
+    #[test]
+    fn test_journal_is_bounded() {
+        let interpreter = Interpreter::new("+++++", Extensions::default()).unwrap();
+        let mut debugger = Debugger::new(interpreter, 2);
+        let mut input = ProgramInput::new(&[]);
+        while debugger.step(&mut input).unwrap().is_some() {}
+        assert!(debugger.step_back().is_some());
+        assert!(debugger.step_back().is_some());
+        assert_eq!(debugger.step_back(), None);
+        assert_eq!(debugger.interpreter().memory()[0], 3);
+
+        // No journal at all still steps, but can't go back
+        let interpreter = Interpreter::new("++", Extensions::default()).unwrap();
+        let mut debugger = Debugger::new(interpreter, 0);
+        assert_eq!(
+            debugger.step(&mut input).unwrap().map(|entry| entry.step),
+            Some(0)
+        );
+        assert!(debugger.step(&mut input).unwrap().is_some());
+        assert_eq!(debugger.step_back(), None);
+        assert_eq!(debugger.interpreter().memory()[0], 2);
+    }
+
+    #[test]
+    fn test_switching_tapes_writes_nothing() {
+        let extensions = Extensions {
+            dual_tape: true,
+            ..Extensions::default()
+        };
+        let interpreter = Interpreter::new("+}+", extensions).unwrap();
+        let mut debugger = Debugger::new(interpreter, 100);
+        let mut input = ProgramInput::new(&[]);
+        let mut writes = Vec::new();
+        while let Some(entry) = debugger.step(&mut input).unwrap() {
+            writes.push(entry.write);
+        }
+        assert_eq!(writes, [Some((0, 0, 1)), None, Some((0, 0, 1))]);
+    }
+}
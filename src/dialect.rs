@@ -0,0 +1,182 @@
+// Front-ends for languages that map one-to-one onto brainfuck commands.
+// Each dialect translates its source into plain brainfuck, which then goes
+// through the usual sanitize/run pipeline.
+
+use crate::Extensions;
+use std::path::Path;
+
+pub trait Dialect {
+    /// Name accepted by `--lang`.
+    fn name(&self) -> &'static str;
+    /// File extensions (without the dot) used to infer the dialect.
+    fn file_extensions(&self) -> &'static [&'static str];
+    /// Translates the source into brainfuck commands. Anything that isn't a
+    /// recognised token is treated as a comment and dropped.
+    fn translate(&self, source: &str) -> String;
+    /// The extensions its commands need, on top of those asked for.
+    fn extensions(&self, requested: Extensions) -> Extensions {
+        requested
+    }
+}
+
+pub struct Brainfuck;
+
+impl Dialect for Brainfuck {
+    fn name(&self) -> &'static str {
+        "brainfuck"
+    }
+
+    fn file_extensions(&self) -> &'static [&'static str] {
+        &["bf", "b"]
+    }
+
+    fn translate(&self, source: &str) -> String {
+        source.to_string()
+    }
+}
+
+/// Brainfuck plus `Y`, which forks a thread. The child gets a copy of the
+/// tapes, its pointer moved one cell right and that cell set to 1; the
+/// parent's cell is set to 0. Threads take turns one instruction at a time,
+/// so their output interleaves the same way on every run.
+pub struct Brainfork;
+
+impl Dialect for Brainfork {
+    fn name(&self) -> &'static str {
+        "brainfork"
+    }
+
+    fn file_extensions(&self) -> &'static [&'static str] {
+        &["bfork"]
+    }
+
+    fn translate(&self, source: &str) -> String {
+        source.to_string()
+    }
+
+    fn extensions(&self, requested: Extensions) -> Extensions {
+        Extensions {
+            fork: true,
+            ..requested
+        }
+    }
+}
+
+// Pairs of punctuation marks shared by Ook!, Blub and their short-hand form
+const PUNCTUATION_PAIRS: [((char, char), char); 8] = [
+    (('.', '?'), '>'),
+    (('?', '.'), '<'),
+    (('.', '.'), '+'),
+    (('!', '!'), '-'),
+    (('!', '.'), '.'),
+    (('.', '!'), ','),
+    (('!', '?'), '['),
+    (('?', '!'), ']'),
+];
+
+/// A dialect whose commands are pairs of tokens such as `Ook. Ook?`. With no
+/// `word` the tokens are the bare punctuation marks (short-hand Ook!).
+pub struct PunctuationDialect {
+    name: &'static str,
+    file_extensions: &'static [&'static str],
+    word: Option<&'static str>,
+}
+
+pub const OOK: PunctuationDialect = PunctuationDialect {
+    name: "ook",
+    file_extensions: &["ook"],
+    word: Some("Ook"),
+};
+
+pub const BLUB: PunctuationDialect = PunctuationDialect {
+    name: "blub",
+    file_extensions: &["blub"],
+    word: Some("Blub"),
+};
+
+pub const SHORT_OOK: PunctuationDialect = PunctuationDialect {
+    name: "ook-short",
+    file_extensions: &["sook"],
+    word: None,
+};
+
+impl PunctuationDialect {
+    fn tokens(&self, source: &str) -> Vec<char> {
+        let is_mark = |character: char| matches!(character, '.' | '?' | '!');
+        match self.word {
+            Some(word) => source
+                .match_indices(word)
+                .filter_map(|(index, _)| source[index + word.len()..].chars().next())
+                .filter(|character| is_mark(*character))
+                .collect(),
+            None => source
+                .chars()
+                .filter(|character| is_mark(*character))
+                .collect(),
+        }
+    }
+}
+
+impl Dialect for PunctuationDialect {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn file_extensions(&self) -> &'static [&'static str] {
+        self.file_extensions
+    }
+
+    fn translate(&self, source: &str) -> String {
+        self.tokens(source)
+            .chunks_exact(2)
+            .filter_map(|pair| {
+                PUNCTUATION_PAIRS
+                    .iter()
+                    .find(|(marks, _)| *marks == (pair[0], pair[1]))
+                    .map(|(_, command)| *command)
+            })
+            .collect()
+    }
+}
+
+pub fn all() -> Vec<&'static dyn Dialect> {
+    vec![&Brainfuck, &Brainfork, &OOK, &BLUB, &SHORT_OOK]
+}
+
+pub fn by_name(name: &str) -> Option<&'static dyn Dialect> {
+    all().into_iter().find(|dialect| dialect.name() == name)
+}
+
+pub fn from_path(path: &Path) -> Option<&'static dyn Dialect> {
+    let extension = path.extension()?.to_str()?;
+    all()
+        .into_iter()
+        .find(|dialect| dialect.file_extensions().contains(&extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(
+            OOK.translate("Ook. Ook? Ook. Ook. Ook! Ook?\nOok? Ook!"),
+            ">+[]"
+        );
+        assert_eq!(BLUB.translate("Blub! Blub. Blub. Blub!"), ".,");
+        assert_eq!(SHORT_OOK.translate(". . ! !"), "+-");
+        assert_eq!(Brainfuck.translate("+-"), "+-");
+
+        assert_eq!(by_name("blub").unwrap().name(), "blub");
+        assert_eq!(from_path(Path::new("hello.ook")).unwrap().name(), "ook");
+        assert!(from_path(Path::new("hello.txt")).is_none());
+        assert!(
+            by_name("brainfork")
+                .unwrap()
+                .extensions(Extensions::default())
+                .fork
+        );
+        assert!(!Brainfuck.extensions(Extensions::default()).fork);
+    }
+}
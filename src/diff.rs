@@ -0,0 +1,294 @@
+// `brainfuck-rs diff`: runs one program on two backends side by side and
+// reports where they first disagree. The backends don't execute the same
+// instructions (the optimizer's whole point is to execute fewer), so they're
+// kept in lock step on what the program does to the outside world instead:
+// each runs to its next output, input or halt, and those have to match one
+// for one. Once both halt, so do the tape and the pointer.
+
+use crate::bench::Backend;
+use crate::ir::Program;
+use crate::{json, Error, Extensions, Interpreter, StepResult, MEMORY_SIZE};
+use std::fmt;
+
+/// What a backend did next, and where: the index of the instruction in the
+/// program it ran.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub instruction: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    Output(u8),
+    Input,
+    Halted,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Divergence {
+    Event {
+        number: usize, // How many events both backends agreed on first
+        expected: Event,
+        actual: Event,
+    },
+    Pointer {
+        expected: usize,
+        actual: usize,
+    },
+    Cell {
+        cell: usize,
+        expected: u8,
+        actual: u8,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Same { events: usize },
+    Diverged(Divergence),
+    OutOfSteps { events: usize }, // Agreed on this many before a backend ran out
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventKind::Output(byte) => write!(formatter, "printed {}", byte),
+            EventKind::Input => write!(formatter, "read input"),
+            EventKind::Halted => write!(formatter, "halted"),
+        }
+    }
+}
+
+impl Event {
+    fn to_json(self) -> String {
+        let (kind, byte) = match self.kind {
+            EventKind::Output(byte) => ("output", byte.to_string()),
+            EventKind::Input => ("input", "null".to_string()),
+            EventKind::Halted => ("halted", "null".to_string()),
+        };
+        json::object(&[
+            ("kind", json::string(kind)),
+            ("byte", byte),
+            ("instruction", self.instruction.to_string()),
+        ])
+    }
+}
+
+impl Verdict {
+    pub fn to_json(&self) -> String {
+        let (verdict, mut fields) = match self {
+            Verdict::Same { events } => ("same", vec![("events", events.to_string())]),
+            Verdict::OutOfSteps { events } => {
+                ("out_of_steps", vec![("events", events.to_string())])
+            }
+            Verdict::Diverged(Divergence::Event {
+                number,
+                expected,
+                actual,
+            }) => (
+                "event",
+                vec![
+                    ("events", number.to_string()),
+                    ("expected", expected.to_json()),
+                    ("actual", actual.to_json()),
+                ],
+            ),
+            Verdict::Diverged(Divergence::Pointer { expected, actual }) => (
+                "pointer",
+                vec![
+                    ("expected", expected.to_string()),
+                    ("actual", actual.to_string()),
+                ],
+            ),
+            Verdict::Diverged(Divergence::Cell {
+                cell,
+                expected,
+                actual,
+            }) => (
+                "cell",
+                vec![
+                    ("cell", cell.to_string()),
+                    ("expected", expected.to_string()),
+                    ("actual", actual.to_string()),
+                ],
+            ),
+        };
+        fields.insert(0, ("verdict", json::string(verdict)));
+        json::object(&fields)
+    }
+}
+
+// One backend, stepped from event to event
+struct Runner<'a> {
+    interpreter: Interpreter,
+    input: std::slice::Iter<'a, u8>,
+    steps: u64,
+}
+
+impl Runner<'_> {
+    // The next event, or None if the step budget runs out first
+    fn next(&mut self, max_steps: u64) -> Result<Option<Event>, Error> {
+        loop {
+            if self.steps >= max_steps {
+                return Ok(None);
+            }
+            let instruction = self.interpreter.source_pointer();
+            let kind = match self.interpreter.step()? {
+                StepResult::Continue => None,
+                StepResult::Output(byte) => Some(EventKind::Output(byte)),
+                StepResult::NeedsInput => {
+                    self.interpreter.provide_input(self.input.next().copied());
+                    Some(EventKind::Input)
+                }
+                StepResult::Halted => Some(EventKind::Halted),
+            };
+            self.steps += 1;
+            if let Some(kind) = kind {
+                return Ok(Some(Event { kind, instruction }));
+            }
+        }
+    }
+}
+
+/// Runs sanitized source on `against` and on `backend` over the same input,
+/// each for at most `max_steps` instructions, and compares them.
+pub fn diff(
+    source: &str,
+    extensions: Extensions,
+    input: &[u8],
+    against: Backend,
+    backend: Backend,
+    max_steps: u64,
+) -> Result<Verdict, Error> {
+    compare(
+        against.compile(source)?,
+        backend.compile(source)?,
+        extensions,
+        input,
+        max_steps,
+    )
+}
+
+/// Like `diff`, for two programs that should do the same thing.
+pub fn compare(
+    expected: Program,
+    actual: Program,
+    extensions: Extensions,
+    input: &[u8],
+    max_steps: u64,
+) -> Result<Verdict, Error> {
+    let runner = |program: Program| -> Result<Runner, Error> {
+        Ok(Runner {
+            interpreter: Interpreter::from_program(program, extensions)?,
+            input: input.iter(),
+            steps: 0,
+        })
+    };
+    let (mut expected, mut actual) = (runner(expected)?, runner(actual)?);
+    let mut events = 0;
+    loop {
+        let (left, right) = match (expected.next(max_steps)?, actual.next(max_steps)?) {
+            (Some(left), Some(right)) => (left, right),
+            _ => return Ok(Verdict::OutOfSteps { events }),
+        };
+        if left.kind != right.kind {
+            return Ok(Verdict::Diverged(Divergence::Event {
+                number: events,
+                expected: left,
+                actual: right,
+            }));
+        }
+        events += 1;
+        if left.kind == EventKind::Halted {
+            break;
+        }
+    }
+    let (left, right) = (&expected.interpreter, &actual.interpreter);
+    if left.memory_pointer() != right.memory_pointer() {
+        return Ok(Verdict::Diverged(Divergence::Pointer {
+            expected: left.memory_pointer(),
+            actual: right.memory_pointer(),
+        }));
+    }
+    match (0..MEMORY_SIZE).find(|cell| left.memory()[*cell] != right.memory()[*cell]) {
+        Some(cell) => Ok(Verdict::Diverged(Divergence::Cell {
+            cell,
+            expected: left.memory()[cell],
+            actual: right.memory()[cell],
+        })),
+        None => Ok(Verdict::Same { events }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse;
+
+    #[test]
+    fn test_diff() {
+        let verdict = diff(
+            "+[,.]>>+++[-<+>]",
+            Extensions::default(),
+            b"ab\0",
+            Backend::Interpreter,
+            Backend::Optimizer,
+            1000,
+        );
+        // Three inputs, three outputs and the halt
+        assert_eq!(verdict.unwrap(), Verdict::Same { events: 7 });
+        let verdict = diff(
+            "+[]",
+            Extensions::default(),
+            b"",
+            Backend::Interpreter,
+            Backend::Optimizer,
+            1000,
+        );
+        assert_eq!(verdict.unwrap(), Verdict::OutOfSteps { events: 0 });
+    }
+
+    #[test]
+    fn test_compare() {
+        let verdict = |expected: &str, actual: &str| {
+            compare(
+                parse(expected),
+                parse(actual),
+                Extensions::default(),
+                b"",
+                1000,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            verdict("+.", "++."),
+            Verdict::Diverged(Divergence::Event {
+                number: 0,
+                expected: Event {
+                    kind: EventKind::Output(1),
+                    instruction: 1
+                },
+                actual: Event {
+                    kind: EventKind::Output(2),
+                    instruction: 2
+                },
+            })
+        );
+        assert_eq!(
+            verdict(">+<", "+"),
+            Verdict::Diverged(Divergence::Cell {
+                cell: 0,
+                expected: 0,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            verdict(">", ""),
+            Verdict::Diverged(Divergence::Pointer {
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
+}
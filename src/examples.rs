@@ -0,0 +1,72 @@
+// Classic programs built into the binary, so there's something to run (and
+// to test against) without hunting for .b files: `brainfuck-rs examples`
+// lists them and `brainfuck-rs run --example NAME` runs one.
+
+#[derive(Debug, PartialEq)]
+pub struct Example {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: &'static str,
+}
+
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        name: "hello",
+        description: "prints Hello World!",
+        source: include_str!("../examples/hello.b"),
+    },
+    Example {
+        name: "fib",
+        description: "prints the first eleven Fibonacci numbers",
+        source: include_str!("../examples/fib.b"),
+    },
+    Example {
+        name: "rot13",
+        description: "ROT13s its input until the input runs out",
+        source: include_str!("../examples/rot13.b"),
+    },
+    Example {
+        name: "mandelbrot",
+        description: "draws the Mandelbrot set as ASCII art (takes a while)",
+        source: include_str!("../examples/mandelbrot.b"),
+    },
+];
+
+pub fn by_name(name: &str) -> Option<&'static Example> {
+    EXAMPLES.iter().find(|example| example.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzz::{execute, execute_program};
+    use crate::{ir, optimize};
+
+    fn output(name: &str, input: &[u8], max_steps: u64) -> Vec<u8> {
+        execute(by_name(name).unwrap().source, input, max_steps)
+            .unwrap()
+            .output
+    }
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(output("hello", b"", 10_000), b"Hello World!\n");
+        assert_eq!(
+            output("fib", b"", 1_000_000),
+            b"1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89"
+        );
+        assert_eq!(output("rot13", b"Hello, World!", 100_000), b"Uryyb, Jbeyq!");
+        assert!(by_name("nope").is_none());
+    }
+
+    #[test]
+    fn test_mandelbrot() {
+        let (program, _) = optimize::optimize(&ir::parse(by_name("mandelbrot").unwrap().source));
+        let output = execute_program(program, b"", u64::MAX).unwrap().output;
+        let rows: Vec<&[u8]> = output.split(|byte| *byte == b'\n').collect();
+        assert_eq!(rows.len(), 23); // 22 rows, each ending in a newline
+        assert!(rows[..22].iter().all(|row| row.len() == 52));
+        // The middle of the main cardioid never escapes
+        assert_eq!(rows[10][35], b'#');
+    }
+}
@@ -0,0 +1,307 @@
+// `brainfuck-rs explain`: lifts a program into the optimized IR and prints
+// it as structured pseudo-code, labelling common idioms (clearing a cell,
+// copying or multiplying one cell into others).
+//
+// Cells are named absolutely (`cell[3]`) for as long as the pointer is
+// statically known. A loop that moves the pointer by a different amount
+// each time round makes it unknown, so from there on cells are named
+// relative to an explicit pointer `p`, as they are inside pbrain procedures.
+
+use crate::ir::{matching_loop_end, Instruction};
+use crate::optimize::{fold_runs, remove_dead_loops};
+use crate::MEMORY_SIZE;
+
+const INDENT: &str = "    ";
+
+#[derive(Clone, Copy)]
+enum Position {
+    Absolute(isize),
+    Relative(isize), // Offset from `p`
+}
+
+impl Position {
+    fn offset(self, amount: isize) -> Position {
+        match self {
+            Position::Absolute(cell) => Position::Absolute(cell + amount),
+            Position::Relative(offset) => Position::Relative(offset + amount),
+        }
+    }
+
+    fn name(self) -> String {
+        match self {
+            Position::Absolute(cell) => format!("cell[{}]", cell.rem_euclid(MEMORY_SIZE as isize)),
+            Position::Relative(0) => "cell[p]".to_string(),
+            Position::Relative(offset) if offset < 0 => format!("cell[p-{}]", -offset),
+            Position::Relative(offset) => format!("cell[p+{}]", offset),
+        }
+    }
+}
+
+// How far a block moves the pointer, or `None` if that depends on the data
+fn net_move(block: &[Instruction]) -> Option<isize> {
+    let mut total = 0;
+    let mut index = 0;
+    while index < block.len() {
+        match block[index] {
+            Instruction::Move(amount) => total += amount,
+            Instruction::LoopStart => {
+                let end = matching_loop_end(block, index);
+                if net_move(&block[index + 1..end]) != Some(0) {
+                    return None;
+                }
+                index = end;
+            }
+            // Defining a procedure doesn't run it, but calling one could move anywhere
+            Instruction::ProcedureStart => index = matching_loop_end(block, index),
+            Instruction::Call => return None,
+            // Switching tapes swaps in another pointer, and a forked child
+            // carries on one cell along
+            Instruction::SelectTape(_) | Instruction::Fork => return None,
+            _ => {}
+        }
+        index += 1;
+    }
+    Some(total)
+}
+
+// `cell += amount`, with big amounts shown as the subtraction they wrap to
+fn add_statement(cell: &str, amount: u8) -> String {
+    if amount > 128 {
+        format!("{} -= {}", cell, 256 - amount as u16)
+    } else {
+        format!("{} += {}", cell, amount)
+    }
+}
+
+struct Explainer {
+    lines: Vec<String>,
+    depth: usize,
+}
+
+impl Explainer {
+    fn emit(&mut self, line: String) {
+        self.lines
+            .push(format!("{}{}", INDENT.repeat(self.depth), line));
+    }
+
+    // Makes `p` point at `position`, returning the position as seen from `p`
+    fn materialize(&mut self, position: Position) -> Position {
+        match position {
+            Position::Absolute(cell) => {
+                self.emit(format!("p = {}", cell.rem_euclid(MEMORY_SIZE as isize)))
+            }
+            Position::Relative(0) => {}
+            Position::Relative(offset) if offset < 0 => self.emit(format!("p -= {}", -offset)),
+            Position::Relative(offset) => self.emit(format!("p += {}", offset)),
+        }
+        Position::Relative(0)
+    }
+
+    // Recognizes loops made only of adds and moves that return to the
+    // counter and decrement it once: clears, copies and multiplications
+    fn idiom(&mut self, body: &[Instruction], position: Position) -> bool {
+        if let [Instruction::Add(1 | 255)] = body {
+            self.emit(format!("{} = 0  // clear", position.name()));
+            return true;
+        }
+        let mut offset = 0;
+        let mut adds: Vec<(isize, u8)> = Vec::new();
+        for instruction in body {
+            let (cell, amount) = match *instruction {
+                Instruction::Add(amount) => (offset, amount),
+                Instruction::AddAt(at, amount) => (offset + at, amount),
+                Instruction::Move(amount) => {
+                    offset += amount;
+                    continue;
+                }
+                _ => return false,
+            };
+            match adds.iter_mut().find(|(at, _)| *at == cell) {
+                Some((_, total)) => *total = total.wrapping_add(amount),
+                None => adds.push((cell, amount)),
+            }
+        }
+        let counter = adds
+            .iter()
+            .find(|(at, _)| *at == 0)
+            .map(|(_, amount)| *amount);
+        if offset != 0 || counter != Some(255) {
+            return false;
+        }
+        let counter_name = position.name();
+        for (at, amount) in adds.iter().filter(|(at, _)| *at != 0) {
+            let target = position.offset(*at).name();
+            match amount {
+                1 => self.emit(format!("{} += {}  // copy", target, counter_name)),
+                255 => self.emit(format!("{} -= {}  // copy", target, counter_name)),
+                amount if *amount > 128 => self.emit(format!(
+                    "{} -= {} * {}  // multiply",
+                    target,
+                    counter_name,
+                    256 - *amount as u16
+                )),
+                amount => self.emit(format!(
+                    "{} += {} * {}  // multiply",
+                    target, counter_name, amount
+                )),
+            }
+        }
+        self.emit(format!("{} = 0", counter_name));
+        true
+    }
+
+    fn block(&mut self, block: &[Instruction], mut position: Position) -> Position {
+        let mut index = 0;
+        while index < block.len() {
+            match block[index] {
+                Instruction::Add(amount) => self.emit(add_statement(&position.name(), amount)),
+                Instruction::AddAt(at, amount) => {
+                    self.emit(add_statement(&position.offset(at).name(), amount))
+                }
+                Instruction::Move(amount) => position = position.offset(amount),
+                Instruction::Output => self.emit(format!("print({})", position.name())),
+                Instruction::Input => self.emit(format!("{} = read()", position.name())),
+                Instruction::Debug => self.emit("debug()".to_string()),
+                Instruction::LoopStart => {
+                    let end = matching_loop_end(block, index);
+                    let body = &block[index + 1..end];
+                    index = end;
+                    if self.idiom(body, position) {
+                        index += 1;
+                        continue;
+                    }
+                    let balanced = net_move(body) == Some(0);
+                    if !balanced {
+                        position = self.materialize(position);
+                    }
+                    self.emit(format!("while {} {{", position.name()));
+                    self.depth += 1;
+                    let after_body = self.block(body, position);
+                    if !balanced {
+                        self.materialize(after_body);
+                    }
+                    self.depth -= 1;
+                    self.emit("}".to_string());
+                }
+                // A procedure runs wherever it's called from, so its body
+                // is explained relative to `p`
+                Instruction::ProcedureStart => {
+                    let end = matching_loop_end(block, index);
+                    self.emit(format!("procedure {} {{", position.name()));
+                    self.depth += 1;
+                    let after_body = self.block(&block[index + 1..end], Position::Relative(0));
+                    self.materialize(after_body);
+                    self.depth -= 1;
+                    self.emit("}".to_string());
+                    index = end;
+                }
+                Instruction::Call => {
+                    position = self.materialize(position);
+                    self.emit(format!("call({})", position.name()));
+                }
+                // Each tape has its own `p`, so it has to be up to date first
+                Instruction::SelectTape(tape) => {
+                    position = self.materialize(position);
+                    self.emit(format!("select_tape({})", tape));
+                }
+                Instruction::Fork => {
+                    position = self.materialize(position);
+                    self.emit("fork()".to_string());
+                }
+                Instruction::LoopEnd | Instruction::ProcedureEnd => {
+                    unreachable!("loop and procedure ends are consumed with their starts")
+                }
+            }
+            index += 1;
+        }
+        position
+    }
+}
+
+/// Pseudo-code for a program with balanced brackets, one statement per line.
+pub fn explain(program: &[Instruction]) -> String {
+    // Not all of `optimize`: folding loops with a known trip count would
+    // leave the idioms nothing to label
+    let (program, _) = remove_dead_loops(&fold_runs(program));
+    let mut explainer = Explainer {
+        lines: Vec::new(),
+        depth: 0,
+    };
+    explainer.block(&program, Position::Absolute(0));
+    explainer
+        .lines
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// Like `explain`, but for a fragment out of context: cells are named
+/// relative to wherever the pointer is when the fragment starts, and a final
+/// `p` update shows where it leaves the pointer.
+pub fn explain_fragment(fragment: &[Instruction]) -> String {
+    // Not `remove_dead_loops`: the tape isn't known to be blank where a fragment
+    // starts, so its leading loops aren't dead
+    let fragment = fold_runs(fragment);
+    let mut explainer = Explainer {
+        lines: Vec::new(),
+        depth: 0,
+    };
+    let end = explainer.block(&fragment, Position::Relative(0));
+    explainer.materialize(end);
+    explainer
+        .lines
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse;
+
+    #[test]
+    fn test_explain_idioms() {
+        assert_eq!(
+            explain(&parse("+++++[->++>+<<]>>[-]<.")),
+            "cell[0] += 5\n\
+             cell[1] += cell[0] * 2  // multiply\n\
+             cell[2] += cell[0]  // copy\n\
+             cell[0] = 0\n\
+             cell[2] = 0  // clear\n\
+             print(cell[1])\n"
+        );
+    }
+
+    #[test]
+    fn test_explain_loops() {
+        assert_eq!(
+            explain(&parse(",[.-->,]+[>+<-.]")),
+            "cell[0] = read()\n\
+             p = 0\n\
+             while cell[p] {\n    \
+                 print(cell[p])\n    \
+                 cell[p] -= 2\n    \
+                 cell[p+1] = read()\n    \
+                 p += 1\n\
+             }\n\
+             cell[p] += 1\n\
+             while cell[p] {\n    \
+                 cell[p+1] += 1\n    \
+                 cell[p] -= 1\n    \
+                 print(cell[p])\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_explain_fragment() {
+        assert_eq!(
+            explain_fragment(&parse("[->+<]>>+")),
+            "cell[p+1] += cell[p]  // copy\n\
+             cell[p] = 0\n\
+             cell[p+2] += 1\n\
+             p += 2\n"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+// C API for embedding the interpreter from C, C++ or Python (via ctypes).
+// The declarations live in `include/brainfuck_rs.h`. Nothing here panics
+// across the boundary: failures are reported through a `bf_error` out
+// parameter, which callers may pass as null if they don't care.
+
+use crate::limits::{Limits, Sandbox};
+use crate::{run_interpreter, sanitize_input, Error, Extensions, Interpreter, ProgramInput};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+pub const BF_OK: i32 = 0;
+pub const BF_ERROR_MISMATCHED_BRACKETS: i32 = 1;
+pub const BF_ERROR_IO: i32 = 2;
+pub const BF_ERROR_INVALID_ARGUMENT: i32 = 3;
+pub const BF_ERROR_INTERNAL: i32 = 4;
+pub const BF_ERROR_LIMIT: i32 = 5;
+
+const MESSAGE_SIZE: usize = 128;
+
+#[repr(C)]
+pub struct bf_error {
+    pub code: i32,
+    pub position: usize, // Index of the offending command, if any
+    pub message: [c_char; MESSAGE_SIZE],
+}
+
+/// A validated program, opaque to C.
+pub struct bf_program {
+    interpreter: Interpreter, // Cloned for each run, so the source is only parsed once
+}
+
+unsafe fn report(error: *mut bf_error, code: i32, position: usize, message: &str) {
+    if error.is_null() {
+        return;
+    }
+    let error = &mut *error;
+    error.code = code;
+    error.position = position;
+    let bytes = message.as_bytes();
+    let len = bytes.len().min(MESSAGE_SIZE - 1);
+    for (target, byte) in error.message.iter_mut().zip(&bytes[..len]) {
+        *target = *byte as c_char;
+    }
+    error.message[len] = 0;
+}
+
+unsafe fn report_error(error: *mut bf_error, failure: &Error) -> i32 {
+    let (code, position) = match failure {
+        Error::MismatchedBrackets(index) => (BF_ERROR_MISMATCHED_BRACKETS, *index),
+        Error::Io(_) | Error::OutputFailed => (BF_ERROR_IO, 0),
+        Error::LimitExceeded(_) => (BF_ERROR_LIMIT, 0),
+        // Runs here are never interrupted, and don't enable pbrain
+        Error::Interrupted | Error::UndefinedProcedure(_) | Error::CallStackOverflow => {
+            (BF_ERROR_INTERNAL, 0)
+        }
+    };
+    report(error, code, position, &failure.to_string());
+    code
+}
+
+/// Checks and stores a program, returning null (and filling in `error`) if
+/// it can't be run.
+///
+/// # Safety
+///
+/// `source` must point to `source_len` readable bytes and `error` must be
+/// null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bf_compile(
+    source: *const c_char,
+    source_len: usize,
+    error: *mut bf_error,
+) -> *mut bf_program {
+    report(error, BF_OK, 0, "");
+    if source.is_null() {
+        report(error, BF_ERROR_INVALID_ARGUMENT, 0, "source is null");
+        return std::ptr::null_mut();
+    }
+    let bytes = std::slice::from_raw_parts(source as *const u8, source_len);
+    let source = sanitize_input(&String::from_utf8_lossy(bytes), Extensions::default());
+    match Interpreter::new(&source, Extensions::default()) {
+        Ok(interpreter) => Box::into_raw(Box::new(bf_program { interpreter })),
+        Err(failure) => {
+            report_error(error, &failure);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Runs a compiled program over `input` (end of input leaves the cell
+/// unchanged) for at most `max_steps` instructions, or without a limit if
+/// it's 0. On success `*output` receives a buffer of `*output_len` bytes
+/// that must be released with `bf_free_output`. Returns the error code,
+/// `BF_ERROR_LIMIT` if the program ran out of steps.
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`, `input` must point to `input_len`
+/// readable bytes (or be null when `input_len` is 0), `output` and
+/// `output_len` must be valid for writes and `error` must be null or valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bf_run(
+    program: *const bf_program,
+    input: *const u8,
+    input_len: usize,
+    max_steps: u64,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+    error: *mut bf_error,
+) -> i32 {
+    report(error, BF_OK, 0, "");
+    if program.is_null() || output.is_null() || output_len.is_null() {
+        report(error, BF_ERROR_INVALID_ARGUMENT, 0, "null argument");
+        return BF_ERROR_INVALID_ARGUMENT;
+    }
+    let input = if input.is_null() {
+        &[][..]
+    } else {
+        std::slice::from_raw_parts(input, input_len)
+    };
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut sandbox = Sandbox::new(Limits {
+            max_steps: Some(max_steps).filter(|steps| *steps > 0),
+            ..Limits::default()
+        });
+        let mut bytes = Vec::new();
+        run_interpreter(
+            (*program).interpreter.clone(),
+            &mut ProgramInput::new(input).with_reader(Box::new(std::io::empty())),
+            &mut bytes,
+            &mut sandbox,
+        )
+        .map(|()| bytes)
+    }));
+    match result {
+        Ok(Ok(bytes)) => {
+            let mut bytes = bytes.into_boxed_slice();
+            *output_len = bytes.len();
+            *output = bytes.as_mut_ptr();
+            std::mem::forget(bytes);
+            BF_OK
+        }
+        Ok(Err(failure)) => report_error(error, &failure),
+        Err(_) => {
+            report(error, BF_ERROR_INTERNAL, 0, "the interpreter panicked");
+            BF_ERROR_INTERNAL
+        }
+    }
+}
+
+/// # Safety
+///
+/// `program` must be null or come from `bf_compile` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bf_free(program: *mut bf_program) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+/// # Safety
+///
+/// `output` and `output_len` must come from a successful `bf_run` and not
+/// have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bf_free_output(output: *mut u8, output_len: usize) {
+    if !output.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            output, output_len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn empty_error() -> bf_error {
+        bf_error {
+            code: -1,
+            position: 0,
+            message: [0; MESSAGE_SIZE],
+        }
+    }
+
+    #[test]
+    fn test_compile_and_run() {
+        let mut error = empty_error();
+        let source = b",+.";
+        unsafe {
+            let program = bf_compile(source.as_ptr() as *const c_char, source.len(), &mut error);
+            assert!(!program.is_null());
+            assert_eq!(error.code, BF_OK);
+
+            let (mut output, mut output_len) = (std::ptr::null_mut(), 0);
+            let code = bf_run(
+                program,
+                b"a".as_ptr(),
+                1,
+                0,
+                &mut output,
+                &mut output_len,
+                &mut error,
+            );
+            assert_eq!(code, BF_OK);
+            assert_eq!(std::slice::from_raw_parts(output, output_len), b"b");
+            bf_free_output(output, output_len);
+            bf_free(program);
+
+            let program = bf_compile(b"+[]".as_ptr() as *const c_char, 3, &mut error);
+            let code = bf_run(
+                program,
+                std::ptr::null(),
+                0,
+                100,
+                &mut output,
+                &mut output_len,
+                &mut error,
+            );
+            assert_eq!(code, BF_ERROR_LIMIT);
+            bf_free(program);
+
+            let program = bf_compile(b"+]".as_ptr() as *const c_char, 2, &mut error);
+            assert!(program.is_null());
+            assert_eq!(error.code, BF_ERROR_MISMATCHED_BRACKETS);
+            assert_eq!(error.position, 1);
+            let message = CStr::from_ptr(error.message.as_ptr());
+            assert_eq!(message.to_str(), Ok("mismatched bracket at index 1"));
+        }
+    }
+
+    #[test]
+    fn test_header_matches_exports() {
+        // The header is written by hand, so check it against this file: every
+        // exported function is declared with as many parameters, and every
+        // error code is defined with the same value
+        let header = include_str!("../include/brainfuck_rs.h");
+        let header: String = header.split_whitespace().collect::<Vec<_>>().join(" ");
+        let source = include_str!("ffi.rs");
+        let mut exports = 0;
+        for rest in source.split("pub unsafe extern \"C\" fn ").skip(1) {
+            let (name, rest) = rest.split_once('(').unwrap();
+            let parameters = rest.split_once(')').unwrap().0;
+            let arity = parameters.matches(':').count();
+            let declaration = header
+                .split_once(&format!("{}(", name))
+                .map(|(_, rest)| rest.split_once(')').unwrap().0)
+                .unwrap_or_else(|| panic!("{} is missing from the header", name));
+            assert_eq!(
+                declaration.split(',').count(),
+                arity,
+                "{} takes a different number of parameters in the header",
+                name
+            );
+            exports += 1;
+        }
+        assert_eq!(exports, 4);
+        for line in source.lines() {
+            if let Some(constant) = line.strip_prefix("pub const ") {
+                let (name, value) = constant.split_once(": i32 = ").unwrap();
+                let define = format!("#define {} {}", name, value.trim_end_matches(';'));
+                assert!(
+                    header.contains(&define),
+                    "{} is missing from the header",
+                    define
+                );
+            }
+        }
+        assert!(header.contains("typedef struct bf_error"));
+        assert!(header.contains("typedef struct bf_program"));
+    }
+}
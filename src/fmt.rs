@@ -0,0 +1,107 @@
+// Source formatting for `brainfuck-rs fmt`. Everything here works on
+// sanitized source, so comments and stray whitespace are already gone.
+
+use crate::{ir, optimize};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct FormatOptions {
+    pub wrap: Option<usize>, // Maximum number of commands per line
+    pub pretty: bool,        // One loop bracket per line, bodies indented
+    pub minify: bool,        // Fold runs of +-/<> down to their net effect
+}
+
+const INDENT: &str = "  ";
+
+pub fn format(source: &str, options: &FormatOptions) -> String {
+    let source = if options.minify {
+        minify(source)
+    } else {
+        source.to_string()
+    };
+    let lines = if options.pretty {
+        pretty_lines(&source)
+    } else {
+        vec![(0, source)]
+    };
+
+    let mut formatted = String::new();
+    for (depth, line) in lines {
+        for chunk in wrap(&line, options.wrap) {
+            formatted.push_str(&INDENT.repeat(depth));
+            formatted.push_str(chunk);
+            formatted.push('\n');
+        }
+    }
+    formatted
+}
+
+/// Replaces every run of `+`/`-` and `<`/`>` with the shortest sequence of
+/// repeated characters that has the same effect.
+pub fn minify(source: &str) -> String {
+    ir::to_source(&optimize::fold_runs(&ir::parse(source)))
+}
+
+// Splits the source into (depth, line) pairs with every bracket on its own line
+fn pretty_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut depth: usize = 0;
+    for character in source.chars() {
+        match character {
+            '[' | ']' => {
+                if !current.is_empty() {
+                    lines.push((depth, std::mem::take(&mut current)));
+                }
+                if character == ']' {
+                    depth = depth.saturating_sub(1);
+                }
+                lines.push((depth, character.to_string()));
+                if character == '[' {
+                    depth += 1;
+                }
+            }
+            _ => current.push(character),
+        }
+    }
+    if !current.is_empty() {
+        lines.push((depth, current));
+    }
+    lines
+}
+
+fn wrap(line: &str, columns: Option<usize>) -> Vec<&str> {
+    match columns {
+        Some(columns) if columns > 0 && line.len() > columns => (0..line.len())
+            .step_by(columns)
+            .map(|start| &line[start..std::cmp::min(line.len(), start + columns)])
+            .collect(),
+        _ => vec![line],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minify() {
+        assert_eq!(minify("+++--><<>>>.[-]"), "+>>.[-]");
+        assert_eq!(minify(&"+".repeat(255)), "-");
+        assert_eq!(minify("+-<>"), "");
+    }
+
+    #[test]
+    fn test_format() {
+        let pretty = FormatOptions {
+            pretty: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(format("++[>+<-]>.", &pretty), "++\n[\n  >+<-\n]\n>.\n");
+
+        let wrapped = FormatOptions {
+            wrap: Some(4),
+            ..FormatOptions::default()
+        };
+        assert_eq!(format("++++++", &wrapped), "++++\n++\n");
+    }
+}
@@ -0,0 +1,208 @@
+// Differential fuzzing: random well-bracketed programs are run both as
+// written and after `optimize`, and must behave identically. Everything is
+// driven by a seeded generator so any failure can be replayed exactly.
+
+use crate::ir::{self, Program};
+use crate::{optimize, Extensions, Interpreter, Memory, StepResult};
+use std::io::{self, Read};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+const COMMANDS: &[u8] = b"+-<>.,[]";
+const INPUT_BYTES: usize = 8; // Input handed to each program before end of input
+
+/// A small xorshift generator; good enough for picking commands and
+/// reproducible from its seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Xorshift gets stuck at zero, and nearby seeds should still diverge
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// An endless stream of pseudo-random bytes from a seed, for feeding `,`
+/// (see `ProgramInput::from_reader`). Reads never run out.
+pub struct RandomBytes(Rng);
+
+impl RandomBytes {
+    pub fn new(seed: u64) -> RandomBytes {
+        RandomBytes(Rng::new(seed))
+    }
+}
+
+impl Read for RandomBytes {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        for chunk in buffer.chunks_mut(8) {
+            let bytes = self.0.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(buffer.len())
+    }
+}
+
+/// Generates a program of at most `max_len` commands with balanced brackets.
+pub fn generate_program(rng: &mut Rng, max_len: usize) -> String {
+    let len = rng.below(max_len + 1);
+    let mut program = String::new();
+    let mut depth = 0;
+    while program.len() + depth < len {
+        let command = COMMANDS[rng.below(COMMANDS.len())];
+        match command {
+            b'[' => depth += 1,
+            b']' if depth == 0 => continue,
+            b']' => depth -= 1,
+            _ => {}
+        }
+        program.push(command as char);
+    }
+    program.push_str(&"]".repeat(depth));
+    program
+}
+
+/// How far a program got within its step budget.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub output: Vec<u8>,
+    pub memory: Memory,
+    pub memory_pointer: usize,
+    pub halted: bool, // False if the step budget ran out first
+}
+
+/// Runs sanitized source over `input` for at most `max_steps` instructions.
+pub fn execute(source: &str, input: &[u8], max_steps: u64) -> Result<Outcome, String> {
+    execute_program(ir::parse(source), input, max_steps)
+}
+
+/// Like `execute`, for a program that may have been optimized.
+pub fn execute_program(program: Program, input: &[u8], max_steps: u64) -> Result<Outcome, String> {
+    let mut interpreter = Interpreter::from_program(program, Extensions::default())
+        .map_err(|error| error.to_string())?;
+    let mut input = input.iter().copied();
+    let mut output = Vec::new();
+    let mut steps = 0;
+    let halted = loop {
+        if steps >= max_steps {
+            break false;
+        }
+        match interpreter.step().map_err(|error| error.to_string())? {
+            StepResult::Output(byte) => output.push(byte),
+            StepResult::NeedsInput => {
+                interpreter.provide_input(input.next());
+                continue;
+            }
+            StepResult::Continue => {}
+            StepResult::Halted => break true,
+        }
+        steps += 1;
+    };
+    Ok(Outcome {
+        output,
+        memory: *interpreter.memory(),
+        memory_pointer: interpreter.memory_pointer(),
+        halted,
+    })
+}
+
+/// Checks that the optimized form of `source` behaves like the original.
+/// Optimizing only ever removes work, so whenever the original halts within
+/// the budget the optimized program must too, with the same output and tape.
+pub fn check_program(source: &str, input: &[u8], max_steps: u64) -> Result<(), String> {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let expected = execute(source, input, max_steps)?;
+        let optimized = optimize::optimize(&ir::parse(source)).0;
+        let actual = execute_program(optimized.clone(), input, max_steps)?;
+        if expected.halted && actual != expected {
+            return Err(format!(
+                "optimized program {:?} diverged: expected output {:?}, got {:?}",
+                ir::to_source(&optimized),
+                expected.output,
+                actual.output
+            ));
+        }
+        Ok(())
+    }));
+    result.unwrap_or_else(|_| Err("the interpreter panicked".to_string()))
+}
+
+/// A program for which `check_program` failed, with what's needed to replay it.
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    pub source: String,
+    pub input: Vec<u8>,
+    pub message: String,
+}
+
+/// Generates and checks `iterations` programs from `seed`, stopping at the
+/// first failure.
+pub fn fuzz(
+    seed: u64,
+    iterations: u64,
+    max_len: usize,
+    max_steps: u64,
+) -> Result<(), Box<Failure>> {
+    let mut rng = Rng::new(seed);
+    for _ in 0..iterations {
+        let source = generate_program(&mut rng, max_len);
+        let input: Vec<u8> = (0..INPUT_BYTES).map(|_| rng.next_u64() as u8).collect();
+        if let Err(message) = check_program(&source, &input, max_steps) {
+            return Err(Box::new(Failure {
+                source,
+                input,
+                message,
+            }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_program() {
+        let mut rng = Rng::new(7);
+        for _ in 0..100 {
+            let program = generate_program(&mut rng, 40);
+            assert!(program.len() <= 40);
+            assert!(crate::generate_jump_table(&program).is_ok());
+        }
+        let first = generate_program(&mut Rng::new(3), 40);
+        assert_eq!(first, generate_program(&mut Rng::new(3), 40));
+    }
+
+    #[test]
+    fn test_fuzz() {
+        assert_eq!(fuzz(1, 300, 30, 2_000), Ok(()));
+        let outcome = execute(",+.", b"a", 100).unwrap();
+        assert_eq!(outcome.output, b"b");
+        assert!(outcome.halted);
+        assert!(!execute("+[]", b"", 100).unwrap().halted);
+    }
+
+    #[test]
+    fn test_random_bytes() {
+        let read = |seed, length| {
+            let mut bytes = vec![0; length];
+            RandomBytes::new(seed).read_exact(&mut bytes).unwrap();
+            bytes
+        };
+        assert_eq!(read(5, 100), read(5, 100));
+        assert_ne!(read(5, 100), read(6, 100));
+        // Reads of any size continue the same stream
+        assert_eq!(&read(5, 100)[..13], &read(5, 13)[..]);
+    }
+}
@@ -0,0 +1,151 @@
+// Golden-file tests: every `name.bf` in a directory is run with `name.in`
+// as input (if present) and its output compared against `name.out`. Each
+// program runs both as written and optimized, so the suite covers the
+// interpreter and the optimizer at once.
+
+use crate::fuzz::execute_program;
+use crate::{ir, optimize, sanitize_input, Extensions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A program and its expectation files.
+#[derive(Debug, PartialEq)]
+pub struct Case {
+    pub name: String,
+    pub source: PathBuf,
+    pub input: Option<PathBuf>,
+    pub expected: PathBuf,
+}
+
+/// Finds the cases in `dir`, sorted by name. Programs without a `.out` file
+/// are skipped, since there's nothing to compare them with.
+pub fn find_cases(dir: &Path) -> io::Result<Vec<Case>> {
+    let mut cases = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let source = entry?.path();
+        if source.extension().and_then(|extension| extension.to_str()) != Some("bf") {
+            continue;
+        }
+        let expected = source.with_extension("out");
+        if !expected.is_file() {
+            continue;
+        }
+        let input = source.with_extension("in");
+        cases.push(Case {
+            name: source.file_stem().unwrap().to_string_lossy().into_owned(),
+            input: input.is_file().then_some(input),
+            source,
+            expected,
+        });
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cases)
+}
+
+/// Runs a case, returning a description of every way it failed (empty if it
+/// passed).
+pub fn run_case(case: &Case, max_steps: u64) -> io::Result<Vec<String>> {
+    let source = sanitize_input(
+        &std::fs::read_to_string(&case.source)?,
+        Extensions::default(),
+    );
+    let input = match &case.input {
+        Some(path) => std::fs::read(path)?,
+        None => Vec::new(),
+    };
+    let expected = std::fs::read(&case.expected)?;
+
+    let mut failures = Vec::new();
+    let unoptimized = ir::parse(&source);
+    let optimized = match crate::generate_jump_table(&source) {
+        Ok(_) => optimize::optimize(&unoptimized).0,
+        Err(_) => unoptimized.clone(), // Reported by the unoptimized run below
+    };
+    for (backend, program) in [("interpreter", unoptimized), ("optimizer", optimized)] {
+        match execute_program(program, &input, max_steps) {
+            Err(error) => failures.push(format!("{}: {}", backend, error)),
+            Ok(outcome) if !outcome.halted => {
+                failures.push(format!("{}: step limit of {} exceeded", backend, max_steps))
+            }
+            Ok(outcome) if outcome.output != expected => failures.push(format!(
+                "{}: output differs\n{}",
+                backend,
+                diff(
+                    &String::from_utf8_lossy(&expected),
+                    &String::from_utf8_lossy(&outcome.output)
+                )
+            )),
+            Ok(_) => {}
+        }
+    }
+    Ok(failures)
+}
+
+/// A line diff of `expected` against `actual`, with `-` marking lines that
+/// are missing and `+` lines that are unexpected.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    // lengths[i][j]: longest common subsequence of expected[i..] and actual[j..]
+    let mut lengths = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == actual.len()
+            || (i < expected.len() && lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), "  a\n- b\n+ x\n  c");
+        assert_eq!(diff("a", "a\nb"), "  a\n+ b");
+    }
+
+    #[test]
+    fn test_run_cases() {
+        let dir = std::env::temp_dir().join(format!("bf-golden-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("echo.bf"), ",+.,+.").unwrap();
+        std::fs::write(dir.join("echo.in"), "ab").unwrap();
+        std::fs::write(dir.join("echo.out"), "bc").unwrap();
+        std::fs::write(dir.join("wrong.bf"), "+++[-]+.").unwrap();
+        std::fs::write(dir.join("wrong.out"), "\u{2}").unwrap();
+        std::fs::write(dir.join("untested.bf"), "+").unwrap();
+
+        let cases = find_cases(&dir).unwrap();
+        let names: Vec<&str> = cases.iter().map(|case| case.name.as_str()).collect();
+        assert_eq!(names, ["echo", "wrong"]);
+        assert_eq!(run_case(&cases[0], 1_000).unwrap(), Vec::<String>::new());
+        let failures = run_case(&cases[1], 1_000).unwrap();
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].starts_with("interpreter: output differs"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+// `brainfuck-rs graph`: the loop structure of a program, as Graphviz DOT or
+// as JSON. Each loop (and pbrain procedure) is a block, drawn inside-out:
+// solid edges go from a block to the loops directly inside it, dashed ones
+// from each loop to the next at the same level, and every loop has an edge
+// back to itself for going round again. With the counts a `run --profile`
+// saved, each loop also says how often it was reached and how many times its
+// body ran, and the ones that never ran are greyed out.
+
+use crate::ir::Instruction;
+use crate::json;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockKind {
+    Program, // The whole program, which every other block is inside
+    Loop,
+    Procedure,
+}
+
+impl BlockKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockKind::Program => "program",
+            BlockKind::Loop => "loop",
+            BlockKind::Procedure => "procedure",
+        }
+    }
+}
+
+/// A bracketed stretch of the program. `start` and `end` are the indices of
+/// its brackets, or the whole program for the outermost block.
+#[derive(Debug, PartialEq)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub start: usize,
+    pub end: usize,
+    pub parent: Option<usize>, // Index of the block it's directly inside
+    pub depth: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Graph {
+    pub blocks: Vec<Block>,   // In the order they start, the program first
+    counts: Option<Vec<u64>>, // Executions per instruction, from a profile
+}
+
+/// The blocks of a program whose brackets are known to balance.
+pub fn graph(program: &[Instruction]) -> Graph {
+    let mut blocks = vec![Block {
+        kind: BlockKind::Program,
+        start: 0,
+        end: program.len(),
+        parent: None,
+        depth: 0,
+    }];
+    let mut open = vec![0];
+    for (index, instruction) in program.iter().enumerate() {
+        let kind = match instruction {
+            Instruction::LoopStart => BlockKind::Loop,
+            Instruction::ProcedureStart => BlockKind::Procedure,
+            Instruction::LoopEnd | Instruction::ProcedureEnd => {
+                if open.len() > 1 {
+                    let block = open.pop().unwrap();
+                    blocks[block].end = index;
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        let parent = open[open.len() - 1];
+        open.push(blocks.len());
+        blocks.push(Block {
+            kind,
+            start: index,
+            end: program.len(),
+            parent: Some(parent),
+            depth: blocks[parent].depth + 1,
+        });
+    }
+    Graph {
+        blocks,
+        counts: None,
+    }
+}
+
+impl Graph {
+    /// Annotates the blocks with the counts of a profiled run, which must be
+    /// of the same program.
+    pub fn with_profile(mut self, counts: Vec<u64>) -> Result<Graph, String> {
+        let instructions = self.blocks[0].end;
+        if counts.len() != instructions {
+            return Err(format!(
+                "the profile is of a program with {} instructions, not {}",
+                counts.len(),
+                instructions
+            ));
+        }
+        self.counts = Some(counts);
+        Ok(self)
+    }
+
+    // How often a block was reached and how many times its body ran: the
+    // counts of its opening and closing brackets
+    fn runs(&self, block: &Block) -> Option<(u64, u64)> {
+        let counts = self.counts.as_ref()?;
+        match block.kind {
+            BlockKind::Program => Some((1, 1)),
+            _ => Some((counts[block.start], counts.get(block.end).copied()?)),
+        }
+    }
+
+    // The blocks directly inside `parent`, in order
+    fn children(&self, parent: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.blocks.len()).filter(move |block| self.blocks[*block].parent == Some(parent))
+    }
+
+    /// DOT for Graphviz. `source` is the sanitized source the program was
+    /// parsed from and `positions` the (line, column) of each command.
+    pub fn to_dot(&self, source: &str, positions: &[(usize, usize)]) -> String {
+        let mut dot =
+            String::from("digraph program {\n    node [shape=box, fontname=monospace];\n");
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut label = match block.kind {
+                BlockKind::Program => format!("program\n{} instructions", block.end),
+                _ => {
+                    let (line, column) = positions.get(block.start).copied().unwrap_or((0, 0));
+                    format!(
+                        "{} at {}:{}\n{}",
+                        block.kind.name(),
+                        line,
+                        column,
+                        excerpt(source, block)
+                    )
+                }
+            };
+            let runs = self.runs(block);
+            match (block.kind, runs, &self.counts) {
+                (BlockKind::Program, _, Some(counts)) => {
+                    label.push_str(&format!("\n{} steps", counts.iter().sum::<u64>()))
+                }
+                (_, Some((reached, iterations)), _) => label.push_str(&format!(
+                    "\nreached {}, ran {} {}",
+                    reached,
+                    iterations,
+                    if iterations == 1 { "time" } else { "times" }
+                )),
+                _ => {}
+            }
+            let style = match runs {
+                Some((0, _)) => ", color=gray, fontcolor=gray",
+                _ => "",
+            };
+            dot.push_str(&format!(
+                "    block{} [label={}{}];\n",
+                index,
+                dot_string(&label),
+                style
+            ));
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            let children: Vec<usize> = self.children(index).collect();
+            for child in &children {
+                dot.push_str(&format!("    block{} -> block{};\n", index, child));
+            }
+            for pair in children.windows(2) {
+                dot.push_str(&format!(
+                    "    block{} -> block{} [style=dashed];\n",
+                    pair[0], pair[1]
+                ));
+            }
+            if block.kind == BlockKind::Loop {
+                dot.push_str(&format!(
+                    "    block{} -> block{} [style=dotted];\n",
+                    index, index
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The blocks as a JSON array, with `positions` as for `to_dot`.
+    pub fn to_json(&self, positions: &[(usize, usize)]) -> String {
+        let blocks: Vec<String> = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let (line, column) = match block.kind {
+                    BlockKind::Program => (1, 1),
+                    _ => positions.get(block.start).copied().unwrap_or((0, 0)),
+                };
+                let (reached, iterations) = match self.runs(block) {
+                    Some((reached, iterations)) => (reached.to_string(), iterations.to_string()),
+                    None => ("null".to_string(), "null".to_string()),
+                };
+                json::object(&[
+                    ("kind", json::string(block.kind.name())),
+                    ("start", block.start.to_string()),
+                    ("end", block.end.to_string()),
+                    ("line", line.to_string()),
+                    ("column", column.to_string()),
+                    (
+                        "parent",
+                        block
+                            .parent
+                            .map_or("null".to_string(), |parent| parent.to_string()),
+                    ),
+                    ("depth", block.depth.to_string()),
+                    ("reached", reached),
+                    ("iterations", iterations),
+                ])
+            })
+            .collect();
+        json::array(&blocks)
+    }
+}
+
+// How much of a block's source goes in its label
+const EXCERPT_CHARS: usize = 24;
+
+fn excerpt(source: &str, block: &Block) -> String {
+    let text: String = source
+        .chars()
+        .skip(block.start)
+        .take(block.end + 1 - block.start)
+        .collect();
+    match text.chars().count() > EXCERPT_CHARS {
+        true => text.chars().take(EXCERPT_CHARS - 3).collect::<String>() + "...",
+        false => text,
+    }
+}
+
+// A quoted DOT string, with newlines as line breaks
+fn dot_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            other => quoted.push(other),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse;
+
+    #[test]
+    fn test_graph() {
+        let source = "+[->[-]<]>[.]";
+        let graph = graph(&parse(source));
+        let shape: Vec<(usize, usize, Option<usize>, usize)> = graph
+            .blocks
+            .iter()
+            .map(|block| (block.start, block.end, block.parent, block.depth))
+            .collect();
+        assert_eq!(
+            shape,
+            [
+                (0, 13, None, 0),
+                (1, 8, Some(0), 1),
+                (4, 6, Some(1), 2),
+                (10, 12, Some(0), 1)
+            ]
+        );
+
+        let positions: Vec<(usize, usize)> = (1..=source.len()).map(|at| (1, at)).collect();
+        let mut counts = vec![1; source.len()];
+        (counts[10], counts[11], counts[12]) = (3, 0, 0);
+        let graph = graph.with_profile(counts).unwrap();
+        let dot = graph.to_dot(source, &positions);
+        assert!(dot.contains("block1 [label=\"loop at 1:2\\n[->[-]<]\\nreached 1, ran 1 time\"];"));
+        assert!(dot.contains("block0 -> block1;\n    block0 -> block3;\n"));
+        assert!(dot.contains("block1 -> block3 [style=dashed];"));
+        assert!(dot.contains("reached 3, ran 0 times\"];"));
+        assert!(graph.with_profile(vec![1]).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+// `--heatmap`: counts reads and writes of every tape cell during a run and
+// renders them as an image, either a binary PPM or an SVG with a tooltip
+// per cell. Cells are laid out row by row, `COLUMNS` to a row.
+
+use crate::interpreter::Access;
+use crate::{ExecutionObserver, MEMORY_SIZE};
+
+const COLUMNS: usize = 16;
+const CELL_PIXELS: usize = 16; // Width and height of a cell in the image
+
+pub struct Heatmap {
+    pub reads: Vec<u64>,
+    pub writes: Vec<u64>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Heatmap {
+        Heatmap::new()
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Heatmap {
+        Heatmap {
+            reads: vec![0; MEMORY_SIZE],
+            writes: vec![0; MEMORY_SIZE],
+        }
+    }
+
+    // Black through red and yellow to white, on a log scale so a few hot
+    // cells don't wash out everything else
+    fn colors(&self) -> Vec<[u8; 3]> {
+        let totals: Vec<u64> = (0..MEMORY_SIZE)
+            .map(|cell| self.reads[cell] + self.writes[cell])
+            .collect();
+        let hottest = (*totals.iter().max().unwrap_or(&0) as f64).ln_1p();
+        totals
+            .iter()
+            .map(|total| {
+                let heat = if hottest > 0.0 {
+                    (*total as f64).ln_1p() / hottest
+                } else {
+                    0.0
+                };
+                let channel = |start: f64| ((heat * 3.0 - start).clamp(0.0, 1.0) * 255.0) as u8;
+                [channel(0.0), channel(1.0), channel(2.0)]
+            })
+            .collect()
+    }
+
+    fn rows() -> usize {
+        MEMORY_SIZE.div_ceil(COLUMNS)
+    }
+
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let colors = self.colors();
+        let (width, height) = (COLUMNS * CELL_PIXELS, Heatmap::rows() * CELL_PIXELS);
+        let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        for y in 0..height {
+            for x in 0..width {
+                let cell = (y / CELL_PIXELS) * COLUMNS + x / CELL_PIXELS;
+                image.extend(colors.get(cell).unwrap_or(&[0, 0, 0]));
+            }
+        }
+        image
+    }
+
+    pub fn to_svg(&self) -> String {
+        let colors = self.colors();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+            COLUMNS * CELL_PIXELS,
+            Heatmap::rows() * CELL_PIXELS
+        );
+        for (cell, [red, green, blue]) in colors.iter().enumerate() {
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{size}\" height=\"{size}\" fill=\"#{:02x}{:02x}{:02x}\"><title>cell {}: {} reads, {} writes</title></rect>\n",
+                (cell % COLUMNS) * CELL_PIXELS,
+                (cell / COLUMNS) * CELL_PIXELS,
+                red,
+                green,
+                blue,
+                cell,
+                self.reads[cell],
+                self.writes[cell],
+                size = CELL_PIXELS,
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+impl ExecutionObserver for Heatmap {
+    fn on_access(&mut self, cell: usize, access: Access) {
+        match access {
+            Access::Read => self.reads[cell] += 1,
+            Access::Write => self.writes[cell] += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run, Extensions, ProgramInput};
+
+    #[test]
+    fn test_heatmap() {
+        let source = "++[>+.<-]";
+        let mut heatmap = Heatmap::new();
+        let result = run(
+            source,
+            Extensions::default(),
+            &mut ProgramInput::new(&[]),
+            &mut Vec::new(),
+            &mut heatmap,
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!((heatmap.reads[0], heatmap.writes[0]), (3, 4));
+        assert_eq!((heatmap.reads[1], heatmap.writes[1]), (2, 2));
+
+        let ppm = heatmap.to_ppm();
+        assert!(ppm.starts_with(b"P6\n256 256\n255\n"));
+        assert_eq!(ppm.len(), 15 + 256 * 256 * 3);
+        assert_eq!(&ppm[15..18], [255, 255, 255]); // Cell 0 is the hottest
+        assert!(heatmap
+            .to_svg()
+            .contains("<title>cell 1: 2 reads, 2 writes</title>"));
+    }
+}
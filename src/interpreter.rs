@@ -0,0 +1,650 @@
+// Cooperative execution: `Interpreter::step` runs one instruction at a time
+// and hands I/O back to the caller, so GUIs and games can drive a program at
+// their own pace. `run` is just a loop over this.
+
+use crate::ir::{self, Instruction, Program};
+use crate::{jump_table, ByteSink, Error, Extensions, JumpTable, Memory, MEMORY_SIZE};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+const DEBUG_WINDOW: usize = 8; // Cells shown either side of the pointer by `#`
+pub const MAX_CALL_DEPTH: usize = 10_000; // Nested pbrain calls before giving up
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepResult {
+    Continue,
+    NeedsInput, // Call `provide_input` and step again to execute the `,`
+    Output(u8),
+    Halted,
+}
+
+/// Why `run_with_fuel` handed control back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Yield {
+    OutOfFuel,  // Ran every instruction it was allowed; call again to carry on
+    NeedsInput, // Call `provide_input`, then carry on
+    Halted,
+}
+
+/// How an instruction uses the cell under the pointer (or, for an `AddAt`,
+/// the one it names).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    /// How `instruction` uses the current cell, if at all. `#` only prints
+    /// cells, so it doesn't count.
+    pub fn of(instruction: Instruction) -> Option<Access> {
+        match instruction {
+            Instruction::Output
+            | Instruction::LoopStart
+            | Instruction::LoopEnd
+            | Instruction::ProcedureStart
+            | Instruction::Call => Some(Access::Read),
+            Instruction::Add(_)
+            | Instruction::AddAt(..)
+            | Instruction::Input
+            | Instruction::Fork => Some(Access::Write),
+            Instruction::Move(_)
+            | Instruction::Debug
+            | Instruction::ProcedureEnd
+            | Instruction::SelectTape(_) => None,
+        }
+    }
+}
+
+/// What `Interpreter::restore` needs to undo one `step`. A step can only
+/// change the current thread's pointers, tape and cell (or the cell an
+/// `AddAt` names), the procedure the cell names, the top of its call stack,
+/// which thread runs next and (by forking) how many there are, so that's all
+/// this keeps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Undo {
+    thread: usize,
+    threads: usize,
+    source_pointer: usize,
+    tape: u8,
+    memory_pointer: usize,
+    cell: u8,
+    added: Option<(usize, u8)>, // The cell an `AddAt` changes, and what it held
+    procedure: Option<usize>,
+    call_depth: usize,
+    call_top: Option<usize>,
+    input: Option<Option<u8>>,
+}
+
+// Everything a brainfork `Y` copies into the child
+#[derive(Debug, Clone)]
+struct Thread {
+    memory: Memory, // The current tape
+    memory_pointer: usize,
+    tape: u8,                    // Which of the dualtape extension's tapes is current
+    other_tape: (Memory, usize), // The other one, with its pointer
+    source_pointer: usize,
+    call_stack: Vec<usize>, // Positions of the `:`s to return to
+}
+
+impl Thread {
+    // Index of the cell `offset` away from the pointer
+    fn cell_at(&self, offset: isize) -> usize {
+        (self.memory_pointer as isize + offset).rem_euclid(MEMORY_SIZE as isize) as usize
+    }
+
+    fn switch_tape(&mut self) {
+        core::mem::swap(&mut self.memory, &mut self.other_tape.0);
+        core::mem::swap(&mut self.memory_pointer, &mut self.other_tape.1);
+        self.tape = 1 - self.tape;
+    }
+}
+
+#[derive(Clone)]
+pub struct Interpreter {
+    program: Program, // One instruction per command unless built with `from_program`
+    jumps: JumpTable,
+    extensions: Extensions,
+    // Only brainfork programs have more than one thread. They take turns one
+    // instruction at a time, in the order they were forked, and a finished
+    // thread keeps its place so undoing a step never has to bring one back
+    threads: Vec<Thread>,
+    current: usize,                   // The thread the next step runs
+    input: Option<Option<u8>>,        // Byte (or end of input) waiting for the next `,`
+    procedures: [Option<usize>; 256], // Where each pbrain procedure's `(` is, once defined
+}
+
+impl Interpreter {
+    /// Prepares sanitized source for execution, checking its brackets.
+    pub fn new(source_code: &str, extensions: Extensions) -> Result<Interpreter, Error> {
+        Interpreter::from_program(ir::parse(source_code), extensions)
+    }
+
+    /// Prepares a program that may have been transformed, e.g. folded by
+    /// `ir::StreamParser`. Source pointers then index the program rather
+    /// than the source.
+    pub fn from_program(program: Program, extensions: Extensions) -> Result<Interpreter, Error> {
+        Ok(Interpreter {
+            jumps: jump_table(&program)?,
+            program,
+            extensions,
+            threads: vec![Thread {
+                memory: [0; MEMORY_SIZE],
+                memory_pointer: 0,
+                tape: 0,
+                other_tape: ([0; MEMORY_SIZE], 0),
+                source_pointer: 0,
+                call_stack: Vec::new(),
+            }],
+            current: 0,
+            input: None,
+            procedures: [None; 256],
+        })
+    }
+
+    pub fn extensions(&self) -> Extensions {
+        self.extensions
+    }
+
+    /// Runs at most `fuel` instructions, writing the bytes printed to
+    /// `output` as `step` reports them, and says why it stopped. Hosts with
+    /// a frame to draw can give the program a slice of it at a time without
+    /// a call per instruction. Waiting for input burns no fuel.
+    pub fn run_with_fuel(&mut self, fuel: u64, output: &mut dyn ByteSink) -> Result<Yield, Error> {
+        for _ in 0..fuel {
+            match self.step()? {
+                StepResult::Continue => {}
+                StepResult::Output(byte) => output.write_bytes(&[byte])?,
+                StepResult::NeedsInput => return Ok(Yield::NeedsInput),
+                StepResult::Halted => return Ok(Yield::Halted),
+            }
+        }
+        match self.current_instruction() {
+            Some(_) => Ok(Yield::OutOfFuel),
+            None => Ok(Yield::Halted),
+        }
+    }
+
+    /// Adds `program`, whose brackets must balance on their own, to the end
+    /// of this one, keeping the tape, the pointer and defined procedures.
+    /// Only the new brackets are matched, so a long session that grows one
+    /// piece at a time never re-parses what it already has. Threads that had
+    /// finished carry on into the new instructions. On mismatched brackets
+    /// nothing is added, and the error's index is into `program`.
+    pub fn append(&mut self, program: &[Instruction]) -> Result<(), Error> {
+        let start = self.program.len();
+        let jumps = jump_table(program)?;
+        self.program.extend_from_slice(program);
+        self.jumps
+            .extend(jumps.into_iter().map(|jump| start + jump));
+        Ok(())
+    }
+
+    /// Stops every thread where the program ends, e.g. to give up on the
+    /// rest of what was appended after it fails.
+    pub fn halt(&mut self) {
+        for thread in &mut self.threads {
+            thread.source_pointer = self.program.len();
+        }
+        self.input = None;
+    }
+
+    /// The first thread's first tape, which a run starts on and a saved
+    /// tape is kept from.
+    pub fn first_tape(&self) -> &Memory {
+        self.tape_of(0, 0)
+    }
+
+    /// Tape `tape` (0 or 1) of thread `thread`, whether or not it's current.
+    pub fn tape_of(&self, thread: usize, tape: u8) -> &Memory {
+        let thread = &self.threads[thread];
+        match thread.tape == tape {
+            true => &thread.memory,
+            false => &thread.other_tape.0,
+        }
+    }
+
+    /// Fills the first tape with `cells`, e.g. what an earlier run left.
+    pub fn set_first_tape(&mut self, cells: &Memory) {
+        let thread = &mut self.threads[0];
+        match thread.tape {
+            0 => thread.memory = *cells,
+            _ => thread.other_tape.0 = *cells,
+        }
+    }
+
+    fn thread(&self) -> &Thread {
+        &self.threads[self.current]
+    }
+
+    /// The current thread's current tape. Without the dualtape extension
+    /// there's only one.
+    pub fn memory(&self) -> &Memory {
+        &self.thread().memory
+    }
+
+    pub fn memory_pointer(&self) -> usize {
+        self.thread().memory_pointer
+    }
+
+    /// Which tape `memory` is: 0 (the first) or 1.
+    pub fn tape(&self) -> u8 {
+        self.thread().tape
+    }
+
+    /// Which thread runs next, counting in the order they were forked.
+    pub fn thread_id(&self) -> usize {
+        self.current
+    }
+
+    /// How many threads have been started, including finished ones.
+    pub fn thread_count(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// How many instructions the program has.
+    pub fn program_len(&self) -> usize {
+        self.program.len()
+    }
+
+    /// Position of the next instruction to execute.
+    pub fn source_pointer(&self) -> usize {
+        self.thread().source_pointer
+    }
+
+    /// The next instruction to execute, or `None` once the program is over.
+    pub fn current_instruction(&self) -> Option<Instruction> {
+        self.program.get(self.source_pointer()).copied()
+    }
+
+    /// The cell the next instruction reads or writes, and how.
+    pub fn next_access(&self) -> Option<(usize, Access)> {
+        let instruction = self.current_instruction()?;
+        let access = Access::of(instruction)?;
+        match instruction {
+            Instruction::AddAt(offset, _) => Some((self.thread().cell_at(offset), access)),
+            _ => Some((self.memory_pointer(), access)),
+        }
+    }
+
+    /// Supplies the byte read by the pending `,`; `None` means end of input,
+    /// which does what the `eof` setting of the extensions says.
+    pub fn provide_input(&mut self, byte: Option<u8>) {
+        self.input = Some(byte);
+    }
+
+    /// Captures what the next `step` may change.
+    pub fn checkpoint(&self) -> Undo {
+        let thread = self.thread();
+        let cell = thread.memory[thread.memory_pointer];
+        Undo {
+            thread: self.current,
+            threads: self.threads.len(),
+            source_pointer: thread.source_pointer,
+            tape: thread.tape,
+            memory_pointer: thread.memory_pointer,
+            cell,
+            added: match self.current_instruction() {
+                Some(Instruction::AddAt(offset, _)) => {
+                    let index = thread.cell_at(offset);
+                    Some((index, thread.memory[index]))
+                }
+                _ => None,
+            },
+            procedure: self.procedures[cell as usize],
+            call_depth: thread.call_stack.len(),
+            call_top: thread.call_stack.last().copied(),
+            input: self.input,
+        }
+    }
+
+    /// Puts the interpreter back as it was when `undo` was captured. Undoing
+    /// several steps must go in reverse order.
+    pub fn restore(&mut self, undo: Undo) {
+        self.threads.truncate(undo.threads);
+        self.current = undo.thread;
+        let thread = &mut self.threads[self.current];
+        thread.source_pointer = undo.source_pointer;
+        if thread.tape != undo.tape {
+            thread.switch_tape();
+        }
+        thread.memory_pointer = undo.memory_pointer;
+        thread.memory[undo.memory_pointer] = undo.cell;
+        if let Some((index, cell)) = undo.added {
+            thread.memory[index] = cell;
+        }
+        self.procedures[undo.cell as usize] = undo.procedure;
+        thread.call_stack.truncate(undo.call_depth);
+        if thread.call_stack.len() < undo.call_depth {
+            thread.call_stack.extend(undo.call_top);
+        }
+        self.input = undo.input;
+    }
+
+    pub fn step(&mut self) -> Result<StepResult, Error> {
+        let Some(instruction) = self.current_instruction() else {
+            return Ok(StepResult::Halted);
+        };
+        let mut result = StepResult::Continue;
+        let thread = &mut self.threads[self.current];
+        let cell = thread.memory[thread.memory_pointer];
+        match instruction {
+            Instruction::Add(amount) => {
+                thread.memory[thread.memory_pointer] = cell.wrapping_add(amount)
+            }
+            Instruction::AddAt(offset, amount) => {
+                let index = thread.cell_at(offset);
+                thread.memory[index] = thread.memory[index].wrapping_add(amount)
+            }
+            Instruction::Move(amount) => thread.memory_pointer = thread.cell_at(amount),
+            Instruction::Output => result = StepResult::Output(cell),
+            Instruction::Input => match self.input.take() {
+                Some(Some(byte)) => thread.memory[thread.memory_pointer] = byte,
+                Some(None) => {
+                    if let Some(byte) = self.extensions.eof.value() {
+                        thread.memory[thread.memory_pointer] = byte
+                    }
+                }
+                None => return Ok(StepResult::NeedsInput),
+            },
+            Instruction::LoopStart if cell == 0 => {
+                thread.source_pointer = self.jumps[thread.source_pointer]
+            }
+            Instruction::LoopEnd if cell != 0 => {
+                thread.source_pointer = self.jumps[thread.source_pointer]
+            }
+            // Without `std` there's no stderr to dump to
+            #[cfg(feature = "std")]
+            Instruction::Debug if self.extensions.debug => {
+                eprintln!(
+                    "{}",
+                    format_debug_state(&thread.memory, thread.memory_pointer)
+                )
+            }
+            // Defining a procedure skips over its body
+            Instruction::ProcedureStart if self.extensions.pbrain => {
+                self.procedures[cell as usize] = Some(thread.source_pointer);
+                thread.source_pointer = self.jumps[thread.source_pointer];
+            }
+            Instruction::ProcedureEnd if self.extensions.pbrain => {
+                if let Some(call) = thread.call_stack.pop() {
+                    thread.source_pointer = call;
+                }
+            }
+            Instruction::Call if self.extensions.pbrain => {
+                let start =
+                    self.procedures[cell as usize].ok_or(Error::UndefinedProcedure(cell))?;
+                if thread.call_stack.len() >= MAX_CALL_DEPTH {
+                    return Err(Error::CallStackOverflow);
+                }
+                thread.call_stack.push(thread.source_pointer);
+                thread.source_pointer = start;
+            }
+            Instruction::SelectTape(tape) if self.extensions.dual_tape && tape != thread.tape => {
+                thread.switch_tape()
+            }
+            // The parent's cell becomes 0; the child starts on the next cell,
+            // set to 1, and carries on after the `Y` too
+            Instruction::Fork if self.extensions.fork => {
+                thread.memory[thread.memory_pointer] = 0;
+                let mut child = thread.clone();
+                child.memory_pointer = (child.memory_pointer + 1) % MEMORY_SIZE;
+                child.memory[child.memory_pointer] = 1;
+                child.source_pointer += 1;
+                self.threads.push(child);
+            }
+            Instruction::LoopStart
+            | Instruction::LoopEnd
+            | Instruction::Debug
+            | Instruction::ProcedureStart
+            | Instruction::ProcedureEnd
+            | Instruction::Call
+            | Instruction::SelectTape(_)
+            | Instruction::Fork => {}
+        }
+        self.threads[self.current].source_pointer += 1;
+        self.schedule();
+        Ok(result)
+    }
+
+    // Moves on to the next thread that hasn't finished, if there's another
+    fn schedule(&mut self) {
+        let count = self.threads.len();
+        if let Some(next) = (1..=count)
+            .map(|offset| (self.current + offset) % count)
+            .find(|thread| self.threads[*thread].source_pointer < self.program.len())
+        {
+            self.current = next;
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn format_debug_state(memory: &Memory, memory_pointer: usize) -> String {
+    let start = memory_pointer.saturating_sub(DEBUG_WINDOW);
+    let end = std::cmp::min(MEMORY_SIZE - 1, memory_pointer + DEBUG_WINDOW);
+    let cells: Vec<String> = (start..=end)
+        .map(|index| {
+            if index == memory_pointer {
+                format!("[{}]", memory[index])
+            } else {
+                memory[index].to_string()
+            }
+        })
+        .collect();
+    format!(
+        "# pointer: {}, cells {}..={}: {}",
+        memory_pointer,
+        start,
+        end,
+        cells.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EofMode;
+
+    #[test]
+    fn test_step() {
+        let mut interpreter = Interpreter::new(",+.<", Extensions::default()).unwrap();
+        assert_eq!(interpreter.step(), Ok(StepResult::NeedsInput));
+        interpreter.provide_input(Some(b'a'));
+        assert_eq!(interpreter.step(), Ok(StepResult::Continue));
+        assert_eq!(interpreter.step(), Ok(StepResult::Continue));
+        assert_eq!(interpreter.step(), Ok(StepResult::Output(b'b')));
+        assert_eq!(interpreter.step(), Ok(StepResult::Continue));
+        assert_eq!(interpreter.memory_pointer(), MEMORY_SIZE - 1);
+        assert_eq!(interpreter.step(), Ok(StepResult::Halted));
+
+        // End of input
+        for (eof, cell) in [
+            (EofMode::Unchanged, 3),
+            (EofMode::Zero, 0),
+            (EofMode::Max, 255),
+        ] {
+            let extensions = Extensions {
+                eof,
+                ..Extensions::default()
+            };
+            let mut interpreter = Interpreter::new("+++,.", extensions).unwrap();
+            let mut output = Vec::new();
+            assert_eq!(
+                interpreter.run_with_fuel(10, &mut output),
+                Ok(Yield::NeedsInput)
+            );
+            interpreter.provide_input(None);
+            assert_eq!(
+                interpreter.run_with_fuel(10, &mut output),
+                Ok(Yield::Halted)
+            );
+            assert_eq!(output, [cell]);
+        }
+    }
+
+    #[test]
+    fn test_run_with_fuel() {
+        let mut interpreter = Interpreter::new("++.,.+", Extensions::default()).unwrap();
+        let mut output = Vec::new();
+        assert_eq!(
+            interpreter.run_with_fuel(2, &mut output),
+            Ok(Yield::OutOfFuel)
+        );
+        assert!(output.is_empty());
+        assert_eq!(
+            interpreter.run_with_fuel(10, &mut output),
+            Ok(Yield::NeedsInput)
+        );
+        interpreter.provide_input(Some(7));
+        // Exactly enough fuel still ends halted
+        assert_eq!(interpreter.run_with_fuel(3, &mut output), Ok(Yield::Halted));
+        assert_eq!(output, [2, 7]);
+    }
+
+    #[test]
+    fn test_append() {
+        let mut interpreter = Interpreter::new("++>", Extensions::default()).unwrap();
+        let run = |interpreter: &mut Interpreter| {
+            let mut outputs = Vec::new();
+            loop {
+                match interpreter.step().unwrap() {
+                    StepResult::Output(byte) => outputs.push(byte),
+                    StepResult::Halted => return outputs,
+                    _ => {}
+                }
+            }
+        };
+        assert!(run(&mut interpreter).is_empty());
+        // The new loop's brackets are matched where they end up
+        interpreter.append(&ir::parse("+++[<+>-]<.")).unwrap();
+        assert_eq!(run(&mut interpreter), [5]);
+        assert_eq!(
+            interpreter.append(&ir::parse("+]")),
+            Err(Error::MismatchedBrackets(1))
+        );
+        assert_eq!(interpreter.program_len(), 14);
+    }
+
+    #[test]
+    fn test_first_tape() {
+        let dual_tape = Extensions {
+            dual_tape: true,
+            ..Extensions::default()
+        };
+        let mut interpreter = Interpreter::new("+}++", dual_tape).unwrap();
+        let mut cells = [0; MEMORY_SIZE];
+        cells[0] = 40;
+        interpreter.set_first_tape(&cells);
+        while interpreter.step().unwrap() != StepResult::Halted {}
+        // Still the first tape while the second is current
+        assert_eq!(interpreter.tape(), 1);
+        assert_eq!(interpreter.first_tape()[0], 41);
+        assert_eq!(interpreter.memory()[0], 2);
+    }
+
+    #[test]
+    fn test_pbrain() {
+        let pbrain = Extensions {
+            pbrain: true,
+            ..Extensions::default()
+        };
+        // Procedure 0 adds three to the next cell; call it twice
+        let mut interpreter = Interpreter::new("(>+++<)::>.", pbrain).unwrap();
+        let mut result = interpreter.step();
+        while result == Ok(StepResult::Continue) {
+            result = interpreter.step();
+        }
+        assert_eq!(result, Ok(StepResult::Output(6)));
+
+        let mut interpreter = Interpreter::new("+:", pbrain).unwrap();
+        interpreter.step().unwrap();
+        assert_eq!(interpreter.step(), Err(Error::UndefinedProcedure(1)));
+        let mut interpreter = Interpreter::new("(:):", pbrain).unwrap();
+        let mut result = interpreter.step();
+        while result == Ok(StepResult::Continue) {
+            result = interpreter.step();
+        }
+        assert_eq!(result, Err(Error::CallStackOverflow));
+    }
+
+    #[test]
+    fn test_dual_tape() {
+        let dual_tape = Extensions {
+            dual_tape: true,
+            ..Extensions::default()
+        };
+        // Each tape keeps its own cells and pointer
+        let mut interpreter = Interpreter::new("+++>}++{<.}.", dual_tape).unwrap();
+        let mut outputs = Vec::new();
+        let mut undos = Vec::new();
+        loop {
+            undos.push(interpreter.checkpoint());
+            match interpreter.step() {
+                Ok(StepResult::Output(byte)) => outputs.push(byte),
+                Ok(StepResult::Halted) => break,
+                result => assert_eq!(result, Ok(StepResult::Continue)),
+            }
+        }
+        assert_eq!(outputs, [3, 2]);
+        assert_eq!((interpreter.tape(), interpreter.memory_pointer()), (1, 0));
+        assert_eq!(interpreter.memory()[0], 2);
+
+        // Stepping back across a switch brings the first tape back
+        for undo in undos.into_iter().rev().skip(1).take(4) {
+            interpreter.restore(undo);
+        }
+        assert_eq!((interpreter.tape(), interpreter.memory_pointer()), (0, 1));
+        assert_eq!(interpreter.memory()[0], 3);
+
+        // Without the extension the commands do nothing
+        let mut interpreter = Interpreter::new("+}.", Extensions::default()).unwrap();
+        interpreter.step().unwrap();
+        interpreter.step().unwrap();
+        assert_eq!(interpreter.step(), Ok(StepResult::Output(1)));
+    }
+
+    #[test]
+    fn test_fork() {
+        let fork = Extensions {
+            fork: true,
+            ..Extensions::default()
+        };
+        // Both add one to their cell: the child's started at 1 and the
+        // parent's at 0. The child's turn comes first, right after the fork
+        let mut interpreter = Interpreter::new("Y+.", fork).unwrap();
+        let mut outputs = Vec::new();
+        let mut undos = Vec::new();
+        loop {
+            undos.push(interpreter.checkpoint());
+            match interpreter.step() {
+                Ok(StepResult::Output(byte)) => outputs.push(byte),
+                Ok(StepResult::Halted) => break,
+                result => assert_eq!(result, Ok(StepResult::Continue)),
+            }
+        }
+        assert_eq!(outputs, [2, 1]);
+        assert_eq!(interpreter.thread_count(), 2);
+
+        // Undoing the fork drops the child again
+        for undo in undos.into_iter().rev() {
+            interpreter.restore(undo);
+        }
+        assert_eq!(interpreter.thread_count(), 1);
+        assert_eq!(
+            (interpreter.thread_id(), interpreter.source_pointer()),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn test_format_debug_state() {
+        let mut memory: Memory = [0; MEMORY_SIZE];
+        memory[1] = 72;
+        assert_eq!(
+            format_debug_state(&memory, 1),
+            "# pointer: 1, cells 0..=9: 0 [72] 0 0 0 0 0 0 0 0"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+// Ctrl-C handling for `run`: SIGINT asks the run loop to stop (through
+// `ExecutionObserver::should_stop`) so we can flush the output, put the
+// terminal back and report where the program was. The handler is installed
+// without SA_RESTART, so a `,` blocked on the terminal is woken up and gives
+// up too (see `ProgramInput::stop_on`), and it stays installed, since dying
+// to a second Ctrl-C would skip the exit handlers that undo `--raw-tty`.
+// There's no signal-handling crate, so this calls libc's `sigaction`
+// directly; as with the terminal handling it's only wired up where the
+// struct layout is known, and elsewhere Ctrl-C keeps its default behavior.
+
+use brainfuck_rs::{json, ExecutionObserver, Memory};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set once Ctrl-C has been pressed, for `ProgramInput::stop_on`.
+pub fn flag() -> &'static AtomicBool {
+    &INTERRUPTED
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod platform {
+    use std::os::raw::c_int;
+
+    #[cfg(target_os = "linux")]
+    mod layout {
+        use std::os::raw::{c_int, c_ulong};
+
+        #[repr(C)]
+        pub struct SigAction {
+            pub handler: usize,
+            pub mask: [c_ulong; 128 / std::mem::size_of::<c_ulong>()],
+            pub flags: c_int,
+            pub restorer: usize,
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    mod layout {
+        use std::os::raw::c_int;
+
+        #[repr(C)]
+        pub struct SigAction {
+            pub handler: usize,
+            pub mask: u32,
+            pub flags: c_int,
+        }
+    }
+
+    use layout::SigAction;
+
+    const SIGINT: c_int = 2;
+
+    extern "C" {
+        fn sigaction(signum: c_int, action: *const SigAction, old: *mut SigAction) -> c_int;
+    }
+
+    extern "C" fn handle_sigint(_: c_int) {
+        super::INTERRUPTED.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn install() {
+        let handler: extern "C" fn(c_int) = handle_sigint;
+        // No flags: in particular not SA_RESTART or SA_RESETHAND
+        let mut action: SigAction = unsafe { std::mem::zeroed() };
+        action.handler = handler as usize;
+        unsafe { sigaction(SIGINT, &action, std::ptr::null_mut()) };
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    pub fn install() {}
+}
+
+/// Follows the run so there's something to report if it's interrupted.
+#[derive(Default)]
+pub struct Interrupt {
+    pub instructions: u64,
+    pub source_pointer: usize,
+    pub memory_pointer: usize,
+    pub memory: Option<Memory>, // As of the last instruction; only kept for snapshots
+    keep_memory: bool,
+}
+
+impl Interrupt {
+    /// Starts catching Ctrl-C. `keep_memory` copies the tape before every
+    /// instruction so it can go into a snapshot.
+    pub fn install(keep_memory: bool) -> Interrupt {
+        platform::install();
+        Interrupt {
+            keep_memory,
+            ..Interrupt::default()
+        }
+    }
+
+    /// The state at the interruption as JSON, enough to pick the run up again.
+    /// It's taken just before the instruction at `source_pointer`, so that
+    /// instruction isn't counted.
+    pub fn snapshot(&self, source: &str) -> String {
+        let memory = self.memory.map_or(Vec::new(), |memory| {
+            memory.iter().map(|cell| cell.to_string()).collect()
+        });
+        json::object(&[
+            ("source", json::string(source)),
+            ("source_pointer", self.source_pointer.to_string()),
+            ("memory_pointer", self.memory_pointer.to_string()),
+            (
+                "instructions",
+                self.instructions.saturating_sub(1).to_string(),
+            ),
+            ("memory", json::array(&memory)),
+        ])
+    }
+}
+
+impl ExecutionObserver for Interrupt {
+    fn on_instruction(&mut self, source_pointer: usize, memory: &Memory, memory_pointer: usize) {
+        self.instructions += 1;
+        self.source_pointer = source_pointer;
+        self.memory_pointer = memory_pointer;
+        if self.keep_memory {
+            self.memory = Some(*memory);
+        }
+    }
+
+    fn should_stop(&self) -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let mut interrupt = Interrupt {
+            keep_memory: true,
+            ..Interrupt::default()
+        };
+        let mut memory = [0; brainfuck_rs::MEMORY_SIZE];
+        memory[0] = 7;
+        interrupt.on_instruction(3, &memory, 1);
+        let snapshot = json::parse(&interrupt.snapshot("+[]")).unwrap();
+        assert_eq!(
+            snapshot.get("source_pointer").and_then(json::Value::as_u64),
+            Some(3)
+        );
+        assert_eq!(
+            snapshot.get("instructions").and_then(json::Value::as_u64),
+            Some(0)
+        );
+        let Some(json::Value::Array(cells)) = snapshot.get("memory") else {
+            panic!("expected the memory to be saved");
+        };
+        assert_eq!(cells[0].as_u64(), Some(7));
+    }
+}
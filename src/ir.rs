@@ -0,0 +1,323 @@
+// Intermediate representation shared by the optimizer and the tooling built
+// on top of it. Loops are kept as bracket markers so passes can rewrite the
+// program freely without having to keep jump targets up to date.
+
+#[cfg(feature = "std")]
+use crate::optimize::fold_runs;
+use crate::optimize::push_folded;
+use crate::{is_command, Error, Extensions};
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Add(u8),          // Wrapping add to the current cell
+    AddAt(isize, u8), // Wrapping add to the cell this far from the pointer, which stays put
+    Move(isize),      // Relative pointer move
+    Output,
+    Input,
+    LoopStart,
+    LoopEnd,
+    Debug,          // `#` from the debug extension
+    ProcedureStart, // `(` from the pbrain extension: defines the procedure numbered by the cell
+    ProcedureEnd,   // `)`: returns from the procedure
+    Call,           // `:`: calls the procedure numbered by the cell
+    SelectTape(u8), // `{` or `}` from the dualtape extension: makes tape 0 or 1 current
+    Fork,           // `Y` from brainfork: starts a thread with a copy of the tapes
+}
+
+pub type Program = Vec<Instruction>;
+
+/// Sources smaller than this aren't worth splitting across threads.
+pub const PARALLEL_MIN_BYTES: usize = 1 << 20;
+
+/// Converts sanitized source into instructions, one per command. Brackets
+/// aren't checked here; use `generate_jump_table` for that.
+pub fn parse(source: &str) -> Program {
+    source
+        .chars()
+        .filter_map(|character| match character {
+            '+' => Some(Instruction::Add(1)),
+            '-' => Some(Instruction::Add(u8::MAX)),
+            '>' => Some(Instruction::Move(1)),
+            '<' => Some(Instruction::Move(-1)),
+            '.' => Some(Instruction::Output),
+            ',' => Some(Instruction::Input),
+            '[' => Some(Instruction::LoopStart),
+            ']' => Some(Instruction::LoopEnd),
+            '#' => Some(Instruction::Debug),
+            '(' => Some(Instruction::ProcedureStart),
+            ')' => Some(Instruction::ProcedureEnd),
+            ':' => Some(Instruction::Call),
+            '{' => Some(Instruction::SelectTape(0)),
+            '}' => Some(Instruction::SelectTape(1)),
+            'Y' => Some(Instruction::Fork),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses sanitized source and folds it like `optimize::fold_runs`, splitting
+/// it into `jobs` chunks that are parsed on their own threads when it's at
+/// least `PARALLEL_MIN_BYTES` long. The chunks are stitched back together in
+/// order, folding across each seam; brackets aren't checked here either.
+#[cfg(feature = "std")]
+pub fn parse_parallel(source: &str, jobs: usize) -> Program {
+    if jobs <= 1 || source.len() < PARALLEL_MIN_BYTES {
+        return fold_runs(&parse(source));
+    }
+    let mut chunks = Vec::new();
+    let mut rest = source;
+    while !rest.is_empty() {
+        let mut end = std::cmp::min(source.len().div_ceil(jobs), rest.len());
+        while !rest.is_char_boundary(end) {
+            end += 1;
+        }
+        let (chunk, after) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = after;
+    }
+    let folded: Vec<Program> = std::thread::scope(|scope| {
+        let threads: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || fold_runs(&parse(chunk))))
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().expect("parsing doesn't panic"))
+            .collect()
+    });
+    let mut program = Program::new();
+    for chunk in folded {
+        let mut chunk = chunk.into_iter();
+        // Only the start of a chunk can fold into what came before it
+        for instruction in chunk.by_ref() {
+            let length = program.len();
+            push_folded(&mut program, instruction);
+            if program.len() > length {
+                break;
+            }
+        }
+        program.extend(chunk);
+    }
+    program
+}
+
+/// Parses source incrementally, for programs too big to hold in memory as a
+/// string. Chunks can be split anywhere, including mid-loop: the parser only
+/// keeps the folded IR built so far (see `optimize::fold_runs`) and the
+/// positions of the loops (and procedures) still open, so an unmatched `]`
+/// is reported as soon as it's fed and an unclosed `[` when the input is
+/// finished. Error positions count commands, like indices into sanitized
+/// source.
+pub struct StreamParser {
+    program: Program,
+    extensions: Extensions,
+    open_loops: Vec<(usize, Instruction)>, // Command positions of the unclosed `[`s and `(`s
+    commands: usize,                       // Commands seen so far
+}
+
+impl StreamParser {
+    pub fn new(extensions: Extensions) -> StreamParser {
+        StreamParser {
+            program: Program::new(),
+            extensions,
+            open_loops: Vec::new(),
+            commands: 0,
+        }
+    }
+
+    /// Parses the next chunk of raw source. Commands are all ASCII, so byte
+    /// chunks may split UTF-8 comments without harm.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        for byte in chunk {
+            if !is_command(*byte as char, self.extensions) {
+                continue;
+            }
+            let instruction = match byte {
+                b'+' => Instruction::Add(1),
+                b'-' => Instruction::Add(u8::MAX),
+                b'>' => Instruction::Move(1),
+                b'<' => Instruction::Move(-1),
+                b'.' => Instruction::Output,
+                b',' => Instruction::Input,
+                b'[' | b'(' => {
+                    let start = match byte {
+                        b'[' => Instruction::LoopStart,
+                        _ => Instruction::ProcedureStart,
+                    };
+                    self.open_loops.push((self.commands, start));
+                    start
+                }
+                b']' | b')' => {
+                    let (start, end) = match byte {
+                        b']' => (Instruction::LoopStart, Instruction::LoopEnd),
+                        _ => (Instruction::ProcedureStart, Instruction::ProcedureEnd),
+                    };
+                    match self.open_loops.pop() {
+                        Some((_, open)) if open == start => end,
+                        _ => return Err(Error::MismatchedBrackets(self.commands)),
+                    }
+                }
+                b':' => Instruction::Call,
+                b'{' => Instruction::SelectTape(0),
+                b'}' => Instruction::SelectTape(1),
+                b'Y' => Instruction::Fork,
+                _ => Instruction::Debug,
+            };
+            self.commands += 1;
+            push_folded(&mut self.program, instruction);
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Program, Error> {
+        match self.open_loops.last() {
+            Some((index, _)) => Err(Error::MismatchedBrackets(*index)),
+            None => Ok(self.program),
+        }
+    }
+}
+
+/// Parses everything `reader` yields, `chunk_size` bytes at a time.
+#[cfg(feature = "std")]
+pub fn parse_stream(
+    reader: &mut dyn Read,
+    extensions: Extensions,
+    chunk_size: usize,
+) -> Result<Program, Error> {
+    let mut parser = StreamParser::new(extensions);
+    let mut chunk = vec![0; chunk_size];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => return parser.finish(),
+            Ok(read) => parser.feed(&chunk[..read])?,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+/// Emits the shortest run of repeated commands for each instruction. An
+/// `AddAt` is written as moves there, which the next instruction's moves
+/// then start from, so `>+>++<<` survives being addressed by offset.
+pub fn to_source(program: &[Instruction]) -> String {
+    let mut source = String::new();
+    let mut offset = 0; // How far the written pointer is from the real one
+    let write_move = |source: &mut String, amount: isize| {
+        let command = if amount > 0 { ">" } else { "<" };
+        source.push_str(&command.repeat(amount.unsigned_abs()))
+    };
+    for instruction in program {
+        match *instruction {
+            Instruction::AddAt(at, _) => {
+                write_move(&mut source, at - offset);
+                offset = at;
+            }
+            Instruction::Move(amount) => {
+                write_move(&mut source, amount - offset);
+                offset = 0;
+                continue;
+            }
+            _ => {
+                write_move(&mut source, -offset);
+                offset = 0;
+            }
+        }
+        match *instruction {
+            // Cells wrap at 256, so e.g. an add of 255 is better written as one `-`
+            Instruction::Add(amount) | Instruction::AddAt(_, amount) if amount <= 128 => {
+                source.push_str(&"+".repeat(amount as usize))
+            }
+            Instruction::Add(amount) | Instruction::AddAt(_, amount) => {
+                source.push_str(&"-".repeat(256 - amount as usize))
+            }
+            Instruction::Move(_) => unreachable!("moves are written above"),
+            Instruction::Output => source.push('.'),
+            Instruction::Input => source.push(','),
+            Instruction::LoopStart => source.push('['),
+            Instruction::LoopEnd => source.push(']'),
+            Instruction::Debug => source.push('#'),
+            Instruction::ProcedureStart => source.push('('),
+            Instruction::ProcedureEnd => source.push(')'),
+            Instruction::Call => source.push(':'),
+            Instruction::SelectTape(0) => source.push('{'),
+            Instruction::SelectTape(_) => source.push('}'),
+            Instruction::Fork => source.push('Y'),
+        }
+    }
+    write_move(&mut source, -offset);
+    source
+}
+
+/// Index of the `LoopEnd` matching the `LoopStart` at `loop_start`, or of
+/// the `ProcedureEnd` matching a `ProcedureStart`. The two always nest.
+pub fn matching_loop_end(program: &[Instruction], loop_start: usize) -> usize {
+    let mut depth = 0;
+    for (index, instruction) in program.iter().enumerate().skip(loop_start) {
+        match instruction {
+            Instruction::LoopStart | Instruction::ProcedureStart => depth += 1,
+            Instruction::LoopEnd | Instruction::ProcedureEnd if depth == 1 => return index,
+            Instruction::LoopEnd | Instruction::ProcedureEnd => depth -= 1,
+            _ => {}
+        }
+    }
+    panic!("unbalanced loop at instruction {}", loop_start);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_parser() {
+        let mut parser = StreamParser::new(Extensions::default());
+        // Split inside a run and inside a loop
+        for chunk in ["++", "+[>", " comment >-", "]<."] {
+            parser.feed(chunk.as_bytes()).unwrap();
+        }
+        let program = parser.finish().unwrap();
+        assert_eq!(to_source(&program), "+++[>>-]<.");
+
+        let source = b"+[[-]]]";
+        let error = parse_stream(&mut &source[..], Extensions::default(), 2);
+        assert_eq!(error, Err(Error::MismatchedBrackets(6)));
+        let error = parse_stream(&mut &b"x[[]"[..], Extensions::default(), 2);
+        assert_eq!(error, Err(Error::MismatchedBrackets(0)));
+
+        let pbrain = Extensions {
+            pbrain: true,
+            ..Extensions::default()
+        };
+        let error = parse_stream(&mut &b"([)]"[..], pbrain, 2);
+        assert_eq!(error, Err(Error::MismatchedBrackets(2)));
+    }
+
+    #[test]
+    fn test_parse_parallel() {
+        // Seams land inside runs, some of which cancel out across them
+        let source = "+++>>-<<[->+<]+-.".repeat(PARALLEL_MIN_BYTES / 16);
+        let expected = fold_runs(&parse(&source));
+        assert_eq!(parse_parallel(&source, 7), expected);
+        assert_eq!(parse_parallel(&"+-".repeat(PARALLEL_MIN_BYTES), 3), []);
+        assert_eq!(
+            parse_parallel("+>", 4),
+            [Instruction::Add(1), Instruction::Move(1)]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = parse("+-><.,[]#{}");
+        assert_eq!(program.len(), 11);
+        assert_eq!(program[1], Instruction::Add(255));
+        assert_eq!(program[10], Instruction::SelectTape(1));
+        assert_eq!(to_source(&program), "+-><.,[]#{}");
+        assert_eq!(
+            to_source(&[Instruction::Add(200), Instruction::Move(-3)]),
+            format!("{}<<<", "-".repeat(56))
+        );
+    }
+}
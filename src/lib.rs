@@ -0,0 +1,967 @@
+// Core library: the interpreter and the tooling built around it. The
+// `brainfuck-rs` binary is a thin command-line layer on top of this.
+//
+// Without the default `std` feature only the engine is built: the IR, the
+// interpreter, the optimizer and its analysis, and resource limits. That
+// needs nothing but `alloc`, so it runs on embedded targets and in bare WASM
+// hosts, with `execute` doing I/O through `ByteSource` and `ByteSink`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "async")]
+pub mod async_io;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+pub mod check;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod dialect;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod examples;
+#[cfg(feature = "std")]
+pub mod explain;
+#[cfg(feature = "ffi")]
+#[allow(non_camel_case_types)] // Named to match the C header
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod fmt;
+#[cfg(feature = "std")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod golden;
+#[cfg(feature = "std")]
+pub mod graph;
+#[cfg(feature = "std")]
+pub mod heatmap;
+pub mod interpreter;
+pub mod ir;
+#[cfg(feature = "std")]
+pub mod json;
+pub mod limits;
+#[cfg(feature = "std")]
+pub mod nested;
+pub mod optimize;
+#[cfg(feature = "std")]
+pub mod output;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod tape;
+#[cfg(feature = "std")]
+pub mod textgen;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "async")]
+pub use async_io::run_async;
+pub use interpreter::{Access, Interpreter, StepResult, Yield};
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use ir::Instruction;
+use limits::Limit;
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, Ordering};
+pub type JumpTable = Vec<usize>; // Indexed by position: each bracket's partner
+
+pub const MEMORY_SIZE: usize = 256;
+pub type Memory = [u8; MEMORY_SIZE];
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    MismatchedBrackets(usize), // Contains the index of the problematic character
+    #[cfg(feature = "std")]
+    Io(io::ErrorKind), // Writing the program's output failed
+    OutputFailed,              // A `ByteSink` couldn't take the program's output
+    Interrupted,               // An observer asked the run to stop
+    UndefinedProcedure(u8),    // `:` called a procedure that hasn't been defined
+    CallStackOverflow,         // Procedure calls nested deeper than `MAX_CALL_DEPTH`
+    LimitExceeded(Limit),      // An observer's resource cap was reached
+}
+
+// The binary's exit statuses, which scripts can rely on. Usage errors and
+// failures outside the program itself also exit with 1.
+pub const EXIT_RUNTIME_ERROR: i32 = 1;
+pub const EXIT_PARSE_ERROR: i32 = 2; // Also used when `--strict` rejects a program
+pub const EXIT_RESOURCE_LIMIT: i32 = 3;
+pub const EXIT_INTERRUPTED: i32 = 130; // As for SIGINT
+
+impl Error {
+    /// The exit status for a run that failed with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::MismatchedBrackets(_) => EXIT_PARSE_ERROR,
+            #[cfg(feature = "std")]
+            Error::Io(_) => EXIT_RUNTIME_ERROR,
+            Error::OutputFailed | Error::UndefinedProcedure(_) => EXIT_RUNTIME_ERROR,
+            Error::CallStackOverflow | Error::LimitExceeded(_) => EXIT_RESOURCE_LIMIT,
+            Error::Interrupted => EXIT_INTERRUPTED,
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Error::MismatchedBrackets(index) => {
+                write!(formatter, "mismatched bracket at index {}", index)
+            }
+            #[cfg(feature = "std")]
+            Error::Io(kind) => write!(formatter, "I/O error: {}", kind),
+            Error::OutputFailed => write!(formatter, "could not write output"),
+            Error::Interrupted => write!(formatter, "interrupted"),
+            Error::UndefinedProcedure(procedure) => {
+                write!(formatter, "call to undefined procedure {}", procedure)
+            }
+            Error::CallStackOverflow => write!(formatter, "procedure calls nested too deeply"),
+            Error::LimitExceeded(limit) => write!(formatter, "{}", limit),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error.kind())
+    }
+}
+
+/// Opt-in language extensions, enabled with `--extensions a,b,...`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Extensions {
+    pub debug: bool,     // `#` dumps the pointer and surrounding cells to stderr
+    pub pbrain: bool,    // `(`, `)` and `:` define and call procedures
+    pub dual_tape: bool, // `{` and `}` switch between two tapes, each with its own pointer
+    pub fork: bool,      // `Y` forks a thread; set by `--lang brainfork` rather than `--extensions`
+    pub io_mode: IoMode, // Set with `--io-mode` rather than `--extensions`
+    pub eof: EofMode,    // Set with `--eof` rather than `--extensions`
+}
+
+impl Extensions {
+    /// These settings with every language extension turned off, keeping the
+    /// I/O ones, which `--extensions` (or the config file's `extensions`)
+    /// doesn't set.
+    pub fn io_only(self) -> Extensions {
+        Extensions {
+            io_mode: self.io_mode,
+            eof: self.eof,
+            ..Extensions::default()
+        }
+    }
+
+    /// Turns on the extension called `name`, returning false if there's no
+    /// such extension.
+    pub fn enable(&mut self, name: &str) -> bool {
+        match name {
+            "debug" => self.debug = true,
+            "pbrain" => self.pbrain = true,
+            "dualtape" => self.dual_tape = true,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// How `.` and `,` exchange cells with the outside world.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum IoMode {
+    #[default]
+    Bytes, // Cells are written and read as raw bytes
+    Numeric, // `.` prints the cell in decimal on its own line; `,` reads a decimal integer
+}
+
+impl IoMode {
+    pub fn by_name(name: &str) -> Option<IoMode> {
+        match name {
+            "bytes" => Some(IoMode::Bytes),
+            "numeric" => Some(IoMode::Numeric),
+            _ => None,
+        }
+    }
+}
+
+/// What `,` does to the cell at end of input. Programs are written for one
+/// convention or another, so it's up to whoever runs them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum EofMode {
+    #[default]
+    Unchanged, // Leave the cell as it was
+    Zero,
+    Max, // 255, i.e. -1
+}
+
+impl EofMode {
+    pub fn by_name(name: &str) -> Option<EofMode> {
+        match name {
+            "unchanged" => Some(EofMode::Unchanged),
+            "zero" => Some(EofMode::Zero),
+            "max" => Some(EofMode::Max),
+            _ => None,
+        }
+    }
+
+    /// What the cell is set to at end of input, if anything.
+    pub fn value(self) -> Option<u8> {
+        match self {
+            EofMode::Unchanged => None,
+            EofMode::Zero => Some(0),
+            EofMode::Max => Some(u8::MAX),
+        }
+    }
+}
+
+/// Where `,` gets its bytes from, for `execute`.
+pub trait ByteSource {
+    /// The next byte, or None at end of input.
+    fn read_byte(&mut self) -> Option<u8>;
+
+    /// Reads the next whitespace-separated decimal integer, wrapped to fit a
+    /// cell. A token that isn't a number counts as end of input.
+    fn read_number(&mut self) -> Option<u8> {
+        let mut byte = self.read_byte()?;
+        while byte.is_ascii_whitespace() {
+            byte = self.read_byte()?;
+        }
+        let mut token = String::new();
+        loop {
+            token.push(byte as char);
+            match self.read_byte() {
+                Some(next) if !next.is_ascii_whitespace() => byte = next,
+                _ => break,
+            }
+        }
+        let number: i64 = token.parse().ok()?;
+        Some(number.rem_euclid(256) as u8)
+    }
+}
+
+/// Where `.` sends its bytes, for `execute`.
+pub trait ByteSink {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error>;
+
+    /// Called before waiting for input, so prompts show, and once the
+    /// program ends.
+    fn flush_bytes(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ByteSource for &[u8] {
+    fn read_byte(&mut self) -> Option<u8> {
+        let (byte, rest) = self.split_first()?;
+        *self = rest;
+        Some(*byte)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write + ?Sized> ByteSink for W {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        Ok(self.write_all(bytes)?)
+    }
+
+    fn flush_bytes(&mut self) -> Result<(), Error> {
+        Ok(self.flush()?)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl ByteSink for Vec<u8> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Bytes consumed by `,`: anything supplied up front (e.g. after a `!`) is
+/// used first, then we fall back to reading lines from real stdin, or to
+/// another reader such as the previous stage of a pipeline.
+#[cfg(feature = "std")]
+pub struct ProgramInput {
+    pending: VecDeque<u8>,
+    reader: Option<Box<dyn Read + Send>>, // Read once `pending` runs out; stdin if unset
+    stop: Option<&'static AtomicBool>,    // Once set, reads a signal interrupts give up
+}
+
+#[cfg(feature = "std")]
+impl ProgramInput {
+    pub fn new(pending: &[u8]) -> ProgramInput {
+        ProgramInput {
+            pending: pending.iter().copied().collect(),
+            reader: None,
+            stop: None,
+        }
+    }
+
+    /// Input read byte by byte from `reader`, with end of input where it ends.
+    pub fn from_reader(reader: Box<dyn Read + Send>) -> ProgramInput {
+        ProgramInput::new(&[]).with_reader(reader)
+    }
+
+    /// Reads from `reader` rather than lines of stdin once the pending bytes
+    /// run out, e.g. to layer a program's own input after some prefix.
+    pub fn with_reader(mut self, reader: Box<dyn Read + Send>) -> ProgramInput {
+        self.reader = Some(reader);
+        self
+    }
+
+    /// Treats input as ended if a read is interrupted (by a signal, say)
+    /// once `flag` is set, so a Ctrl-C handler can stop a `,` that's waiting
+    /// on the terminal. The handler mustn't ask for reads to be restarted.
+    pub fn stop_on(mut self, flag: &'static AtomicBool) -> ProgramInput {
+        self.stop = Some(flag);
+        self
+    }
+
+    pub fn read_byte(&mut self) -> Option<u8> {
+        if let Some(byte) = self.pending.pop_front() {
+            return Some(byte);
+        }
+        if self.reader.is_some() {
+            self.refill();
+            return self.pending.pop_front();
+        }
+        stdin_line(self.stop)?.first().copied()
+    }
+
+    // Reads more input into `pending`, returning false at end of input
+    fn refill(&mut self) -> bool {
+        let stop = self.stop;
+        let reader: &mut dyn Read = match &mut self.reader {
+            Some(reader) => reader,
+            None => {
+                let line = stdin_line(stop).unwrap_or_default();
+                self.pending.extend(&line);
+                return !line.is_empty();
+            }
+        };
+        let mut chunk = [0; 4096];
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(read) => {
+                    self.pending.extend(&chunk[..read]);
+                    return read > 0;
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted && !stopped(stop) => {}
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /// Reads the next whitespace-separated decimal integer, wrapped to fit a
+    /// cell. A token that isn't a number counts as end of input.
+    pub fn read_number(&mut self) -> Option<u8> {
+        loop {
+            match self.pending.front().copied() {
+                Some(byte) if byte.is_ascii_whitespace() => {
+                    self.pending.pop_front();
+                }
+                Some(_) => break,
+                None if !self.refill() => return None,
+                None => {}
+            }
+        }
+        let mut token = String::new();
+        while let Some(byte) = self.pending.pop_front() {
+            if byte.is_ascii_whitespace() {
+                break;
+            }
+            token.push(byte as char);
+        }
+        let number: i64 = token.parse().ok()?;
+        Some(number.rem_euclid(256) as u8)
+    }
+}
+
+#[cfg(feature = "std")]
+fn stopped(stop: Option<&AtomicBool>) -> bool {
+    stop.is_some_and(|flag| flag.load(Ordering::SeqCst))
+}
+
+// A line of stdin, newline included; empty at end of input. `read_line`
+// would retry reads a signal interrupts, so this reads the buffer itself
+#[cfg(feature = "std")]
+fn stdin_line(stop: Option<&AtomicBool>) -> Option<Vec<u8>> {
+    use std::io::BufRead;
+    let mut stdin = std::io::stdin().lock();
+    let mut line = Vec::new();
+    loop {
+        let available = match stdin.fill_buf() {
+            Ok(available) => available,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted && !stopped(stop) => continue,
+            Err(_) => return None,
+        };
+        let (taken, done) = match available.iter().position(|byte| *byte == b'\n') {
+            Some(end) => (end + 1, true),
+            None => (available.len(), available.is_empty()),
+        };
+        line.extend_from_slice(&available[..taken]);
+        stdin.consume(taken);
+        if done {
+            return Some(line);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ByteSource for ProgramInput {
+    fn read_byte(&mut self) -> Option<u8> {
+        ProgramInput::read_byte(self)
+    }
+
+    fn read_number(&mut self) -> Option<u8> {
+        ProgramInput::read_number(self)
+    }
+}
+
+/// Where `,` reads from, so program input doesn't have to be typed in.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum InputSource {
+    #[default]
+    Stdin,
+    Bytes(Vec<u8>), // Given inline, as a string or in hex
+    File(PathBuf),
+    Random(u64), // Seed for an endless pseudo-random stream
+}
+
+/// What `,` gets once the data from an `InputSource` runs out.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum InputEnd {
+    #[default]
+    Stdin, // Carry on with lines of stdin
+    Eof, // End of input
+}
+
+#[cfg(feature = "std")]
+impl InputSource {
+    /// Parses `random:SEED`, `file:PATH`, `str:TEXT` or `hex:DIGITS`.
+    pub fn parse(spec: &str) -> Result<InputSource, String> {
+        match spec.split_once(':') {
+            Some(("random", seed)) => seed
+                .parse()
+                .map(InputSource::Random)
+                .map_err(|_| format!("invalid seed '{}' for random input", seed)),
+            Some(("file", path)) => Ok(InputSource::File(PathBuf::from(path))),
+            Some(("str", text)) => Ok(InputSource::Bytes(text.as_bytes().to_vec())),
+            Some(("hex", digits)) => InputSource::from_hex(digits),
+            _ => Err(format!("unknown input source '{}'", spec)),
+        }
+    }
+
+    /// Bytes written as pairs of hex digits, optionally separated by spaces.
+    pub fn from_hex(hex: &str) -> Result<InputSource, String> {
+        let digits: Vec<char> = hex.chars().filter(|digit| *digit != ' ').collect();
+        if !digits.len().is_multiple_of(2) {
+            return Err(format!("odd number of hex digits in '{}'", hex));
+        }
+        digits
+            .chunks(2)
+            .map(|pair| {
+                let pair: String = pair.iter().collect();
+                u8::from_str_radix(&pair, 16).map_err(|_| format!("invalid hex byte '{}'", pair))
+            })
+            .collect::<Result<_, _>>()
+            .map(InputSource::Bytes)
+    }
+
+    /// Input for `,` that starts with `pending` and continues from this source.
+    pub fn open(&self, pending: &[u8], end: InputEnd) -> io::Result<ProgramInput> {
+        let data = match self {
+            InputSource::Stdin => return Ok(ProgramInput::new(pending)),
+            InputSource::Random(seed) => {
+                let random = Box::new(fuzz::RandomBytes::new(*seed));
+                return Ok(ProgramInput::new(pending).with_reader(random));
+            }
+            InputSource::Bytes(bytes) => bytes.clone(),
+            InputSource::File(path) => std::fs::read(path)?,
+        };
+        let input = ProgramInput::new(&[pending, &data].concat());
+        Ok(match end {
+            InputEnd::Stdin => input,
+            InputEnd::Eof => input.with_reader(Box::new(io::empty())),
+        })
+    }
+}
+
+/// Collects every byte the program reads, e.g. to replay an interactive
+/// session later with `ProgramInput::from_reader`.
+#[derive(Debug, Default)]
+pub struct InputRecorder {
+    pub bytes: Vec<u8>,
+}
+
+impl ExecutionObserver for InputRecorder {
+    fn on_input(&mut self, byte: Option<u8>) {
+        self.bytes.extend(byte);
+    }
+}
+
+/// Splits `code!input` at the first `!`, returning the code and the input bytes.
+pub fn split_bang_input(source: &str) -> (&str, &[u8]) {
+    match source.split_once('!') {
+        Some((code, input)) => (code, input.as_bytes()),
+        None => (source, &[]),
+    }
+}
+
+pub fn generate_jump_table(source_code: &str) -> Result<JumpTable, Error> {
+    jump_table(&ir::parse(source_code))
+}
+
+/// Pairs up the loops (and pbrain procedures) of a program, checking its
+/// brackets. Every bracket's entry holds the position of its partner, so
+/// jumps resolve in constant time in either direction; other entries are
+/// unused.
+pub fn jump_table(program: &[Instruction]) -> Result<JumpTable, Error> {
+    let mut jumps = vec![0; program.len()];
+    let mut bracket_stack = Vec::new();
+    for (index, instruction) in program.iter().enumerate() {
+        let opening = match instruction {
+            Instruction::LoopStart | Instruction::ProcedureStart => {
+                bracket_stack.push(index);
+                continue;
+            }
+            Instruction::LoopEnd => Instruction::LoopStart,
+            Instruction::ProcedureEnd => Instruction::ProcedureStart,
+            _ => continue,
+        };
+        let index_of_opening_bracket = bracket_stack
+            .pop()
+            .filter(|start| program[*start] == opening)
+            .ok_or(Error::MismatchedBrackets(index))?;
+        jumps[index_of_opening_bracket] = index;
+        jumps[index] = index_of_opening_bracket;
+    }
+    if let Some(index) = bracket_stack.last() {
+        return Err(Error::MismatchedBrackets(*index));
+    }
+    Ok(jumps)
+}
+
+/// Hooks called by `run` as the program executes, so downstream tools
+/// (visualizers, debuggers, coverage tools) can follow along without a copy
+/// of the run loop. Every method defaults to doing nothing.
+pub trait ExecutionObserver {
+    /// Called before the instruction at `source_pointer` executes.
+    fn on_instruction(&mut self, _source_pointer: usize, _memory: &Memory, _memory_pointer: usize) {
+    }
+    /// Called after `on_instruction` when the instruction reads or writes
+    /// the cell under the pointer, which is `cell`.
+    fn on_access(&mut self, _cell: usize, _access: Access) {}
+    /// Called with every byte written by `.`.
+    fn on_output(&mut self, _byte: u8) {}
+    /// Called with every byte read by `,`, or `None` at end of input.
+    fn on_input(&mut self, _byte: Option<u8>) {}
+    /// Called when the `[` at `source_pointer` starts a loop. Loops skipped
+    /// because the cell is already zero are neither entered nor exited.
+    fn on_loop_enter(&mut self, _source_pointer: usize) {}
+    /// Called when the `]` at `source_pointer` falls through, ending its loop.
+    fn on_loop_exit(&mut self, _source_pointer: usize) {}
+    /// Checked before every instruction; returning true ends the run with
+    /// `Error::Interrupted`.
+    fn should_stop(&self) -> bool {
+        false
+    }
+    /// Checked before every instruction and every byte of output; returning
+    /// a limit ends the run with `Error::LimitExceeded` before it happens.
+    fn exceeded_limit(&self) -> Option<Limit> {
+        None
+    }
+}
+
+impl ExecutionObserver for () {}
+
+impl<T: ExecutionObserver + ?Sized> ExecutionObserver for &mut T {
+    fn on_instruction(&mut self, source_pointer: usize, memory: &Memory, memory_pointer: usize) {
+        (**self).on_instruction(source_pointer, memory, memory_pointer)
+    }
+    fn on_access(&mut self, cell: usize, access: Access) {
+        (**self).on_access(cell, access)
+    }
+    fn on_output(&mut self, byte: u8) {
+        (**self).on_output(byte)
+    }
+    fn on_input(&mut self, byte: Option<u8>) {
+        (**self).on_input(byte)
+    }
+    fn on_loop_enter(&mut self, source_pointer: usize) {
+        (**self).on_loop_enter(source_pointer)
+    }
+    fn on_loop_exit(&mut self, source_pointer: usize) {
+        (**self).on_loop_exit(source_pointer)
+    }
+    fn should_stop(&self) -> bool {
+        (**self).should_stop()
+    }
+    fn exceeded_limit(&self) -> Option<Limit> {
+        (**self).exceeded_limit()
+    }
+}
+
+/// Runs two observers side by side, e.g. `&mut (&mut coverage, &mut heatmap)`.
+impl<A: ExecutionObserver, B: ExecutionObserver> ExecutionObserver for (A, B) {
+    fn on_instruction(&mut self, source_pointer: usize, memory: &Memory, memory_pointer: usize) {
+        self.0
+            .on_instruction(source_pointer, memory, memory_pointer);
+        self.1
+            .on_instruction(source_pointer, memory, memory_pointer);
+    }
+    fn on_access(&mut self, cell: usize, access: Access) {
+        self.0.on_access(cell, access);
+        self.1.on_access(cell, access);
+    }
+    fn on_output(&mut self, byte: u8) {
+        self.0.on_output(byte);
+        self.1.on_output(byte);
+    }
+    fn on_input(&mut self, byte: Option<u8>) {
+        self.0.on_input(byte);
+        self.1.on_input(byte);
+    }
+    fn on_loop_enter(&mut self, source_pointer: usize) {
+        self.0.on_loop_enter(source_pointer);
+        self.1.on_loop_enter(source_pointer);
+    }
+    fn on_loop_exit(&mut self, source_pointer: usize) {
+        self.0.on_loop_exit(source_pointer);
+        self.1.on_loop_exit(source_pointer);
+    }
+    fn should_stop(&self) -> bool {
+        self.0.should_stop() || self.1.should_stop()
+    }
+    fn exceeded_limit(&self) -> Option<Limit> {
+        self.0.exceeded_limit().or(self.1.exceeded_limit())
+    }
+}
+
+/// Runs sanitized source, writing the bytes it prints to `output` as they
+/// are. Use `output::Output` to turn them into text.
+#[cfg(feature = "std")]
+pub fn run(
+    source_code: &str,
+    extensions: Extensions,
+    input: &mut ProgramInput,
+    output: &mut dyn Write,
+    observer: &mut dyn ExecutionObserver,
+) -> Result<(), Error> {
+    let interpreter = Interpreter::new(source_code, extensions)?;
+    run_interpreter(interpreter, input, output, observer)
+}
+
+/// Like `run`, for an interpreter that has already been set up.
+#[cfg(feature = "std")]
+pub fn run_interpreter(
+    mut interpreter: Interpreter,
+    input: &mut ProgramInput,
+    mut output: &mut dyn Write,
+    observer: &mut dyn ExecutionObserver,
+) -> Result<(), Error> {
+    execute(&mut interpreter, input, &mut output, observer)
+}
+
+/// Runs the interpreter to the end over any byte I/O: what `run` does,
+/// without needing `std`. The interpreter is left as the program left it,
+/// e.g. to `append` more and carry on.
+pub fn execute(
+    interpreter: &mut Interpreter,
+    input: &mut dyn ByteSource,
+    output: &mut dyn ByteSink,
+    observer: &mut dyn ExecutionObserver,
+) -> Result<(), Error> {
+    while let Some(instruction) = interpreter.current_instruction() {
+        if observer.should_stop() {
+            return Err(Error::Interrupted);
+        }
+        let source_pointer = interpreter.source_pointer();
+        let cell = interpreter.memory()[interpreter.memory_pointer()];
+        observer.on_instruction(
+            source_pointer,
+            interpreter.memory(),
+            interpreter.memory_pointer(),
+        );
+        if let Some((cell, access)) = interpreter.next_access() {
+            observer.on_access(cell, access);
+        }
+        match instruction {
+            Instruction::LoopStart if cell != 0 => observer.on_loop_enter(source_pointer),
+            Instruction::LoopEnd if cell == 0 => observer.on_loop_exit(source_pointer),
+            _ => {}
+        }
+        if let Some(limit) = observer.exceeded_limit() {
+            return Err(Error::LimitExceeded(limit));
+        }
+        match interpreter.step()? {
+            StepResult::Output(byte) => {
+                observer.on_output(byte);
+                if let Some(limit) = observer.exceeded_limit() {
+                    return Err(Error::LimitExceeded(limit));
+                }
+                match interpreter.extensions().io_mode {
+                    IoMode::Bytes => output.write_bytes(&[byte])?,
+                    IoMode::Numeric => {
+                        output.write_bytes(alloc::format!("{}\n", byte).as_bytes())?
+                    }
+                }
+            }
+            StepResult::NeedsInput => {
+                // Whatever the program printed (e.g. a prompt) should be
+                // visible before we wait for input
+                output.flush_bytes()?;
+                let byte = match interpreter.extensions().io_mode {
+                    IoMode::Bytes => input.read_byte(),
+                    IoMode::Numeric => input.read_number(),
+                };
+                // Waiting for input is where a run is most often stopped; it
+                // stops before the `,` rather than taking the input as ended
+                if observer.should_stop() {
+                    return Err(Error::Interrupted);
+                }
+                observer.on_input(byte);
+                interpreter.provide_input(byte);
+                interpreter.step()?;
+            }
+            StepResult::Continue | StepResult::Halted => {}
+        }
+    }
+    output.flush_bytes()?;
+    Ok(())
+}
+
+/// Whether `sanitize_input` keeps this character.
+pub fn is_command(character: char, extensions: Extensions) -> bool {
+    match character {
+        '>' | '<' | '+' | '-' | '.' | ',' | '[' | ']' => true,
+        '#' => extensions.debug,
+        '(' | ')' | ':' => extensions.pbrain,
+        '{' | '}' => extensions.dual_tape,
+        'Y' => extensions.fork,
+        _ => false,
+    }
+}
+
+pub fn sanitize_input(input: &str, extensions: Extensions) -> String {
+    input
+        .chars()
+        .filter(|character| is_command(*character, extensions))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_generate_jump_table() {
+        let source_code = "[[]]";
+        let result = generate_jump_table(source_code).unwrap();
+        assert_eq!(result, vec![3, 2, 1, 0]);
+
+        let source_code2 = "[+[]-]";
+        let result2 = generate_jump_table(source_code2).unwrap();
+        assert_eq!(result2, vec![5, 0, 3, 2, 0, 0]);
+
+        let source_code3 = "[]]";
+        let result3 = generate_jump_table(source_code3);
+        assert!(result3.is_err());
+        assert_eq!(result3.unwrap_err(), Error::MismatchedBrackets(2));
+
+        assert_eq!(generate_jump_table("([])").unwrap(), vec![3, 2, 1, 0]);
+        assert_eq!(
+            generate_jump_table("[(])"),
+            Err(Error::MismatchedBrackets(2))
+        );
+    }
+
+    #[test]
+    fn test_debug_extension() {
+        let source = "+#-".to_string();
+        assert_eq!(sanitize_input(&source, Extensions::default()), "+-");
+        let extensions = Extensions {
+            debug: true,
+            ..Extensions::default()
+        };
+        assert_eq!(sanitize_input(&source, extensions), "+#-");
+    }
+
+    #[test]
+    fn test_bang_input() {
+        assert_eq!(split_bang_input(",.,.!hi!"), (",.,.", &b"hi!"[..]));
+        assert_eq!(split_bang_input("+."), ("+.", &b""[..]));
+
+        let mut input = ProgramInput::new(b"ab");
+        assert_eq!(input.read_byte(), Some(b'a'));
+        assert_eq!(input.read_byte(), Some(b'b'));
+    }
+
+    #[test]
+    fn test_stop_on() {
+        // A read that's always interrupted, like one waiting on a terminal
+        // while Ctrl-C is pressed
+        struct Interrupted;
+        impl Read for Interrupted {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::Interrupted.into())
+            }
+        }
+        static STOP: AtomicBool = AtomicBool::new(true);
+        let mut input = ProgramInput::from_reader(Box::new(Interrupted)).stop_on(&STOP);
+        assert_eq!(input.read_byte(), None);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut recorder = InputRecorder::default();
+        let mut input = ProgramInput::new(b"hi");
+        let mut output = Vec::new();
+        let source = ",.,.";
+        run(
+            source,
+            Extensions::default(),
+            &mut input,
+            &mut output,
+            &mut recorder,
+        )
+        .unwrap();
+        assert_eq!(recorder.bytes, b"hi");
+
+        // Reading past the end of a recording is end of input, not stdin
+        let mut replay = ProgramInput::from_reader(Box::new(io::Cursor::new(recorder.bytes)));
+        let mut replayed = Vec::new();
+        run(
+            ",.,.,.",
+            Extensions::default(),
+            &mut replay,
+            &mut replayed,
+            &mut (),
+        )
+        .unwrap();
+        assert_eq!(replayed, b"hii");
+    }
+
+    #[test]
+    fn test_input_source() {
+        assert_eq!(
+            InputSource::from_hex("41 4243"),
+            Ok(InputSource::Bytes(b"ABC".to_vec()))
+        );
+        assert!(InputSource::from_hex("414").is_err());
+        assert!(InputSource::from_hex("4g").is_err());
+        assert_eq!(InputSource::parse("random:7"), Ok(InputSource::Random(7)));
+        assert!(InputSource::parse("tape:1").is_err());
+
+        let source = InputSource::parse("str:yz").unwrap();
+        let mut input = source.open(b"x", InputEnd::Eof).unwrap();
+        let mut output = Vec::new();
+        run(
+            ",.,.,.,.",
+            Extensions::default(),
+            &mut input,
+            &mut output,
+            &mut (),
+        )
+        .unwrap();
+        assert_eq!(output, b"xyzz");
+    }
+
+    #[test]
+    fn test_numeric_io() {
+        let mut input = ProgramInput::new(b" 12\n-1 300 x 7");
+        assert_eq!(input.read_number(), Some(12));
+        assert_eq!(input.read_number(), Some(255));
+        assert_eq!(input.read_number(), Some(44));
+        assert_eq!(input.read_number(), None);
+        assert_eq!(input.read_number(), Some(7));
+
+        let extensions = Extensions {
+            io_mode: IoMode::Numeric,
+            ..Extensions::default()
+        };
+        let mut input = ProgramInput::new(b"41 9");
+        let mut output = Vec::new();
+        run(",+.,.", extensions, &mut input, &mut output, &mut ()).unwrap();
+        assert_eq!(output, b"42\n9\n");
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut interpreter = Interpreter::new(",[.,]", Extensions::default()).unwrap();
+        let mut input: &[u8] = b"hi\0";
+        let mut output = Vec::new();
+        execute(&mut interpreter, &mut input, &mut output, &mut ()).unwrap();
+        assert_eq!(output, b"hi");
+
+        // Numbers without `ProgramInput`'s lookahead read the same way
+        let mut input: &[u8] = b" 12\n-1 x 7";
+        assert_eq!(input.read_number(), Some(12));
+        assert_eq!(input.read_number(), Some(255));
+        assert_eq!(input.read_number(), None);
+        assert_eq!(input.read_number(), Some(7));
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: Vec<String>,
+    }
+
+    impl ExecutionObserver for RecordingObserver {
+        fn on_output(&mut self, byte: u8) {
+            self.events.push(format!("output {}", byte));
+        }
+        fn on_input(&mut self, byte: Option<u8>) {
+            self.events.push(format!("input {:?}", byte));
+        }
+        fn on_loop_enter(&mut self, source_pointer: usize) {
+            self.events.push(format!("enter {}", source_pointer));
+        }
+        fn on_loop_exit(&mut self, source_pointer: usize) {
+            self.events.push(format!("exit {}", source_pointer));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut observer = RecordingObserver::default();
+        let mut input = ProgramInput::new(b"\x02");
+        let mut output = Vec::new();
+        run(
+            ",[.-][]",
+            Extensions::default(),
+            &mut input,
+            &mut output,
+            &mut observer,
+        )
+        .unwrap();
+        assert_eq!(output, [2, 1]);
+        assert_eq!(
+            observer.events,
+            ["input Some(2)", "enter 1", "output 2", "output 1", "exit 4"]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+// Resource caps for running untrusted programs. `Sandbox` watches a run and
+// makes `run_interpreter` fail with `Error::LimitExceeded` as soon as the
+// program would go over a cap, before the offending instruction (or byte of
+// output) takes effect.
+
+use crate::{Access, ExecutionObserver, Memory, MEMORY_SIZE};
+use alloc::boxed::Box;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub max_tape_bytes: Option<usize>, // Distinct cells read or written; the tape is still `MEMORY_SIZE`
+    pub max_output_bytes: Option<usize>,
+}
+
+/// The cap a run went over, with its value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Steps(u64),
+    TapeBytes(usize),
+    OutputBytes(usize),
+}
+
+impl core::fmt::Display for Limit {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Limit::Steps(steps) => write!(formatter, "step limit of {} exceeded", steps),
+            Limit::TapeBytes(bytes) => write!(formatter, "tape limit of {} bytes exceeded", bytes),
+            Limit::OutputBytes(bytes) => {
+                write!(formatter, "output limit of {} bytes exceeded", bytes)
+            }
+        }
+    }
+}
+
+pub struct Sandbox {
+    limits: Limits,
+    pub steps: u64,
+    pub tape_bytes: usize,
+    pub output_bytes: usize,
+    touched: Box<[bool; MEMORY_SIZE]>,
+    exceeded: Option<Limit>,
+}
+
+impl Sandbox {
+    pub fn new(limits: Limits) -> Sandbox {
+        Sandbox {
+            limits,
+            steps: 0,
+            tape_bytes: 0,
+            output_bytes: 0,
+            touched: Box::new([false; MEMORY_SIZE]),
+            exceeded: None,
+        }
+    }
+
+    // Records the first cap to be passed
+    fn check(&mut self, limit: Limit, over: bool) {
+        if over && self.exceeded.is_none() {
+            self.exceeded = Some(limit);
+        }
+    }
+}
+
+impl ExecutionObserver for Sandbox {
+    fn on_instruction(&mut self, _source_pointer: usize, _memory: &Memory, _memory_pointer: usize) {
+        self.steps += 1;
+        if let Some(max) = self.limits.max_steps {
+            self.check(Limit::Steps(max), self.steps > max);
+        }
+    }
+
+    fn on_access(&mut self, cell: usize, _access: Access) {
+        if !core::mem::replace(&mut self.touched[cell], true) {
+            self.tape_bytes += 1;
+        }
+        if let Some(max) = self.limits.max_tape_bytes {
+            self.check(Limit::TapeBytes(max), self.tape_bytes > max);
+        }
+    }
+
+    fn on_output(&mut self, _byte: u8) {
+        self.output_bytes += 1;
+        if let Some(max) = self.limits.max_output_bytes {
+            self.check(Limit::OutputBytes(max), self.output_bytes > max);
+        }
+    }
+
+    fn exceeded_limit(&self) -> Option<Limit> {
+        self.exceeded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run, Error, Extensions, ProgramInput};
+
+    fn run_limited(source: &str, limits: Limits) -> (Result<(), Error>, Vec<u8>) {
+        let mut output = Vec::new();
+        let result = run(
+            source,
+            Extensions::default(),
+            &mut ProgramInput::new(&[]),
+            &mut output,
+            &mut Sandbox::new(limits),
+        );
+        (result, output)
+    }
+
+    #[test]
+    fn test_limits() {
+        let steps = Limits {
+            max_steps: Some(100),
+            ..Limits::default()
+        };
+        assert_eq!(
+            run_limited("+[]", steps).0,
+            Err(Error::LimitExceeded(Limit::Steps(100)))
+        );
+        assert_eq!(run_limited("+++.", steps), (Ok(()), vec![3]));
+
+        let tape = Limits {
+            max_tape_bytes: Some(2),
+            ..Limits::default()
+        };
+        assert_eq!(run_limited("+>+<+>+", tape).0, Ok(()));
+        assert_eq!(
+            run_limited("+>+>+", tape).0,
+            Err(Error::LimitExceeded(Limit::TapeBytes(2)))
+        );
+
+        let output = Limits {
+            max_output_bytes: Some(2),
+            ..Limits::default()
+        };
+        assert_eq!(
+            run_limited("+...", output),
+            (Err(Error::LimitExceeded(Limit::OutputBytes(2))), vec![1, 1])
+        );
+    }
+}
@@ -0,0 +1,479 @@
+// `brainfuck-rs lsp`: a Language Server Protocol server over stdio for
+// editor integrations. It publishes `check` diagnostics as documents change,
+// highlights the bracket matching the one under the cursor, shows the
+// statically inferred effect of the code under the cursor on hover (as
+// `explain` pseudo-code relative to the pointer), and formats documents with
+// the `fmt` engine.
+//
+// Documents are synced whole on every change; they're small enough that
+// reanalyzing from scratch is cheaper than tracking edits.
+
+use crate::framing::{read_message, write_message};
+use brainfuck_rs::check::{self, Severity};
+use brainfuck_rs::fmt::{self, FormatOptions};
+use brainfuck_rs::json::{self, Value};
+use brainfuck_rs::{explain, ir, is_command, sanitize_input, Extensions};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_FAILED: i64 = -32803;
+
+// Zero-based line and UTF-16 offset, as the protocol counts them
+type Position = (usize, usize);
+
+struct Document {
+    text: String,
+    source: Vec<char>,           // Sanitized
+    positions: Vec<Position>,    // Of each command
+    matches: Vec<Option<usize>>, // The bracket matching each command, if it's a matched one
+}
+
+impl Document {
+    fn new(text: String, extensions: Extensions) -> Document {
+        let source: Vec<char> = sanitize_input(&text, extensions).chars().collect();
+        let mut positions = Vec::new();
+        let (mut line, mut character) = (0, 0);
+        for symbol in text.chars() {
+            if is_command(symbol, extensions) {
+                positions.push((line, character));
+            }
+            if symbol == '\n' {
+                line += 1;
+                character = 0;
+            } else {
+                character += symbol.len_utf16();
+            }
+        }
+        let mut matches = vec![None; source.len()];
+        let mut open: Vec<usize> = Vec::new();
+        for (index, command) in source.iter().enumerate() {
+            match command {
+                '[' | '(' => open.push(index),
+                ']' | ')' => {
+                    let opening = if *command == ']' { '[' } else { '(' };
+                    match open.pop() {
+                        Some(start) if source[start] == opening => {
+                            matches[start] = Some(index);
+                            matches[index] = Some(start);
+                        }
+                        // Past a mismatch nothing else can be trusted to pair up
+                        _ => break,
+                    }
+                }
+                _ => {}
+            }
+        }
+        Document {
+            text,
+            source,
+            positions,
+            matches,
+        }
+    }
+
+    // The command under the cursor, or the one just before it
+    fn command_at(&self, (line, character): Position) -> Option<usize> {
+        let exact = self
+            .positions
+            .iter()
+            .position(|at| *at == (line, character));
+        exact.or_else(|| {
+            let before = character.checked_sub(1)?;
+            self.positions.iter().position(|at| *at == (line, before))
+        })
+    }
+
+    fn range(&self, index: usize) -> String {
+        let (line, character) = self
+            .positions
+            .get(index)
+            .or(self.positions.last())
+            .copied()
+            .unwrap_or((0, 0));
+        range((line, character), (line, character + 1))
+    }
+
+    // Where the text ends, for edits that replace all of it
+    fn end(&self) -> Position {
+        let line = self.text.matches('\n').count();
+        let last = self.text.rsplit('\n').next().unwrap_or("");
+        (line, last.encode_utf16().count())
+    }
+
+    // The code whose effect is shown when hovering over `index`: the whole
+    // loop (or procedure) for a bracket, the surrounding run of adds and
+    // moves for those, and otherwise just the command itself.
+    fn fragment(&self, index: usize) -> Option<(usize, usize)> {
+        let arithmetic = |command: &char| "+-<>".contains(*command);
+        match self.source[index] {
+            '[' | '(' | ']' | ')' => {
+                let other = self.matches[index]?;
+                Some((index.min(other), index.max(other)))
+            }
+            command if arithmetic(&command) => {
+                let start = self.source[..index]
+                    .iter()
+                    .rposition(|command| !arithmetic(command))
+                    .map_or(0, |before| before + 1);
+                let end = self.source[index..]
+                    .iter()
+                    .position(|command| !arithmetic(command))
+                    .map_or(self.source.len(), |after| index + after)
+                    - 1;
+                Some((start, end))
+            }
+            _ => Some((index, index)),
+        }
+    }
+}
+
+fn range(start: Position, end: Position) -> String {
+    let position = |(line, character): Position| {
+        json::object(&[
+            ("line", line.to_string()),
+            ("character", character.to_string()),
+        ])
+    };
+    json::object(&[("start", position(start)), ("end", position(end))])
+}
+
+// Request ids are echoed back as they came, number or string
+fn encode_id(id: &Value) -> String {
+    match id {
+        Value::String(id) => json::string(id),
+        Value::Number(id) => id.to_string(),
+        _ => "null".to_string(),
+    }
+}
+
+fn text_document_uri(params: &Value) -> Option<&str> {
+    params.get("textDocument")?.get("uri")?.as_str()
+}
+
+fn cursor(params: &Value) -> Option<Position> {
+    let position = params.get("position")?;
+    let line = position.get("line")?.as_u64()?;
+    let character = position.get("character")?.as_u64()?;
+    Some((line as usize, character as usize))
+}
+
+pub struct Server<W: Write> {
+    writer: W,
+    extensions: Extensions,
+    documents: HashMap<String, Document>, // By URI
+    finished: bool,                       // Set by the `exit` notification
+}
+
+impl<W: Write> Server<W> {
+    pub fn new(writer: W, extensions: Extensions) -> Server<W> {
+        Server {
+            writer,
+            extensions,
+            documents: HashMap::new(),
+            finished: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn send(&mut self, mut fields: Vec<(&str, String)>) {
+        fields.insert(0, ("jsonrpc", json::string("2.0")));
+        // There's nobody left to report a broken connection to
+        let _ = write_message(&mut self.writer, &json::object(&fields));
+    }
+
+    fn notify(&mut self, method: &str, params: String) {
+        self.send(vec![("method", json::string(method)), ("params", params)]);
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) {
+        let diagnostics: Vec<String> = match self.documents.get(uri) {
+            Some(document) => {
+                let source: String = document.source.iter().collect();
+                // Suggestions are worded with the one-based positions `check` prints
+                let positions: Vec<Position> = document
+                    .positions
+                    .iter()
+                    .map(|(line, character)| (line + 1, character + 1))
+                    .collect();
+                check::check(&source)
+                    .iter()
+                    .map(|diagnostic| {
+                        let severity = match diagnostic.severity {
+                            Severity::Error => 1,
+                            Severity::Warning => 2,
+                        };
+                        let message = match diagnostic.repair {
+                            Some(repair) => {
+                                format!("{}\n{}", diagnostic.message, repair.describe(&positions))
+                            }
+                            None => diagnostic.message.clone(),
+                        };
+                        json::object(&[
+                            ("range", document.range(diagnostic.index)),
+                            ("severity", severity.to_string()),
+                            ("code", json::string(diagnostic.code)),
+                            ("source", json::string("brainfuck-rs")),
+                            ("message", json::string(&message)),
+                        ])
+                    })
+                    .collect()
+            }
+            None => Vec::new(), // Clears them once a document is closed
+        };
+        let params = json::object(&[
+            ("uri", json::string(uri)),
+            ("diagnostics", json::array(&diagnostics)),
+        ]);
+        self.notify("textDocument/publishDiagnostics", params);
+    }
+
+    pub fn handle(&mut self, message: &Value) {
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let Some(id) = message.get("id") else {
+            self.notification(method, &params);
+            return;
+        };
+        let result = self.request(method, &params);
+        let mut fields = vec![("id", encode_id(id))];
+        match result {
+            Ok(result) => fields.push(("result", result)),
+            Err((code, message)) => fields.push((
+                "error",
+                json::object(&[
+                    ("code", code.to_string()),
+                    ("message", json::string(&message)),
+                ]),
+            )),
+        }
+        self.send(fields);
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) {
+        let Some(uri) = text_document_uri(params) else {
+            self.finished |= method == "exit";
+            return;
+        };
+        let uri = uri.to_string();
+        let text = match method {
+            "textDocument/didOpen" => params
+                .get("textDocument")
+                .and_then(|document| document.get("text")?.as_str()),
+            // Only full syncs are advertised, so the last change is the whole text
+            "textDocument/didChange" => match params.get("contentChanges") {
+                Some(Value::Array(changes)) => changes
+                    .last()
+                    .and_then(|change| change.get("text")?.as_str()),
+                _ => None,
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri);
+                return;
+            }
+            _ => return,
+        };
+        if let Some(text) = text {
+            let document = Document::new(text.to_string(), self.extensions);
+            self.documents.insert(uri.clone(), document);
+            self.publish_diagnostics(&uri);
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<String, (i64, String)> {
+        let document = || {
+            text_document_uri(params)
+                .and_then(|uri| self.documents.get(uri))
+                .ok_or((INVALID_PARAMS, "unknown document".to_string()))
+        };
+        match method {
+            "initialize" => {
+                let capabilities = json::object(&[
+                    ("textDocumentSync", "1".to_string()), // Full
+                    ("hoverProvider", "true".to_string()),
+                    ("documentHighlightProvider", "true".to_string()),
+                    ("documentFormattingProvider", "true".to_string()),
+                ]);
+                let server_info = json::object(&[
+                    ("name", json::string("brainfuck-rs")),
+                    ("version", json::string(env!("CARGO_PKG_VERSION"))),
+                ]);
+                Ok(json::object(&[
+                    ("capabilities", capabilities),
+                    ("serverInfo", server_info),
+                ]))
+            }
+            "shutdown" => Ok("null".to_string()),
+            "textDocument/documentHighlight" => {
+                let document = document()?;
+                let highlights = cursor(params)
+                    .and_then(|cursor| document.command_at(cursor))
+                    .and_then(|index| Some((index, document.matches[index]?)))
+                    .map(|(index, other)| {
+                        [index, other]
+                            .map(|at| json::object(&[("range", document.range(at))]))
+                            .to_vec()
+                    });
+                Ok(highlights.map_or("null".to_string(), |highlights| json::array(&highlights)))
+            }
+            "textDocument/hover" => {
+                let document = document()?;
+                let Some((start, end)) = cursor(params)
+                    .and_then(|cursor| document.command_at(cursor))
+                    .and_then(|index| document.fragment(index))
+                else {
+                    return Ok("null".to_string());
+                };
+                let fragment: String = document.source[start..=end].iter().collect();
+                let explanation = explain::explain_fragment(&ir::parse(&fragment));
+                let explanation = match explanation.as_str() {
+                    "" => "no effect\n",
+                    explanation => explanation,
+                };
+                let contents = json::object(&[
+                    ("kind", json::string("markdown")),
+                    ("value", json::string(&format!("```\n{}```", explanation))),
+                ]);
+                let (start_line, start_character) = document.positions[start];
+                let (end_line, end_character) = document.positions[end];
+                Ok(json::object(&[
+                    ("contents", contents),
+                    (
+                        "range",
+                        range((start_line, start_character), (end_line, end_character + 1)),
+                    ),
+                ]))
+            }
+            "textDocument/formatting" => {
+                let document = document()?;
+                // `fmt` works on sanitized source, so it would drop comments
+                if document.text.chars().any(|character| {
+                    !character.is_whitespace() && !is_command(character, self.extensions)
+                }) {
+                    return Err((
+                        REQUEST_FAILED,
+                        "formatting would remove the comments in this document".to_string(),
+                    ));
+                }
+                let source: String = document.source.iter().collect();
+                let options = FormatOptions {
+                    pretty: true,
+                    ..FormatOptions::default()
+                };
+                let edit = json::object(&[
+                    ("range", range((0, 0), document.end())),
+                    ("newText", json::string(&fmt::format(&source, &options))),
+                ]);
+                Ok(json::array(&[edit]))
+            }
+            other => Err((METHOD_NOT_FOUND, format!("unsupported method '{}'", other))),
+        }
+    }
+}
+
+pub fn serve(input: &mut impl BufRead, output: impl Write, extensions: Extensions) {
+    let mut server = Server::new(output, extensions);
+    while !server.is_finished() {
+        let Ok(Some(message)) = read_message(input) else {
+            break;
+        };
+        // Malformed messages can't be answered without an id
+        if let Ok(message) = json::parse(&message) {
+            server.handle(&message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: Option<u64>, method: &str, params: String) -> Value {
+        let mut fields = vec![("method", json::string(method)), ("params", params)];
+        if let Some(id) = id {
+            fields.push(("id", id.to_string()));
+        }
+        json::parse(&json::object(&fields)).unwrap()
+    }
+
+    fn at(line: usize, character: usize) -> String {
+        let position = json::object(&[
+            ("line", line.to_string()),
+            ("character", character.to_string()),
+        ]);
+        json::object(&[
+            (
+                "textDocument",
+                json::object(&[("uri", json::string("a.bf"))]),
+            ),
+            ("position", position),
+        ])
+    }
+
+    #[test]
+    fn test_server() {
+        let mut server = Server::new(Vec::new(), Extensions::default());
+        let open = json::object(&[(
+            "textDocument",
+            json::object(&[
+                ("uri", json::string("a.bf")),
+                ("text", json::string("clear [-]\n>+++]")),
+            ]),
+        )]);
+        server.handle(&message(None, "textDocument/didOpen", open));
+        server.handle(&message(
+            Some(1),
+            "textDocument/documentHighlight",
+            at(0, 9),
+        ));
+        server.handle(&message(Some(2), "textDocument/hover", at(1, 2)));
+        server.handle(&message(Some(3), "textDocument/formatting", at(0, 0)));
+
+        let mut output = &server.writer[..];
+        let mut replies = Vec::new();
+        while let Some(reply) = read_message(&mut output).unwrap() {
+            replies.push(json::parse(&reply).unwrap());
+        }
+        let diagnostics = replies[0]
+            .get("params")
+            .and_then(|params| params.get("diagnostics"));
+        let Some(Value::Array(diagnostics)) = diagnostics else {
+            panic!("no diagnostics in {:?}", replies[0]);
+        };
+        assert_eq!(diagnostics.len(), 1);
+        let start = diagnostics[0]
+            .get("range")
+            .and_then(|range| range.get("start"));
+        assert_eq!(start.and_then(|start| start.get("line")?.as_u64()), Some(1));
+        assert_eq!(
+            start.and_then(|start| start.get("character")?.as_u64()),
+            Some(4)
+        );
+
+        let Some(Value::Array(highlights)) = replies[1].get("result") else {
+            panic!("no highlights in {:?}", replies[1]);
+        };
+        let characters: Vec<u64> = highlights
+            .iter()
+            .filter_map(|highlight| {
+                highlight
+                    .get("range")?
+                    .get("start")?
+                    .get("character")?
+                    .as_u64()
+            })
+            .collect();
+        assert_eq!(characters, [8, 6]);
+
+        let hover = replies[2]
+            .get("result")
+            .and_then(|result| result.get("contents")?.get("value")?.as_str());
+        assert_eq!(hover, Some("```\ncell[p+1] += 3\np += 1\n```"));
+        assert!(replies[3].get("error").is_some());
+    }
+}
@@ -1,178 +1,2184 @@
-use std::io::{self, Read};
-type LoopLut = Vec<(usize, usize)>;
-const MEMORY_SIZE: usize = 256;
-type Memory = [u8; MEMORY_SIZE];
+mod dap;
+mod debug;
+mod framing;
+mod interrupt;
+mod lsp;
+mod render;
+mod repl;
+mod serve;
+mod tty;
+mod visualize;
+
+use brainfuck_rs::dialect::{self, Dialect};
+use brainfuck_rs::fmt::{self, FormatOptions};
+use brainfuck_rs::{
+    analysis, asm, batch, bench, check,
+    config::{self, Config},
+    coverage::Coverage,
+    debugger, diff,
+    examples::{self, Example},
+    execute, explain, fuzz, generate_jump_table, golden, graph,
+    heatmap::Heatmap,
+    ir, json,
+    limits::{Limits, Sandbox},
+    nested::{self, NestedStats},
+    optimize,
+    output::{Encoding, Output},
+    pipeline,
+    report::Report,
+    sanitize_input, split_bang_input, stats,
+    tape::{self, TapeBackend},
+    textgen, EofMode, Error, Extensions, InputEnd, InputRecorder, InputSource, Interpreter, IoMode,
+    ProgramInput, EXIT_INTERRUPTED, EXIT_PARSE_ERROR, EXIT_RUNTIME_ERROR, MEMORY_SIZE,
+};
+use render::{ColorChoice, Render, Tone};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+/// Where a program comes from and how to read it; shared by every subcommand.
+#[derive(Debug, Default, PartialEq)]
+struct SourceOptions {
+    lang: Option<String>, // Front-end to use; inferred from the file extension if unset
+    path: Option<PathBuf>, // Source file; read from stdin if unset
+    example: Option<&'static Example>, // A built-in program instead of a file
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    extensions: Extensions,
+    bang_input: bool, // Treat everything after the first `!` as program input
+    source: SourceOptions,
+    visualize: Option<u32>, // Steps per second for the terminal visualizer (0 = unthrottled)
+    coverage: bool,         // Show which instructions ran on stderr afterwards
+    lcov: Option<PathBuf>,  // Write coverage as an lcov tracefile
+    profile: Option<PathBuf>, // Save each instruction's count for `graph --profile`
+    heatmap: Option<PathBuf>, // Write tape accesses as a PPM image (or SVG, by extension)
+    snapshot: Option<PathBuf>, // Where to save the state if the run is interrupted
+    stream: bool,           // Parse the source in chunks instead of loading it whole
+    jobs: Option<usize>,    // Threads to parse big sources with
+    optimization: u8,       // 1 runs the optimizer's output when nothing needs source positions
+    flush_every: Option<usize>, // Flush output after this many bytes, not just on input and exit
+    output_encoding: Encoding,
+    record_input: Option<PathBuf>, // Save every byte read by `,` here
+    replay: Option<PathBuf>,       // Read input from a recording instead of stdin
+    nested: Option<PathBuf>,       // Child program for a self-interpreter, fed in before its input
+    format: OutputFormat, // JSON reports the output with the outcome instead of printing it
+    strict: bool,         // Refuse to run programs `check` has warnings about
+    limits: Limits,       // Caps for untrusted programs; unlimited by default
+    input: InputSource,   // Where `,` reads from after any `!` input
+    input_end: InputEnd,  // What `,` gets once `input` runs out
+    raw_tty: bool,        // Read keypresses unechoed, without waiting for a line
+    output_file: Option<PathBuf>, // Save the output here, byte for byte, instead of printing it
+    tee: bool,            // Print it as well as saving it
+    color: ColorChoice,   // For diagnostics and runtime errors
+    tape_file: Option<PathBuf>, // Start on the tape saved here, and save it back
+}
+
+const DEFAULT_VISUALIZE_SPEED: u32 = 20;
 
 #[derive(Debug, PartialEq)]
-enum Error {
-    MismatchedBrackets(usize), // Contains the index of the problematic character
-                               // Add other errors here if needed
+enum Command {
+    Run(Box<Options>), // Boxed, being much bigger than the rest
+    Fmt(SourceOptions, Extensions, FormatOptions),
+    Optimize(OptimizeOptions),
+    Check(SourceOptions, Extensions, OutputFormat, bool, ColorChoice), // Strict
+    Serve(String, Limits),                                             // Address to listen on
+    Fuzz(FuzzOptions),
+    Test(PathBuf, u64), // Directory of golden files and the step limit per run
+    Bench(BenchOptions),
+    Diff(DiffOptions),
+    RunAll(RunAllOptions),
+    Graph(GraphOptions),
+    GenText(Option<String>), // Text to print; read from stdin if unset
+    Asm(Option<PathBuf>, Option<PathBuf>), // Source and output; stdin and stdout if unset
+    Explain(SourceOptions, Extensions, bool, ColorChoice), // Print what analysis infers instead
+    Pipe(PipeOptions),
+    Debug(SourceOptions, Extensions, usize), // Journal size
+    Dap(Extensions, usize),                  // And the journal size
+    Lsp(Extensions),
+    Repl(Extensions),
+    ConfigInit(Option<PathBuf>), // The default config path if unset
+    Examples,
+    Stats(SourceOptions, Extensions, OutputFormat),
 }
 
-fn generate_loop_lookup_table(source_code: &str) -> Result<LoopLut, Error> {
-    let mut loop_lut = LoopLut::new();
-    let mut bracket_stack = Vec::new();
-    for (index, character) in source_code.chars().enumerate() {
-        match character {
-            '[' => bracket_stack.push(index),
-            ']' => {
-                let index_of_opening_bracket = bracket_stack
-                    .last()
-                    .copied()
-                    .ok_or(Error::MismatchedBrackets(index))?;
-                bracket_stack.pop();
-                loop_lut.push((index_of_opening_bracket, index));
-            }
-            _ => {}
+#[derive(Debug, Default, PartialEq)]
+struct PipeOptions {
+    extensions: Extensions,
+    output_encoding: Encoding,
+    sources: Vec<SourceOptions>, // In pipeline order
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct OptimizeOptions {
+    source: SourceOptions,
+    extensions: Extensions,
+    output: Option<PathBuf>, // Written to stdout if unset
+    verbose: bool,           // Report what the passes removed on stderr
+}
+
+#[derive(Debug, PartialEq)]
+struct FuzzOptions {
+    seed: Option<u64>, // Derived from the clock (and printed) if unset
+    iterations: u64,
+    max_len: usize, // Commands per generated program
+    max_steps: u64, // Budget per run, so looping programs still finish
+}
+
+impl Default for FuzzOptions {
+    fn default() -> FuzzOptions {
+        FuzzOptions {
+            seed: None,
+            iterations: 10_000,
+            max_len: 64,
+            max_steps: 10_000,
         }
     }
-    if let Some(index) = bracket_stack.last() {
-        return Err(Error::MismatchedBrackets(*index));
+}
+
+#[derive(Debug, PartialEq)]
+struct BenchOptions {
+    source: SourceOptions,
+    extensions: Extensions,
+    runs: usize,
+    backend: bench::Backend,
+    input: Option<PathBuf>, // File fed to the program's `,`; end of input if unset
+    format: OutputFormat,
+}
+
+impl Default for BenchOptions {
+    fn default() -> BenchOptions {
+        BenchOptions {
+            source: SourceOptions::default(),
+            extensions: Extensions::default(),
+            runs: 10,
+            backend: bench::Backend::default(),
+            input: None,
+            format: OutputFormat::default(),
+        }
     }
-    Ok(loop_lut)
 }
 
-fn increment_memory_pointer(memory_pointer: usize) -> usize {
-    if memory_pointer < MEMORY_SIZE {
-        return memory_pointer + 1;
-    } else {
-        return 0;
+#[derive(Debug, PartialEq)]
+struct DiffOptions {
+    source: SourceOptions,
+    extensions: Extensions,
+    against: bench::Backend, // The one trusted to be right
+    backend: bench::Backend,
+    input: Option<PathBuf>, // File fed to the program's `,`; end of input if unset
+    max_steps: u64,         // Per backend
+    format: OutputFormat,
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions {
+            source: SourceOptions::default(),
+            extensions: Extensions::default(),
+            against: bench::Backend::Interpreter,
+            backend: bench::Backend::Optimizer,
+            input: None,
+            max_steps: u64::MAX,
+            format: OutputFormat::default(),
+        }
     }
 }
 
-fn decrement_memory_pointer(memory_pointer: usize) -> usize {
-    if memory_pointer > 0 {
-        return memory_pointer - 1;
-    } else {
-        return MEMORY_SIZE - 1;
-    }
-}
-
-fn run(source_code: &String) -> Result<(), Error> {
-    let loop_lut = generate_loop_lookup_table(source_code)?;
-    let mut memory: Memory = [0; MEMORY_SIZE];
-    let mut memory_pointer: usize = 0;
-    let mut source_pointer: usize = 0;
-
-    println!(""); // Add a newline for aesthetics
-    while source_pointer < source_code.len() {
-        let character = source_code.chars().nth(source_pointer).unwrap();
-        match character {
-            '>' => memory_pointer = increment_memory_pointer(memory_pointer),
-            '<' => memory_pointer = decrement_memory_pointer(memory_pointer),
-            '+' => memory[memory_pointer] += 1,
-            '-' => memory[memory_pointer] -= 1,
-            '.' => print!("{}", memory[memory_pointer] as char),
-            ',' => {
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input).unwrap();
-                memory[memory_pointer] = input.as_bytes()[0];
-            }
-            '[' => {
-                if memory[memory_pointer] == 0 {
-                    source_pointer = loop_lut
-                        .iter()
-                        .find(|(open_idx, _)| *open_idx == source_pointer)
-                        .map(|(_, close_idx)| *close_idx)
-                        .ok_or(Error::MismatchedBrackets(source_pointer))?;
-                }
+#[derive(Debug, PartialEq)]
+struct RunAllOptions {
+    dir: PathBuf,
+    jobs: usize, // Programs run at once
+    extensions: Extensions,
+    limits: Limits, // For each program on its own
+    format: OutputFormat,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct GraphOptions {
+    source: SourceOptions,
+    extensions: Extensions,
+    profile: Option<PathBuf>, // Counts saved by `run --profile`
+    output: Option<PathBuf>,  // Printed if unset
+    format: OutputFormat,     // Text means DOT
+}
+
+// `--extensions` replaces the extensions `base` has enabled, but keeps its
+// I/O mode and EOF behavior, which have flags (and config keys) of their own
+fn parse_extensions(list: &str, base: Extensions) -> Result<Extensions, String> {
+    let mut extensions = base.io_only();
+    for name in list.split(',') {
+        if !extensions.enable(name.trim()) {
+            return Err(format!("unknown extension '{}'", name.trim()));
+        }
+    }
+    Ok(extensions)
+}
+
+type ArgIter<'a> = std::slice::Iter<'a, String>;
+
+fn next_value<'a>(args: &mut ArgIter<'a>, flag: &str) -> Result<&'a String, String> {
+    args.next().ok_or(format!("{} expects a value", flag))
+}
+
+fn next_number<T: std::str::FromStr>(args: &mut ArgIter, flag: &str) -> Result<T, String> {
+    let value = next_value(args, flag)?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid number '{}' for {}", value, flag))
+}
+
+impl SourceOptions {
+    // Handles `--lang` and the positional source path, rejecting anything else
+    fn parse_arg(&mut self, arg: &str, args: &mut ArgIter) -> Result<(), String> {
+        match arg {
+            "--lang" => self.lang = Some(next_value(args, arg)?.clone()),
+            other if other.starts_with("--") => {
+                return Err(format!("unknown argument '{}'", other))
+            }
+            path if self.path.is_none() => self.path = Some(PathBuf::from(path)),
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+        Ok(())
+    }
+
+    fn dialect(&self) -> Result<&'static dyn Dialect, String> {
+        if let Some(lang) = &self.lang {
+            return dialect::by_name(lang).ok_or(format!("unknown language '{}'", lang));
+        }
+        let inferred = self.path.as_deref().and_then(dialect::from_path);
+        Ok(inferred.unwrap_or(&dialect::Brainfuck))
+    }
+
+    // How diagnostics and reports refer to the source
+    fn display_name(&self) -> String {
+        if let Some(example) = self.example {
+            return format!("<example {}>", example.name);
+        }
+        self.path
+            .as_ref()
+            .map_or("<stdin>".to_string(), |path| path.display().to_string())
+    }
+
+    // Reads the program and translates it into brainfuck
+    fn load(&self) -> Result<String, String> {
+        let dialect = self.dialect()?;
+        if let Some(example) = self.example {
+            return Ok(dialect.translate(example.source));
+        }
+        let source = read_source(self.path.as_deref())
+            .map_err(|error| format!("could not read source: {}", error))?;
+        Ok(dialect.translate(&source))
+    }
+}
+
+fn parse_run_args(args: &[String], config: &Config) -> Result<Options, String> {
+    let mut options = Options {
+        extensions: config.extensions,
+        output_encoding: config.output_encoding,
+        flush_every: config.flush_every,
+        optimization: config.optimization,
+        ..Options::default()
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                options.extensions =
+                    parse_extensions(next_value(&mut args, arg)?, options.extensions)?
+            }
+            "--bang-input" => options.bang_input = true,
+            "--io-mode" => {
+                let name = next_value(&mut args, arg)?;
+                options.extensions.io_mode =
+                    IoMode::by_name(name).ok_or(format!("unknown I/O mode '{}'", name))?;
+            }
+            "--eof" => {
+                let name = next_value(&mut args, arg)?;
+                options.extensions.eof =
+                    EofMode::by_name(name).ok_or(format!("unknown EOF mode '{}'", name))?;
+            }
+            "--visualize" => options.visualize = Some(DEFAULT_VISUALIZE_SPEED),
+            "--speed" => options.visualize = Some(next_number(&mut args, arg)?),
+            "--coverage" => options.coverage = true,
+            "--lcov" => options.lcov = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--profile" => options.profile = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--heatmap" => options.heatmap = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--snapshot" => options.snapshot = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--stream" => options.stream = true,
+            "--example" => {
+                let name = next_value(&mut args, arg)?;
+                options.source.example = Some(examples::by_name(name).ok_or(format!(
+                    "unknown example '{}' (see `brainfuck-rs examples`)",
+                    name
+                ))?);
             }
-            ']' => {
-                if memory[memory_pointer] != 0 {
-                    source_pointer = loop_lut
-                        .iter()
-                        .find(|(_, close_idx)| *close_idx == source_pointer)
-                        .map(|(open_idx, _)| *open_idx)
-                        .ok_or(Error::MismatchedBrackets(source_pointer))?;
+            "--jobs" => match next_number(&mut args, arg)? {
+                0 => return Err("--jobs expects at least 1".to_string()),
+                jobs => options.jobs = Some(jobs),
+            },
+            "--optimization" => match next_number(&mut args, arg)? {
+                level @ (0 | 1) => options.optimization = level,
+                _ => return Err("--optimization expects 0 or 1".to_string()),
+            },
+            "--flush-every" => match next_number(&mut args, arg)? {
+                0 => return Err("--flush-every expects a positive number".to_string()),
+                bytes => options.flush_every = Some(bytes),
+            },
+            "--unbuffered" => options.flush_every = Some(1),
+            "--max-steps" => options.limits.max_steps = Some(next_number(&mut args, arg)?),
+            "--max-tape-bytes" => {
+                options.limits.max_tape_bytes = Some(next_tape_bytes(&mut args, arg)?)
+            }
+            "--max-output-bytes" => {
+                options.limits.max_output_bytes = Some(next_number(&mut args, arg)?)
+            }
+            "--record-input" => {
+                options.record_input = Some(PathBuf::from(next_value(&mut args, arg)?))
+            }
+            "--replay" => options.replay = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--nested" => options.nested = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--input" => options.input = InputSource::parse(next_value(&mut args, arg)?)?,
+            "--input-file" => {
+                options.input = InputSource::File(PathBuf::from(next_value(&mut args, arg)?))
+            }
+            "--input-str" => {
+                options.input = InputSource::Bytes(next_value(&mut args, arg)?.clone().into_bytes())
+            }
+            "--input-hex" => options.input = InputSource::from_hex(next_value(&mut args, arg)?)?,
+            "--input-end" => {
+                options.input_end = match next_value(&mut args, arg)?.as_str() {
+                    "stdin" => InputEnd::Stdin,
+                    "eof" => InputEnd::Eof,
+                    other => return Err(format!("unknown input end '{}'", other)),
                 }
             }
-            _ => {}
+            "--format" => options.format = parse_format(next_value(&mut args, arg)?)?,
+            "--strict" => options.strict = true,
+            "--raw-tty" => options.raw_tty = true,
+            "--output-file" => {
+                options.output_file = Some(PathBuf::from(next_value(&mut args, arg)?))
+            }
+            "--tee" => options.tee = true,
+            "--color" => options.color = ColorChoice::parse(next_value(&mut args, arg)?)?,
+            "--tape-file" => options.tape_file = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--output-encoding" => {
+                let name = next_value(&mut args, arg)?;
+                options.output_encoding =
+                    Encoding::by_name(name).ok_or(format!("unknown output encoding '{}'", name))?;
+            }
+            other => options.source.parse_arg(other, &mut args)?,
         }
-        source_pointer += 1;
     }
-    println!(""); // Add a newline for aesthetics
-    return Ok(());
+    if options.tee && options.output_file.is_none() {
+        return Err("--tee needs --output-file".to_string());
+    }
+    if options.source.example.is_some() && options.source.path.is_some() {
+        return Err("--example can't be combined with a source file".to_string());
+    }
+    options.extensions = options.source.dialect()?.extensions(options.extensions);
+    Ok(options)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn test_generate_loop_lookup_table() {
-        let source_code = "[[]]";
-        let result = generate_loop_lookup_table(source_code).unwrap();
-        assert_eq!(result, vec![(1, 2), (0, 3)]);
+fn parse_fmt_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut source = SourceOptions::default();
+    let mut extensions = config.extensions;
+    let mut format = FormatOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                extensions = parse_extensions(next_value(&mut args, arg)?, extensions)?
+            }
+            "--wrap" => format.wrap = Some(next_number(&mut args, arg)?),
+            "--pretty" => format.pretty = true,
+            "--minify" => format.minify = true,
+            other => source.parse_arg(other, &mut args)?,
+        }
+    }
+    let extensions = source.dialect()?.extensions(extensions);
+    Ok(Command::Fmt(source, extensions, format))
+}
+
+fn parse_optimize_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut options = OptimizeOptions {
+        extensions: config.extensions,
+        ..OptimizeOptions::default()
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                options.extensions =
+                    parse_extensions(next_value(&mut args, arg)?, options.extensions)?
+            }
+            "-o" | "--output" => options.output = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--verbose" => options.verbose = true,
+            other => options.source.parse_arg(other, &mut args)?,
+        }
+    }
+    options.extensions = options.source.dialect()?.extensions(options.extensions);
+    Ok(Command::Optimize(options))
+}
+
+// `--max-tape-bytes` caps how many distinct cells a run touches, not how
+// much tape it gets, and no run can touch more cells than the ring has
+fn next_tape_bytes(args: &mut ArgIter, flag: &str) -> Result<usize, String> {
+    match next_number(args, flag)? {
+        cells if cells >= MEMORY_SIZE => Err(format!(
+            "{} can be at most {}: the tape only has {} cells, so a run can't touch more",
+            flag,
+            MEMORY_SIZE - 1,
+            MEMORY_SIZE
+        )),
+        cells => Ok(cells),
+    }
+}
+
+fn parse_format(format: &str) -> Result<OutputFormat, String> {
+    match format {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        other => Err(format!("unknown format '{}'", other)),
+    }
+}
+
+fn parse_check_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut source = SourceOptions::default();
+    let mut extensions = config.extensions;
+    let mut format = OutputFormat::default();
+    let mut strict = false;
+    let mut color = ColorChoice::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                extensions = parse_extensions(next_value(&mut args, arg)?, extensions)?
+            }
+            "--format" => format = parse_format(next_value(&mut args, arg)?)?,
+            "--strict" => strict = true,
+            "--color" => color = ColorChoice::parse(next_value(&mut args, arg)?)?,
+            other => source.parse_arg(other, &mut args)?,
+        }
+    }
+    let extensions = source.dialect()?.extensions(extensions);
+    Ok(Command::Check(source, extensions, format, strict, color))
+}
 
-        let source_code2 = "[[[]]]";
-        let result2 = generate_loop_lookup_table(source_code2).unwrap();
-        assert_eq!(result2, vec![(2, 3), (1, 4), (0, 5)]);
+fn parse_serve_args(args: &[String]) -> Result<Command, String> {
+    let mut host = "127.0.0.1".to_string();
+    let mut port: u16 = 8080;
+    let mut limits = serve::default_limits();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--host" => host = next_value(&mut args, arg)?.clone(),
+            "--port" => port = next_number(&mut args, arg)?,
+            "--max-steps" => limits.max_steps = Some(next_number(&mut args, arg)?),
+            "--max-tape-bytes" => limits.max_tape_bytes = Some(next_tape_bytes(&mut args, arg)?),
+            "--max-output" => limits.max_output_bytes = Some(next_number(&mut args, arg)?),
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+    Ok(Command::Serve(format!("{}:{}", host, port), limits))
+}
 
-        let source_code3 = "[]]";
-        let result3 = generate_loop_lookup_table(source_code3);
-        assert!(result3.is_err());
-        assert_eq!(result3.unwrap_err(), Error::MismatchedBrackets(2));
+fn parse_fuzz_args(args: &[String]) -> Result<Command, String> {
+    let mut options = FuzzOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => options.seed = Some(next_number(&mut args, arg)?),
+            "--iterations" => options.iterations = next_number(&mut args, arg)?,
+            "--max-len" => options.max_len = next_number(&mut args, arg)?,
+            "--max-steps" => options.max_steps = next_number(&mut args, arg)?,
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
     }
+    Ok(Command::Fuzz(options))
 }
 
-fn truncate_string(s: &String, a: usize, b: usize) -> String {
-    let mut s = s.clone();
-    s.drain(..a).for_each(drop);
-    s.drain(b..).for_each(drop);
-    return s;
+fn parse_test_args(args: &[String]) -> Result<Command, String> {
+    let mut dir = None;
+    let mut max_steps = u64::MAX;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-steps" => max_steps = next_number(&mut args, arg)?,
+            other if other.starts_with("--") => {
+                return Err(format!("unknown argument '{}'", other))
+            }
+            path if dir.is_none() => dir = Some(PathBuf::from(path)),
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    let dir = dir.ok_or("test expects a directory")?;
+    Ok(Command::Test(dir, max_steps))
+}
+
+fn parse_bench_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut options = BenchOptions {
+        extensions: config.extensions,
+        ..BenchOptions::default()
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                options.extensions =
+                    parse_extensions(next_value(&mut args, arg)?, options.extensions)?
+            }
+            "--runs" => options.runs = next_number(&mut args, arg)?,
+            "--backend" => options.backend = parse_backend(next_value(&mut args, arg)?)?,
+            "--input" => options.input = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--format" => options.format = parse_format(next_value(&mut args, arg)?)?,
+            other => options.source.parse_arg(other, &mut args)?,
+        }
+    }
+    options.extensions = options.source.dialect()?.extensions(options.extensions);
+    Ok(Command::Bench(options))
 }
 
-fn display_lut_error(error: Error, source_code: &String) {
+fn parse_backend(name: &str) -> Result<bench::Backend, String> {
+    bench::Backend::by_name(name).ok_or(format!("unknown backend '{}'", name))
+}
+
+fn parse_diff_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut options = DiffOptions {
+        extensions: config.extensions,
+        ..DiffOptions::default()
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                options.extensions =
+                    parse_extensions(next_value(&mut args, arg)?, options.extensions)?
+            }
+            "--against" => options.against = parse_backend(next_value(&mut args, arg)?)?,
+            "--backend" => options.backend = parse_backend(next_value(&mut args, arg)?)?,
+            "--input" => options.input = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--max-steps" => options.max_steps = next_number(&mut args, arg)?,
+            "--format" => options.format = parse_format(next_value(&mut args, arg)?)?,
+            other => options.source.parse_arg(other, &mut args)?,
+        }
+    }
+    options.extensions = options.source.dialect()?.extensions(options.extensions);
+    Ok(Command::Diff(options))
+}
+
+fn parse_run_all_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut dir = None;
+    let mut jobs = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut extensions = config.extensions;
+    let mut limits = Limits::default();
+    let mut format = OutputFormat::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--jobs" => match next_number(&mut args, arg)? {
+                0 => return Err("--jobs expects at least 1".to_string()),
+                number => jobs = number,
+            },
+            "--extensions" => {
+                extensions = parse_extensions(next_value(&mut args, arg)?, extensions)?
+            }
+            "--max-steps" => limits.max_steps = Some(next_number(&mut args, arg)?),
+            "--max-tape-bytes" => limits.max_tape_bytes = Some(next_tape_bytes(&mut args, arg)?),
+            "--max-output-bytes" => limits.max_output_bytes = Some(next_number(&mut args, arg)?),
+            "--format" => format = parse_format(next_value(&mut args, arg)?)?,
+            other if other.starts_with("--") => {
+                return Err(format!("unknown argument '{}'", other))
+            }
+            path if dir.is_none() => dir = Some(PathBuf::from(path)),
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    Ok(Command::RunAll(RunAllOptions {
+        dir: dir.ok_or("run-all expects a directory")?,
+        jobs,
+        extensions,
+        limits,
+        format,
+    }))
+}
+
+fn parse_gen_text_args(args: &[String]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::GenText(None)),
+        [text] if !text.starts_with("--") => Ok(Command::GenText(Some(text.clone()))),
+        [arg] => Err(format!("unknown argument '{}'", arg)),
+        [_, extra, ..] => Err(format!("unexpected argument '{}'", extra)),
+    }
+}
+
+fn parse_asm_args(args: &[String]) -> Result<Command, String> {
+    let (mut source, mut output) = (None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            other if other.starts_with("--") => {
+                return Err(format!("unknown argument '{}'", other))
+            }
+            path if source.is_none() => source = Some(PathBuf::from(path)),
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    Ok(Command::Asm(source, output))
+}
+
+fn parse_stats_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut source = SourceOptions::default();
+    let mut extensions = config.extensions;
+    let mut format = OutputFormat::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                extensions = parse_extensions(next_value(&mut args, arg)?, extensions)?
+            }
+            "--format" => format = parse_format(next_value(&mut args, arg)?)?,
+            other => source.parse_arg(other, &mut args)?,
+        }
+    }
+    let extensions = source.dialect()?.extensions(extensions);
+    Ok(Command::Stats(source, extensions, format))
+}
+
+fn parse_explain_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut source = SourceOptions::default();
+    let mut extensions = config.extensions;
+    let mut analysis = false;
+    let mut color = ColorChoice::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                extensions = parse_extensions(next_value(&mut args, arg)?, extensions)?
+            }
+            "--analysis" => analysis = true,
+            "--color" => color = ColorChoice::parse(next_value(&mut args, arg)?)?,
+            other => source.parse_arg(other, &mut args)?,
+        }
+    }
+    let extensions = source.dialect()?.extensions(extensions);
+    Ok(Command::Explain(source, extensions, analysis, color))
+}
+
+fn parse_graph_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut options = GraphOptions {
+        extensions: config.extensions,
+        ..GraphOptions::default()
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                options.extensions =
+                    parse_extensions(next_value(&mut args, arg)?, options.extensions)?
+            }
+            "--profile" => options.profile = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "-o" | "--output" => options.output = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            "--format" => options.format = parse_format(next_value(&mut args, arg)?)?,
+            other => options.source.parse_arg(other, &mut args)?,
+        }
+    }
+    options.extensions = options.source.dialect()?.extensions(options.extensions);
+    Ok(Command::Graph(options))
+}
+
+fn parse_pipe_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut options = PipeOptions {
+        extensions: config.extensions,
+        output_encoding: config.output_encoding,
+        ..PipeOptions::default()
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                options.extensions =
+                    parse_extensions(next_value(&mut args, arg)?, options.extensions)?
+            }
+            "--output-encoding" => {
+                let name = next_value(&mut args, arg)?;
+                options.output_encoding =
+                    Encoding::by_name(name).ok_or(format!("unknown output encoding '{}'", name))?;
+            }
+            other if other.starts_with("--") => {
+                return Err(format!("unknown argument '{}'", other))
+            }
+            path => options.sources.push(SourceOptions {
+                path: Some(PathBuf::from(path)),
+                ..SourceOptions::default()
+            }),
+        }
+    }
+    if options.sources.is_empty() {
+        return Err("pipe expects at least one program".to_string());
+    }
+    Ok(Command::Pipe(options))
+}
+
+fn parse_debug_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut source = SourceOptions::default();
+    let mut extensions = config.extensions;
+    let mut journal = config.journal;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                extensions = parse_extensions(next_value(&mut args, arg)?, extensions)?
+            }
+            "--journal" => journal = next_number(&mut args, arg)?,
+            other => source.parse_arg(other, &mut args)?,
+        }
+    }
+    let extensions = source.dialect()?.extensions(extensions);
+    Ok(Command::Debug(source, extensions, journal))
+}
+
+fn parse_dap_args(args: &[String], config: &Config) -> Result<Command, String> {
+    let mut extensions = config.extensions;
+    let mut journal = config.journal;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                extensions = parse_extensions(next_value(&mut args, arg)?, extensions)?
+            }
+            "--journal" => journal = next_number(&mut args, arg)?,
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+    Ok(Command::Dap(extensions, journal))
+}
+
+// For the LSP server and the REPL, which only take `--extensions`
+fn parse_protocol_args(args: &[String], config: &Config) -> Result<Extensions, String> {
+    let mut extensions = config.extensions;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extensions" => {
+                extensions = parse_extensions(next_value(&mut args, arg)?, extensions)?
+            }
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+    Ok(extensions)
+}
+
+fn parse_config_args(args: &[String]) -> Result<Command, String> {
+    match args {
+        [init] if init == "init" => Ok(Command::ConfigInit(None)),
+        [init, path] if init == "init" && !path.starts_with("--") => {
+            Ok(Command::ConfigInit(Some(PathBuf::from(path))))
+        }
+        _ => Err("usage: config init [PATH]".to_string()),
+    }
+}
+
+fn parse_args(args: &[String], config: &Config) -> Result<Command, String> {
+    match args.first().map(String::as_str) {
+        Some("run") => Ok(Command::Run(Box::new(parse_run_args(&args[1..], config)?))),
+        Some("fmt") => parse_fmt_args(&args[1..], config),
+        Some("optimize") => parse_optimize_args(&args[1..], config),
+        Some("check") => parse_check_args(&args[1..], config),
+        Some("serve") => parse_serve_args(&args[1..]),
+        Some("fuzz") => parse_fuzz_args(&args[1..]),
+        Some("test") => parse_test_args(&args[1..]),
+        Some("bench") => parse_bench_args(&args[1..], config),
+        Some("diff") => parse_diff_args(&args[1..], config),
+        Some("run-all") => parse_run_all_args(&args[1..], config),
+        Some("gen-text") => parse_gen_text_args(&args[1..]),
+        Some("asm") => parse_asm_args(&args[1..]),
+        Some("explain") => parse_explain_args(&args[1..], config),
+        Some("stats") => parse_stats_args(&args[1..], config),
+        Some("graph") => parse_graph_args(&args[1..], config),
+        Some("pipe") => parse_pipe_args(&args[1..], config),
+        Some("debug") => parse_debug_args(&args[1..], config),
+        Some("dap") => parse_dap_args(&args[1..], config),
+        Some("lsp") => Ok(Command::Lsp(parse_protocol_args(&args[1..], config)?)),
+        Some("repl") => Ok(Command::Repl(parse_protocol_args(&args[1..], config)?)),
+        Some("config") => parse_config_args(&args[1..]),
+        Some("examples") if args.len() == 1 => Ok(Command::Examples),
+        Some("examples") => Err("usage: examples".to_string()),
+        _ => Ok(Command::Run(Box::new(parse_run_args(args, config)?))),
+    }
+}
+
+fn read_source(path: Option<&Path>) -> io::Result<String> {
+    match path {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut buffer = Vec::new();
+            io::stdin().read_to_end(&mut buffer)?;
+            String::from_utf8(buffer)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+        }
+    }
+}
+
+// Explains the error and exits with its status. `source_code` is sanitized,
+// so it's all one line.
+fn display_lut_error(error: Error, source_code: &str, color: ColorChoice) -> ! {
     println!("\n\nSorry! Your Brainfuck program experienced a runtime error!");
     match error {
         Error::MismatchedBrackets(index) => {
-            let start_index = std::cmp::max(0, index as i32 - 10) as usize;
-            let end_index = std::cmp::min(source_code.len() - 1, index + 10);
+            let (code, caret) =
+                color
+                    .render(&io::stdout())
+                    .snippet(source_code, 1, index + 1, Tone::Error);
+            println!("{}", code);
+            println!("{}", caret);
+            println!("{}", mismatched_bracket_message(source_code, index));
+        }
+        Error::Io(kind) => println!("Writing the program's output failed: {}", kind),
+        Error::OutputFailed => println!("Writing the program's output failed"),
+        Error::Interrupted => println!("The program was interrupted"),
+        Error::UndefinedProcedure(procedure) => {
+            println!(
+                "The program called procedure {}, which isn't defined",
+                procedure
+            )
+        }
+        Error::CallStackOverflow => println!(
+            "The program nested more than {} procedure calls",
+            brainfuck_rs::interpreter::MAX_CALL_DEPTH
+        ),
+        Error::LimitExceeded(limit) => println!("The program was stopped: {}", limit),
+    }
+    std::process::exit(error.exit_code());
+}
 
-            let trimmed_code = truncate_string(&source_code, start_index, end_index);
+// What's wrong with the bracket at `index`, and the edit `check` would
+// suggest. The snippet shows the sanitized source as one line, so the
+// suggestion counts columns the same way
+fn mismatched_bracket_message(source_code: &str, index: usize) -> String {
+    let mut message = match source_code.chars().nth(index) {
+        Some('[' | '(') => format!("The opening bracket at index {} is never closed", index),
+        _ => format!(
+            "The closing bracket at index {} does not have a matching opening bracket",
+            index
+        ),
+    };
+    if let Some(repair) = check::repair(source_code) {
+        let positions: Vec<(usize, usize)> =
+            (1..=source_code.len()).map(|column| (1, column)).collect();
+        message += &format!("\nhelp: {}", repair.describe(&positions));
+    }
+    message
+}
 
-            let is_left_trimmed = start_index > 0;
-            let is_right_trimmed = end_index < source_code.len() - 1;
-            let caret_index = if is_left_trimmed {
-                10
-            } else {
-                index - start_index
-            };
-            let caret = if is_right_trimmed {
-                format!("{}^", " ".repeat(caret_index))
+fn exit_with(message: &str) -> ! {
+    eprintln!("brainfuck-rs: {}", message);
+    std::process::exit(EXIT_RUNTIME_ERROR);
+}
+
+// Like `exit_with`, but with the status for `error`
+fn exit_for(message: &str, error: &Error) -> ! {
+    eprintln!("brainfuck-rs: {}", message);
+    std::process::exit(error.exit_code());
+}
+
+// What `check` prints for each diagnostic, also used by `run --strict`,
+// with the line of `source` it's about
+fn print_diagnostics(
+    output: &mut dyn Write,
+    render: &Render,
+    name: &str,
+    source: &str,
+    diagnostics: &[check::Diagnostic],
+    positions: &[(usize, usize)],
+) {
+    for diagnostic in diagnostics {
+        let (line, column) = positions[diagnostic.index];
+        let tone = match diagnostic.severity {
+            check::Severity::Error => Tone::Error,
+            check::Severity::Warning => Tone::Warning,
+        };
+        let _ = writeln!(
+            output,
+            "{}:{}:{}: {}: {}",
+            name,
+            line,
+            column,
+            render.paint(diagnostic.severity.name(), tone),
+            diagnostic.message
+        );
+        let _ = write!(output, "{}", render.excerpt(source, line, column, tone));
+        if let Some(repair) = diagnostic.repair {
+            let _ = writeln!(output, "  help: {}", repair.describe(positions));
+        }
+    }
+}
+
+// Whether `check` fails: on any error, or on anything at all with `--strict`
+fn rejects(diagnostics: &[check::Diagnostic], strict: bool) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| strict || diagnostic.severity == check::Severity::Error)
+}
+
+const STREAM_CHUNK_BYTES: usize = 1 << 20;
+
+// What `run` can't do on folded IR, which doesn't map back onto the source
+fn needs_source_positions(options: &Options) -> Option<&'static str> {
+    let unsupported = [
+        (options.bang_input, "--bang-input"),
+        (options.visualize.is_some(), "--visualize"),
+        (
+            options.coverage || options.lcov.is_some() || options.profile.is_some(),
+            "coverage",
+        ),
+        (options.heatmap.is_some(), "--heatmap"),
+        (options.snapshot.is_some(), "--snapshot"),
+        (options.nested.is_some(), "--nested"),
+        (options.format == OutputFormat::Json, "--format json"),
+    ];
+    unsupported
+        .iter()
+        .find(|(used, _)| *used)
+        .map(|(_, name)| *name)
+}
+
+// `run --stream`: the source is never held as a string, only as folded IR,
+// so the tooling that maps back onto the source isn't available
+fn stream_command(options: Options) -> Result<(), String> {
+    let unsupported = needs_source_positions(&options)
+        .or(options.strict.then_some("--strict"))
+        .or(
+            (options.source.dialect()?.name() != dialect::Brainfuck.name())
+                .then_some("other languages"),
+        );
+    if let Some(name) = unsupported {
+        return Err(format!("{} can't be combined with --stream", name));
+    }
+
+    let mut reader: Box<dyn Read> = match (&options.source.path, options.source.example) {
+        (Some(path), _) => Box::new(
+            std::fs::File::open(path)
+                .map_err(|error| format!("could not read source: {}", error))?,
+        ),
+        (None, Some(example)) => Box::new(example.source.as_bytes()),
+        (None, None) => Box::new(io::stdin()),
+    };
+    let program = match ir::parse_stream(&mut reader, options.extensions, STREAM_CHUNK_BYTES) {
+        Ok(program) => program,
+        Err(Error::Io(kind)) => return Err(format!("could not read source: {}", kind)),
+        Err(error) => exit_for(&error.to_string(), &error),
+    };
+    let interpreter = Interpreter::from_program(program, options.extensions)
+        .map_err(|error| error.to_string())?;
+    run_folded(&options, interpreter)
+}
+
+// `--tape-file`: loads the saved tape into the interpreter, if there is one
+fn open_tape(
+    options: &Options,
+    interpreter: Option<&mut Interpreter>,
+) -> Result<Option<Box<dyn TapeBackend>>, String> {
+    let Some(path) = &options.tape_file else {
+        return Ok(None);
+    };
+    let mut tape = tape::open(path)
+        .map_err(|error| format!("could not open {}: {}", path.display(), error))?;
+    if let Some(interpreter) = interpreter {
+        interpreter.set_first_tape(tape.cells());
+    }
+    Ok(Some(tape))
+}
+
+// Saves the tape a run left, however it ended
+fn save_tape(
+    options: &Options,
+    tape: Option<Box<dyn TapeBackend>>,
+    interpreter: &Interpreter,
+) -> Result<(), String> {
+    let (Some(mut tape), Some(path)) = (tape, &options.tape_file) else {
+        return Ok(());
+    };
+    *tape.cells() = *interpreter.first_tape();
+    tape.sync()
+        .map_err(|error| format!("could not save the tape to {}: {}", path.display(), error))
+}
+
+// Runs a program that's been folded with `--stream`, `--jobs` or
+// `--optimization 1`
+fn run_folded(options: &Options, mut interpreter: Interpreter) -> Result<(), String> {
+    let instructions = interpreter.program_len();
+    let tape = open_tape(options, Some(&mut interpreter))?;
+    let mut program_input = open_input(options, &[])?;
+    let mut recorder = InputRecorder::default();
+    let mut interrupt = interrupt::Interrupt::install(false);
+    let mut sandbox = Sandbox::new(options.limits);
+    let mut stdout = open_output(options, false)?;
+    aesthetic_newline(options);
+    let result = execute(
+        &mut interpreter,
+        &mut program_input,
+        &mut stdout,
+        &mut ((&mut recorder, &mut sandbox), &mut interrupt),
+    )
+    .and_then(|()| Ok(stdout.finish()?));
+    aesthetic_newline(options);
+    save_recording(options, &recorder)?;
+    save_tape(options, tape, &interpreter)?;
+    match result {
+        Ok(()) => Ok(()),
+        Err(Error::Interrupted) => {
+            let _ = stdout.flush();
+            eprintln!(
+                "interrupted after {} folded instructions at folded instruction {} of {}, pointer {}",
+                interrupt.instructions,
+                interrupt.source_pointer + 1,
+                instructions,
+                interrupt.memory_pointer
+            );
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        Err(error) => exit_for(&error.to_string(), &error),
+    }
+}
+
+// Where `run` sends the program's output: straight to stdout, kept for the
+// JSON report, or nowhere when it's only saved to a file
+enum Sink {
+    Stdout(io::Stdout),
+    Capture(Vec<u8>),
+    Discard,
+}
+
+impl Write for Sink {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Stdout(stdout) => stdout.write(bytes),
+            Sink::Capture(captured) => captured.write(bytes),
+            Sink::Discard => Ok(bytes.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::Capture(_) | Sink::Discard => Ok(()),
+        }
+    }
+}
+
+// The program's output, also saved to `--output-file` if there is one
+fn open_output(options: &Options, json: bool) -> Result<Output<Sink>, String> {
+    let sink = if json {
+        Sink::Capture(Vec::new())
+    } else if options.output_file.is_some() && !options.tee {
+        Sink::Discard
+    } else {
+        Sink::Stdout(io::stdout())
+    };
+    let output = Output::new(sink, options.flush_every, options.output_encoding);
+    match &options.output_file {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .map_err(|error| format!("could not write {}: {}", path.display(), error))?;
+            Ok(output.with_copy(Box::new(file)))
+        }
+        None => Ok(output),
+    }
+}
+
+// Blank lines around the program's output, left out when raw output may be
+// binary and when the output isn't printed at all
+fn aesthetic_newline(options: &Options) {
+    let printed = options.output_file.is_none() || options.tee;
+    if options.output_encoding != Encoding::Raw && printed {
+        println!();
+    }
+}
+
+// Input for `,`: a recording when replaying, otherwise `pending` then the
+// input source (stdin unless chosen otherwise). A read left waiting on the
+// terminal gives up when Ctrl-C is pressed.
+fn open_input(options: &Options, pending: &[u8]) -> Result<ProgramInput, String> {
+    let input = if options.input != InputSource::Stdin {
+        if options.replay.is_some() {
+            return Err("--input can't be combined with --replay".to_string());
+        }
+        options
+            .input
+            .open(pending, options.input_end)
+            .map_err(|error| format!("could not read input: {}", error))?
+    } else {
+        match &options.replay {
+            Some(path) => {
+                let recording = std::fs::File::open(path)
+                    .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+                ProgramInput::from_reader(Box::new(recording))
+            }
+            // The child's input is passed through byte for byte, not by lines,
+            // as are keypresses in raw mode
+            None if options.nested.is_some() || options.raw_tty => {
+                ProgramInput::new(pending).with_reader(Box::new(io::stdin()))
+            }
+            None => ProgramInput::new(pending),
+        }
+    };
+    Ok(input.stop_on(interrupt::flag()))
+}
+
+// The program a self-interpreter is asked to run, as plain brainfuck
+fn load_child(path: &Path) -> Result<String, String> {
+    let source = SourceOptions {
+        path: Some(path.to_path_buf()),
+        ..SourceOptions::default()
+    };
+    Ok(sanitize_input(&source.load()?, Extensions::default()))
+}
+
+fn report_nested(child: &str, stats: &NestedStats) {
+    if !stats.loaded() {
+        eprintln!("nested: the interpreter stopped before reading the whole child program");
+        return;
+    }
+    eprintln!(
+        "nested: read the {}-command child in {} instructions, then ran it in {}",
+        child.len(),
+        stats.loading_instructions,
+        stats.running_instructions
+    );
+    // The host can't run the child in fewer instructions than it takes alone
+    match nested::direct_instructions(child, &stats.child_input, stats.running_instructions) {
+        Some(direct) => eprintln!(
+            "nested: the child alone takes {} instructions ({:.1}x overhead)",
+            direct,
+            stats.running_instructions as f64 / direct.max(1) as f64
+        ),
+        None => eprintln!("nested: the child doesn't halt on its own with the same input"),
+    }
+}
+
+fn save_recording(options: &Options, recorder: &InputRecorder) -> Result<(), String> {
+    match &options.record_input {
+        Some(path) => std::fs::write(path, &recorder.bytes)
+            .map_err(|error| format!("could not write {}: {}", path.display(), error)),
+        None => Ok(()),
+    }
+}
+
+fn run_command(mut options: Options) -> Result<(), String> {
+    if options.raw_tty {
+        tty::enable()
+            .map_err(|error| format!("--raw-tty needs stdin to be a terminal: {}", error))?;
+    }
+    if options.stream {
+        return stream_command(options);
+    }
+    let json = options.format == OutputFormat::Json;
+    if json && options.visualize.is_some() {
+        return Err("--format json can't be combined with --visualize".to_string());
+    }
+    if json {
+        // Reported byte for byte, as text or base64
+        options.output_encoding = Encoding::Raw;
+    }
+    if options.bang_input && options.source.dialect()?.name() != dialect::Brainfuck.name() {
+        return Err("--bang-input is only supported for brainfuck sources".to_string());
+    }
+    if options.bang_input && options.replay.is_some() {
+        return Err("--bang-input can't be combined with --replay".to_string());
+    }
+    if options.bang_input && options.nested.is_some() {
+        return Err("--bang-input can't be combined with --nested".to_string());
+    }
+    let wants_coverage = options.coverage || options.lcov.is_some() || options.profile.is_some();
+    if wants_coverage && options.visualize.is_some() {
+        return Err("coverage can't be recorded while visualizing".to_string());
+    }
+    if options.heatmap.is_some() && options.visualize.is_some() {
+        return Err("a heatmap can't be recorded while visualizing".to_string());
+    }
+    if options.output_file.is_some() && options.visualize.is_some() {
+        return Err("--output-file can't be combined with --visualize".to_string());
+    }
+
+    let buffer = options.source.load()?;
+    let (buffer, program_input) = if options.bang_input {
+        split_bang_input(&buffer)
+    } else {
+        (buffer.as_str(), &[][..])
+    };
+    let raw_source = buffer;
+    let buffer = sanitize_input(buffer, options.extensions);
+    if options.strict {
+        let diagnostics = check::check(&buffer);
+        if !diagnostics.is_empty() {
+            let positions = check::command_positions(raw_source, options.extensions);
+            if json {
+                let mut report = Report::new("run");
+                report.fail(EXIT_PARSE_ERROR, Some("rejected by --strict".to_string()));
+                report.field("diagnostics", check::to_json(&diagnostics, &positions));
+                println!("{}", report.to_json());
             } else {
-                format!("{}^", " ".repeat(caret_index))
-            };
+                let name = options.source.display_name();
+                let render = options.color.render(&io::stderr());
+                print_diagnostics(
+                    &mut io::stderr(),
+                    &render,
+                    &name,
+                    raw_source,
+                    &diagnostics,
+                    &positions,
+                );
+            }
+            std::process::exit(EXIT_PARSE_ERROR);
+        }
+    }
+    if let Some(max) = options.limits.max_tape_bytes {
+        // The tape wraps, so no program can touch more of it than it has
+        let bound = analysis::tape_bound(&ir::parse(&buffer));
+        let cells = bound.span().min(MEMORY_SIZE);
+        if bound.bounded && cells > max {
+            eprintln!(
+                "warning: {} can touch up to {} cells, more than --max-tape-bytes {}",
+                options.source.display_name(),
+                cells,
+                max
+            );
+        }
+    }
+    // Parsing in parallel is only worth it for big programs, and folding or
+    // optimizing only when nothing needs to know which command is which.
+    // That includes --max-steps, which counts commands: a folded run of them
+    // is one step, so the limit would be hit at a different point
+    let jobs = options.jobs.unwrap_or(1);
+    let parallel = jobs > 1 && buffer.len() >= ir::PARALLEL_MIN_BYTES;
+    if (parallel || options.optimization > 0)
+        && needs_source_positions(&options).is_none()
+        && options.limits.max_steps.is_none()
+    {
+        // Folded positions don't say where in the source the problem is, and
+        // the optimizer needs balanced brackets anyway
+        if let Err(error) = generate_jump_table(&buffer) {
+            display_lut_error(error, &buffer, options.color);
+        }
+        let program = match parallel {
+            true => ir::parse_parallel(&buffer, jobs),
+            false => ir::parse(&buffer),
+        };
+        let program = match options.optimization {
+            0 => program,
+            _ => optimize::optimize(&program).0,
+        };
+        let interpreter = Interpreter::from_program(program, options.extensions)
+            .map_err(|error| error.to_string())?;
+        return run_folded(&options, interpreter);
+    }
+    let child = options.nested.as_deref().map(load_child).transpose()?;
+    let program_input = match &child {
+        Some(child) => nested::nested_input(child),
+        None => program_input.to_vec(),
+    };
+    let mut program_input = open_input(&options, &program_input)?;
+    let mut nested_stats = NestedStats::new(child.as_deref().unwrap_or(""));
+    let mut recorder = InputRecorder::default();
+    let mut coverage = Coverage::new(buffer.len());
+    let mut heatmap = Heatmap::new();
+    let mut sandbox = Sandbox::new(options.limits);
+    let mut interrupt = interrupt::Interrupt::install(options.snapshot.is_some());
+    let mut stdout = open_output(&options, json)?;
+    // Set up before the run so there's an interpreter to take the tape from
+    // afterwards
+    let (mut interpreter, mut result) = match Interpreter::new(&buffer, options.extensions) {
+        Ok(interpreter) => (Some(interpreter), Ok(())),
+        Err(error) => (None, Err(error)),
+    };
+    let tape = open_tape(&options, interpreter.as_mut())?;
 
-            println!("{}", trimmed_code);
-            println!("{}", caret);
+    match options.visualize {
+        Some(speed) => {
+            let mut visualizer = visualize::Visualizer::new(&buffer, speed);
+            if let Some(interpreter) = &mut interpreter {
+                result = execute(
+                    interpreter,
+                    &mut program_input,
+                    &mut io::sink(),
+                    &mut (
+                        (&mut visualizer, (&mut recorder, &mut nested_stats)),
+                        (&mut interrupt, &mut sandbox),
+                    ),
+                );
+            }
+            visualizer.finish();
+        }
+        None => {
+            aesthetic_newline(&options);
+            if let Some(interpreter) = &mut interpreter {
+                result = execute(
+                    interpreter,
+                    &mut program_input,
+                    &mut stdout,
+                    &mut (
+                        (
+                            (&mut coverage, &mut heatmap),
+                            (&mut recorder, &mut nested_stats),
+                        ),
+                        (&mut interrupt, &mut sandbox),
+                    ),
+                )
+                .and_then(|()| Ok(stdout.finish()?));
+            }
+            aesthetic_newline(&options);
+        }
+    }
+    save_recording(&options, &recorder)?;
+    if let Some(interpreter) = &interpreter {
+        save_tape(&options, tape, interpreter)?;
+    }
+    if result == Err(Error::Interrupted) {
+        let _ = stdout.flush();
+        let positions = check::command_positions(raw_source, options.extensions);
+        let (line, column) = positions[interrupt.source_pointer];
+        eprintln!(
+            "interrupted after {} instructions at {}:{}:{} (instruction {} of {}), pointer {}",
+            interrupt.instructions,
+            options.source.display_name(),
+            line,
+            column,
+            interrupt.source_pointer + 1,
+            buffer.len(),
+            interrupt.memory_pointer
+        );
+        if let Some(path) = &options.snapshot {
+            std::fs::write(path, interrupt.snapshot(&buffer) + "\n")
+                .map_err(|error| format!("could not write {}: {}", path.display(), error))?;
+            eprintln!("saved a snapshot to {}", path.display());
+        }
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+    if json {
+        let mut report = Report::new("run");
+        if let Err(error) = &result {
+            report.fail(error.exit_code(), Some(error.to_string()));
+        }
+        if let Sink::Capture(output) = stdout.get_ref() {
+            report.output(output);
+        }
+        let stats = json::object(&[
+            ("instructions", interrupt.instructions.to_string()),
+            ("memory_pointer", interrupt.memory_pointer.to_string()),
+            ("input_bytes", recorder.bytes.len().to_string()),
+        ]);
+        report.field("stats", stats);
+        println!("{}", report.to_json());
+        if report.exit_code() != 0 {
+            std::process::exit(report.exit_code());
+        }
+    } else if let Err(error) = result {
+        let _ = stdout.flush(); // Show what the program printed before it failed
+        display_lut_error(error, &buffer, options.color);
+    }
+
+    if let Some(child) = &child {
+        report_nested(child, &nested_stats);
+    }
+    if options.coverage {
+        eprint!("{}", coverage.render(raw_source, options.extensions));
+    }
+    if let Some(path) = &options.lcov {
+        let name = options.source.display_name();
+        std::fs::write(
+            path,
+            coverage.to_lcov(raw_source, options.extensions, &name),
+        )
+        .map_err(|error| format!("could not write {}: {}", path.display(), error))?;
+    }
+    if let Some(path) = &options.profile {
+        std::fs::write(path, coverage.to_profile())
+            .map_err(|error| format!("could not write {}: {}", path.display(), error))?;
+    }
+    if let Some(path) = &options.heatmap {
+        let image = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("svg") => heatmap.to_svg().into_bytes(),
+            _ => heatmap.to_ppm(),
+        };
+        std::fs::write(path, image)
+            .map_err(|error| format!("could not write {}: {}", path.display(), error))?;
+    }
+    Ok(())
+}
+
+fn fmt_command(
+    source: SourceOptions,
+    extensions: Extensions,
+    format: FormatOptions,
+) -> Result<(), String> {
+    let buffer = sanitize_input(&source.load()?, extensions);
+    print!("{}", fmt::format(&buffer, &format));
+    Ok(())
+}
+
+fn optimize_command(options: OptimizeOptions) -> Result<(), String> {
+    let buffer = sanitize_input(&options.source.load()?, options.extensions);
+    if let Err(error) = generate_jump_table(&buffer) {
+        display_lut_error(error, &buffer, ColorChoice::default());
+    }
+
+    let (program, stats) = optimize::optimize(&ir::parse(&buffer));
+    if options.verbose {
+        eprintln!(
+            "cancelled {} commands that undo each other",
+            stats.cancelled_commands
+        );
+        eprintln!(
+            "removed {} instructions in loops that can never run",
+            stats.dead_instructions
+        );
+        eprintln!(
+            "folded {} loops that always run the same number of times",
+            stats.folded_loops
+        );
+        eprintln!(
+            "removed {} pointer moves by addressing cells by offset",
+            stats.removed_moves
+        );
+    }
+    let optimized = ir::to_source(&program) + "\n";
+    match options.output {
+        Some(path) => std::fs::write(&path, optimized)
+            .map_err(|error| format!("could not write {}: {}", path.display(), error)),
+        None => {
+            print!("{}", optimized);
+            Ok(())
+        }
+    }
+}
+
+fn check_command(
+    source: SourceOptions,
+    extensions: Extensions,
+    format: OutputFormat,
+    strict: bool,
+    color: ColorChoice,
+) -> Result<(), String> {
+    let buffer = source.load()?;
+    let diagnostics = check::check(&sanitize_input(&buffer, extensions));
+    let positions = check::command_positions(&buffer, extensions);
+    let rejected = rejects(&diagnostics, strict);
+    match format {
+        OutputFormat::Text => print_diagnostics(
+            &mut io::stdout(),
+            &color.render(&io::stdout()),
+            &source.display_name(),
+            &buffer,
+            &diagnostics,
+            &positions,
+        ),
+        OutputFormat::Json => {
+            let mut report = Report::new("check");
+            report.field("diagnostics", check::to_json(&diagnostics, &positions));
+            if rejected {
+                report.fail(EXIT_PARSE_ERROR, None);
+            }
+            println!("{}", report.to_json());
+        }
+    }
+
+    if rejected {
+        std::process::exit(EXIT_PARSE_ERROR);
+    }
+    Ok(())
+}
+
+fn fuzz_command(options: FuzzOptions) -> Result<(), String> {
+    let seed = options.seed.unwrap_or_else(|| {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+        now.map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    });
+    eprintln!("fuzzing {} programs with seed {}", options.iterations, seed);
+    match fuzz::fuzz(seed, options.iterations, options.max_len, options.max_steps) {
+        Ok(()) => {
+            println!("no failures");
+            Ok(())
+        }
+        Err(failure) => {
+            println!("program: {}", failure.source);
+            println!("input: {:?}", failure.input);
+            println!("{}", failure.message);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn test_command(dir: &Path, max_steps: u64) -> Result<(), String> {
+    let read_error = |error: io::Error| format!("could not read {}: {}", dir.display(), error);
+    let cases = golden::find_cases(dir).map_err(read_error)?;
+    let mut failed = 0;
+    for case in &cases {
+        let failures = golden::run_case(case, max_steps).map_err(read_error)?;
+        if failures.is_empty() {
+            println!("PASS {}", case.name);
+            continue;
+        }
+        failed += 1;
+        println!("FAIL {}", case.name);
+        for failure in failures {
+            for line in failure.lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+    println!("\n{} passed, {} failed", cases.len() - failed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn bench_command(options: BenchOptions) -> Result<(), String> {
+    let buffer = sanitize_input(&options.source.load()?, options.extensions);
+    let input = match &options.input {
+        Some(path) => std::fs::read(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?,
+        None => Vec::new(),
+    };
+    let json = options.format == OutputFormat::Json;
+
+    let mut measurements = Vec::new();
+    if !json {
+        println!(
+            "{:>4} {:>12} {:>14} {:>14} {:>6}",
+            "run", "time (ms)", "instructions", "instr/s", "cells"
+        );
+    }
+    for run in 1..=options.runs {
+        let measurement = match bench::measure(&buffer, options.extensions, &input, options.backend)
+        {
+            Ok(measurement) => measurement,
+            Err(error) if json => {
+                let mut report = Report::new("bench");
+                report.fail(error.exit_code(), Some(error.to_string()));
+                println!("{}", report.to_json());
+                std::process::exit(error.exit_code());
+            }
+            Err(error) => {
+                display_lut_error(error, &buffer, ColorChoice::default());
+            }
+        };
+        if !json {
+            println!(
+                "{:>4} {:>12.3} {:>14} {:>14.3e} {:>6}",
+                run,
+                measurement.elapsed.as_secs_f64() * 1000.0,
+                measurement.instructions,
+                measurement.instructions_per_second(),
+                measurement.cells_touched
+            );
+        }
+        measurements.push(measurement);
+    }
+
+    let statistic = |value: fn(&bench::Measurement) -> f64| {
+        let samples: Vec<f64> = measurements.iter().map(value).collect();
+        bench::mean_and_stddev(&samples)
+    };
+    let (time, time_stddev) = statistic(|m| m.elapsed.as_secs_f64() * 1000.0);
+    let (rate, rate_stddev) = statistic(bench::Measurement::instructions_per_second);
+    let (instructions, _) = statistic(|m| m.instructions as f64);
+    let (cells, _) = statistic(|m| m.cells_touched as f64);
+    if json {
+        let runs: Vec<String> = measurements
+            .iter()
+            .map(|measurement| {
+                json::object(&[
+                    (
+                        "time_ms",
+                        json::number(measurement.elapsed.as_secs_f64() * 1000.0),
+                    ),
+                    ("instructions", measurement.instructions.to_string()),
+                    (
+                        "instructions_per_second",
+                        json::number(measurement.instructions_per_second()),
+                    ),
+                    ("cells_touched", measurement.cells_touched.to_string()),
+                ])
+            })
+            .collect();
+        let mean = json::object(&[
+            ("time_ms", json::number(time)),
+            ("time_ms_stddev", json::number(time_stddev)),
+            ("instructions", json::number(instructions)),
+            ("instructions_per_second", json::number(rate)),
+            ("instructions_per_second_stddev", json::number(rate_stddev)),
+            ("cells_touched", json::number(cells)),
+        ]);
+        let mut report = Report::new("bench");
+        report.field("runs", json::array(&runs));
+        report.field("mean", mean);
+        println!("{}", report.to_json());
+        return Ok(());
+    }
+    println!(
+        "\nmean: {:.3} ms ± {:.3}, {:.0} instructions, {:.3e} instr/s ± {:.3e}, {:.0} cells",
+        time, time_stddev, instructions, rate, rate_stddev, cells
+    );
+    Ok(())
+}
 
+fn run_all_command(options: RunAllOptions) -> Result<(), String> {
+    let programs = batch::find_programs(&options.dir)
+        .map_err(|error| format!("could not read {}: {}", options.dir.display(), error))?;
+    let outcomes = batch::run_all(&programs, options.extensions, options.limits, options.jobs);
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.error.is_some())
+        .count();
+    match options.format {
+        OutputFormat::Text => {
+            let width = outcomes
+                .iter()
+                .map(|outcome| outcome.name.chars().count())
+                .chain(["program".len()])
+                .max()
+                .unwrap_or(0);
             println!(
-                "The closing bracket at index {} does not have a matching opening bracket",
-                index,
+                "{:<width$} {:<6} {:>12} {:>10}  result",
+                "program", "status", "steps", "time (ms)"
             );
+            for outcome in &outcomes {
+                let (status, result) = match &outcome.error {
+                    Some(error) => ("FAIL", error.clone()),
+                    None => ("ok", format!("{} bytes of output", outcome.output.len())),
+                };
+                println!(
+                    "{:<width$} {:<6} {:>12} {:>10.2}  {}",
+                    outcome.name,
+                    status,
+                    outcome.steps,
+                    outcome.elapsed.as_secs_f64() * 1000.0,
+                    result
+                );
+            }
+            println!("\n{} ok, {} failed", outcomes.len() - failed, failed);
+        }
+        OutputFormat::Json => {
+            let mut report = Report::new("run-all");
+            let programs: Vec<String> = outcomes.iter().map(batch::Outcome::to_json).collect();
+            report.field("programs", json::array(&programs));
+            report.field("failed", failed.to_string());
+            if failed > 0 {
+                report.fail(1, None);
+            }
+            println!("{}", report.to_json());
+        }
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn diff_command(options: DiffOptions) -> Result<(), String> {
+    let raw_source = options.source.load()?;
+    let buffer = sanitize_input(&raw_source, options.extensions);
+    let input = match &options.input {
+        Some(path) => std::fs::read(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?,
+        None => Vec::new(),
+    };
+    let (against, backend) = (options.against, options.backend);
+    let verdict = match diff::diff(
+        &buffer,
+        options.extensions,
+        &input,
+        against,
+        backend,
+        options.max_steps,
+    ) {
+        Ok(verdict) => verdict,
+        Err(error) if options.format == OutputFormat::Json => {
+            let mut report = Report::new("diff");
+            report.fail(error.exit_code(), Some(error.to_string()));
+            println!("{}", report.to_json());
+            std::process::exit(error.exit_code());
+        }
+        Err(error) => display_lut_error(error, &buffer, ColorChoice::default()),
+    };
+    let diverged = matches!(verdict, diff::Verdict::Diverged(_));
+    if options.format == OutputFormat::Json {
+        let mut report = Report::new("diff");
+        report.field("against", json::string(against.name()));
+        report.field("backend", json::string(backend.name()));
+        report.field("result", verdict.to_json());
+        if diverged {
+            report.fail(1, None);
+        }
+        println!("{}", report.to_json());
+    } else {
+        // Only the interpreter runs the source command for command
+        let positions = check::command_positions(&raw_source, options.extensions);
+        let at = |backend: bench::Backend, instruction: usize| match backend {
+            bench::Backend::Interpreter => {
+                let (line, column) = positions[instruction];
+                format!("command {} ({}:{})", instruction, line, column)
+            }
+            bench::Backend::Optimizer => format!("instruction {} of its program", instruction),
+        };
+        let names = format!("{} and {}", against.name(), backend.name());
+        match verdict {
+            diff::Verdict::Same { events } => {
+                println!("{} agree: {} events and the same final tape", names, events)
+            }
+            diff::Verdict::OutOfSteps { events } => println!(
+                "{} agree on {} events before one runs out of steps",
+                names, events
+            ),
+            diff::Verdict::Diverged(diff::Divergence::Event {
+                number,
+                expected,
+                actual,
+            }) => {
+                println!("{} diverge after {} events:", names, number);
+                for (backend, event) in [(against, expected), (backend, actual)] {
+                    println!(
+                        "  {:<12} {} at {}",
+                        backend.name(),
+                        event.kind,
+                        at(backend, event.instruction)
+                    );
+                }
+            }
+            diff::Verdict::Diverged(diff::Divergence::Pointer { expected, actual }) => println!(
+                "{} halt with the pointer at cell {} and cell {}",
+                names, expected, actual
+            ),
+            diff::Verdict::Diverged(diff::Divergence::Cell {
+                cell,
+                expected,
+                actual,
+            }) => println!(
+                "{} halt with cell {} holding {} and {}",
+                names, cell, expected, actual
+            ),
+        }
+    }
+    if diverged {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn gen_text_command(text: Option<String>) -> Result<(), String> {
+    let text = match text {
+        Some(text) => text.into_bytes(),
+        None => {
+            let mut buffer = Vec::new();
+            io::stdin()
+                .read_to_end(&mut buffer)
+                .map_err(|error| format!("could not read text: {}", error))?;
+            buffer
+        }
+    };
+    println!("{}", textgen::generate(&text));
+    Ok(())
+}
+
+fn asm_command(source: Option<&Path>, output: Option<&Path>) -> Result<(), String> {
+    let buffer =
+        read_source(source).map_err(|error| format!("could not read source: {}", error))?;
+    let name = source.map_or("<stdin>".to_string(), |path| path.display().to_string());
+    let program = asm::assemble(&buffer).map_err(|error| format!("{}: {}", name, error))? + "\n";
+    match output {
+        Some(path) => std::fs::write(path, program)
+            .map_err(|error| format!("could not write {}: {}", path.display(), error)),
+        None => {
+            print!("{}", program);
+            Ok(())
+        }
+    }
+}
+
+fn explain_command(
+    source: SourceOptions,
+    extensions: Extensions,
+    analysis: bool,
+    color: ColorChoice,
+) -> Result<(), String> {
+    let raw_source = source.load()?;
+    let buffer = sanitize_input(&raw_source, extensions);
+    if let Err(error) = generate_jump_table(&buffer) {
+        display_lut_error(error, &buffer, color);
+    }
+    let program = ir::parse(&buffer);
+    if !analysis {
+        print!("{}", explain::explain(&program));
+        return Ok(());
+    }
+    // Like diagnostics, one line per fact at the command it's about
+    let positions = check::command_positions(&raw_source, extensions);
+    let render = color.render(&io::stdout());
+    for (index, fact) in analysis::analyze(&program) {
+        let (line, column) = positions[index];
+        println!("{}:{}:{}: {}", source.display_name(), line, column, fact);
+        print!("{}", render.excerpt(&raw_source, line, column, Tone::Note));
+    }
+    Ok(())
+}
+
+fn graph_command(options: GraphOptions) -> Result<(), String> {
+    let raw_source = options.source.load()?;
+    let buffer = sanitize_input(&raw_source, options.extensions);
+    if let Err(error) = generate_jump_table(&buffer) {
+        display_lut_error(error, &buffer, ColorChoice::default());
+    }
+    let mut graph = graph::graph(&ir::parse(&buffer));
+    if let Some(path) = &options.profile {
+        let read_error = |error: String| format!("could not read {}: {}", path.display(), error);
+        let profile =
+            std::fs::read_to_string(path).map_err(|error| read_error(error.to_string()))?;
+        let coverage = Coverage::from_profile(&profile).map_err(read_error)?;
+        graph = graph
+            .with_profile(coverage.counts)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+    }
+    let positions = check::command_positions(&raw_source, options.extensions);
+    let rendered = match options.format {
+        OutputFormat::Text => graph.to_dot(&buffer, &positions),
+        OutputFormat::Json => {
+            let mut report = Report::new("graph");
+            report.field("blocks", graph.to_json(&positions));
+            report.to_json() + "\n"
+        }
+    };
+    match &options.output {
+        Some(path) => std::fs::write(path, rendered)
+            .map_err(|error| format!("could not write {}: {}", path.display(), error)),
+        None => {
+            print!("{}", rendered);
+            Ok(())
         }
     }
 }
 
-fn sanitize_input(input: &String) -> String {
-    let mut sanitized_input = String::new();
-    for character in input.chars() {
-        match character {
-            '>' | '<' | '+' | '-' | '.' | ',' | '[' | ']' => sanitized_input.push(character),
-            _ => {}
+fn stats_command(
+    source: SourceOptions,
+    extensions: Extensions,
+    format: OutputFormat,
+) -> Result<(), String> {
+    let stats = stats::stats(&source.load()?, extensions);
+    match format {
+        OutputFormat::Text => print!("{}", stats),
+        OutputFormat::Json => {
+            let mut report = Report::new("stats");
+            report.field("stats", stats.to_json());
+            println!("{}", report.to_json());
         }
     }
-    return sanitized_input;
+    Ok(())
+}
+
+fn pipe_command(options: PipeOptions) -> Result<(), String> {
+    let mut sources = Vec::new();
+    for source in &options.sources {
+        sources.push(sanitize_input(&source.load()?, options.extensions));
+    }
+    let mut stdout = Output::new(io::stdout(), None, options.output_encoding);
+    pipeline::run_pipeline(
+        &sources,
+        options.extensions,
+        ProgramInput::new(&[]),
+        &mut stdout,
+        pipeline::CHANNEL_CAPACITY,
+    )
+    .unwrap_or_else(|error| {
+        let _ = stdout.flush();
+        let name = options.sources[error.stage].display_name();
+        exit_for(&format!("{}: {}", name, error.error), &error.error)
+    });
+    stdout.finish().map_err(|error| error.to_string())
+}
+
+fn debug_command(
+    source: SourceOptions,
+    extensions: Extensions,
+    journal: usize,
+) -> Result<(), String> {
+    let buffer = sanitize_input(&source.load()?, extensions);
+    let interpreter = match Interpreter::new(&buffer, extensions) {
+        Ok(interpreter) => interpreter,
+        Err(error) => {
+            display_lut_error(error, &buffer, ColorChoice::default());
+        }
+    };
+    debug::debug(&buffer, debugger::Debugger::new(interpreter, journal));
+    Ok(())
+}
+
+fn config_init_command(path: Option<PathBuf>) -> Result<(), String> {
+    let path = path
+        .or_else(config::default_path)
+        .ok_or("no config path; give one or set HOME")?;
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+    let write_error = |error: io::Error| format!("could not write {}: {}", path.display(), error);
+    if let Some(directory) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(directory).map_err(write_error)?;
+    }
+    std::fs::write(&path, config::TEMPLATE).map_err(write_error)?;
+    println!("wrote {}", path.display());
+    Ok(())
 }
 
 fn main() {
-    let mut buffer = Vec::new();
-    io::stdin().read_to_end(&mut buffer).unwrap();
-    let buffer = String::from_utf8(buffer).unwrap();
-    let buffer = sanitize_input(&buffer);
-
-    let res = run(&buffer);
-    if res.is_err() {
-        display_lut_error(res.unwrap_err(), &buffer);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = config::load().unwrap_or_else(|message| exit_with(&message));
+    let command = parse_args(&args, &config).unwrap_or_else(|message| exit_with(&message));
+    let result = match command {
+        Command::Run(options) => run_command(*options),
+        Command::Fmt(source, extensions, format) => fmt_command(source, extensions, format),
+        Command::Optimize(options) => optimize_command(options),
+        Command::Check(source, extensions, format, strict, color) => {
+            check_command(source, extensions, format, strict, color)
+        }
+        Command::Serve(address, limits) => serve::serve(&address, limits)
+            .map_err(|error| format!("could not serve on {}: {}", address, error)),
+        Command::Fuzz(options) => fuzz_command(options),
+        Command::Test(dir, max_steps) => test_command(&dir, max_steps),
+        Command::Bench(options) => bench_command(options),
+        Command::Diff(options) => diff_command(options),
+        Command::RunAll(options) => run_all_command(options),
+        Command::Graph(options) => graph_command(options),
+        Command::GenText(text) => gen_text_command(text),
+        Command::Asm(source, output) => asm_command(source.as_deref(), output.as_deref()),
+        Command::Explain(source, extensions, analysis, color) => {
+            explain_command(source, extensions, analysis, color)
+        }
+        Command::Stats(source, extensions, format) => stats_command(source, extensions, format),
+        Command::Pipe(options) => pipe_command(options),
+        Command::Debug(source, extensions, journal) => debug_command(source, extensions, journal),
+        Command::Dap(extensions, journal) => {
+            dap::serve(io::stdin(), io::stdout(), extensions, journal);
+            Ok(())
+        }
+        Command::Lsp(extensions) => {
+            lsp::serve(&mut io::stdin().lock(), io::stdout(), extensions);
+            Ok(())
+        }
+        Command::Repl(extensions) => {
+            repl::repl(extensions);
+            Ok(())
+        }
+        Command::ConfigInit(path) => config_init_command(path),
+        Command::Examples => {
+            for example in examples::EXAMPLES {
+                println!("{:<12}{}", example.name, example.description);
+            }
+            Ok(())
+        }
+    };
+    if let Err(message) = result {
+        exit_with(&message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_parse_extensions() {
+        assert!(
+            parse_extensions("debug", Extensions::default())
+                .unwrap()
+                .debug
+        );
+        assert!(parse_extensions("nope", Extensions::default()).is_err());
+
+        // Every subcommand keeps the configured I/O settings through it
+        let config = Config {
+            extensions: Extensions {
+                pbrain: true,
+                io_mode: IoMode::Numeric,
+                eof: EofMode::Zero,
+                ..Extensions::default()
+            },
+            ..Config::default()
+        };
+        let expected = Extensions {
+            debug: true,
+            ..config.extensions.io_only()
+        };
+        let parse = |subcommand: &str| {
+            let args: Vec<String> = [subcommand, "--extensions", "debug"]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect();
+            parse_args(&args, &config).unwrap()
+        };
+        assert_eq!(parse("dap"), Command::Dap(expected, config.journal));
+        assert_eq!(parse("lsp"), Command::Lsp(expected));
+        assert_eq!(parse("repl"), Command::Repl(expected));
+        let Command::Stats(_, extensions, _) = parse("stats") else {
+            panic!("expected the stats subcommand");
+        };
+        assert_eq!(extensions, expected);
+        let Command::Check(_, extensions, ..) = parse("check") else {
+            panic!("expected the check subcommand");
+        };
+        assert_eq!(extensions, expected);
+        let Command::Bench(options) = parse("bench") else {
+            panic!("expected the bench subcommand");
+        };
+        assert_eq!(options.extensions, expected);
+    }
+
+    #[test]
+    fn test_resolve_dialect() {
+        let args = ["hello.ook".to_string()];
+        let options = parse_run_args(&args, &Config::default()).unwrap();
+        assert_eq!(options.source.dialect().unwrap().name(), "ook");
+
+        let args = [
+            "--lang".to_string(),
+            "blub".to_string(),
+            "hello.ook".to_string(),
+        ];
+        let options = parse_run_args(&args, &Config::default()).unwrap();
+        assert_eq!(options.source.dialect().unwrap().name(), "blub");
+
+        let options = parse_run_args(&[], &Config::default()).unwrap();
+        assert_eq!(options.source.dialect().unwrap().name(), "brainfuck");
+    }
+
+    #[test]
+    fn test_parse_example() {
+        let args = ["--example".to_string(), "fib".to_string()];
+        let options = parse_run_args(&args, &Config::default()).unwrap();
+        assert_eq!(
+            options.source.example.map(|example| example.name),
+            Some("fib")
+        );
+        assert_eq!(options.source.display_name(), "<example fib>");
+
+        let args = ["--example".to_string(), "nope".to_string()];
+        assert!(parse_run_args(&args, &Config::default()).is_err());
+        let args = [
+            "--example".to_string(),
+            "fib".to_string(),
+            "fib.b".to_string(),
+        ];
+        assert!(parse_run_args(&args, &Config::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_optimization() {
+        let config = Config {
+            optimization: 1,
+            ..Config::default()
+        };
+        assert_eq!(parse_run_args(&[], &config).unwrap().optimization, 1);
+        let args = ["--optimization".to_string(), "0".to_string()];
+        assert_eq!(parse_run_args(&args, &config).unwrap().optimization, 0);
+        let args = ["--optimization".to_string(), "2".to_string()];
+        assert!(parse_run_args(&args, &config).is_err());
+    }
+
+    #[test]
+    fn test_parse_max_tape_bytes() {
+        let args = ["--max-tape-bytes".to_string(), "255".to_string()];
+        let options = parse_run_args(&args, &Config::default()).unwrap();
+        assert_eq!(options.limits.max_tape_bytes, Some(255));
+        let args = ["--max-tape-bytes".to_string(), "256".to_string()];
+        assert_eq!(
+            parse_run_args(&args, &Config::default()),
+            Err(
+                "--max-tape-bytes can be at most 255: the tape only has 256 cells, so a run can't touch more"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_diff_args() {
+        let args: Vec<String> = [
+            "diff",
+            "prog.b",
+            "--against",
+            "optimizer",
+            "--max-steps",
+            "9",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        let Command::Diff(options) = parse_args(&args, &Config::default()).unwrap() else {
+            panic!("expected the diff subcommand");
+        };
+        assert_eq!(options.against, bench::Backend::Optimizer);
+        assert_eq!(options.max_steps, 9);
+        let args = ["--backend".to_string(), "jit".to_string()];
+        assert_eq!(
+            parse_diff_args(&args, &Config::default()),
+            Err("unknown backend 'jit'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_fmt_args() {
+        let args: Vec<String> = ["fmt", "--pretty", "--wrap", "40", "prog.bf"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let Command::Fmt(source, _, format) = parse_args(&args, &Config::default()).unwrap() else {
+            panic!("expected the fmt subcommand");
+        };
+        assert_eq!(source.path, Some(PathBuf::from("prog.bf")));
+        assert!(format.pretty && !format.minify);
+        assert_eq!(format.wrap, Some(40));
+    }
+
+    #[test]
+    fn test_parse_dap_args() {
+        // The configured journal reaches the debug adapter unless overridden
+        let config = Config {
+            journal: 5,
+            ..Config::default()
+        };
+        let dap = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            parse_args(&args, &config)
+        };
+        assert_eq!(dap(&["dap"]), Ok(Command::Dap(Extensions::default(), 5)));
+        assert_eq!(
+            dap(&["dap", "--journal", "9"]),
+            Ok(Command::Dap(Extensions::default(), 9))
+        );
+    }
+
+    #[test]
+    fn test_mismatched_bracket_message() {
+        assert_eq!(
+            mismatched_bracket_message("+[-", 1),
+            "The opening bracket at index 1 is never closed\n\
+             help: did you mean to close the loop opened at line 1, col 2 at the end?"
+        );
+        assert!(mismatched_bracket_message("+]", 1).starts_with("The closing bracket"));
+    }
+
+    #[test]
+    fn test_rejects() {
+        let diagnostics = check::check("+[>+<]");
+        assert!(!rejects(&diagnostics, false));
+        assert!(rejects(&diagnostics, true));
+        assert!(rejects(&check::check("[[]"), false));
+        assert!(!rejects(&check::check("+."), true));
     }
 }
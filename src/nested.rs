@@ -0,0 +1,115 @@
+// `run --nested`: support for running brainfuck self-interpreters. The
+// child program is handed to the interpreter program as the start of its
+// input, followed by `!` and then the child's own input, and the run is
+// split into the part spent reading the child in and the part spent
+// running it, so interpreter overhead can be measured.
+
+use crate::{ExecutionObserver, Extensions, Interpreter, Memory, StepResult};
+
+/// The input a self-interpreter expects: the child program, then `!`.
+pub fn nested_input(child: &str) -> Vec<u8> {
+    let mut input = child.as_bytes().to_vec();
+    input.push(b'!');
+    input
+}
+
+/// Splits the host program's instructions at the point where it has read
+/// the whole child program, and keeps the input the child went on to read.
+#[derive(Debug)]
+pub struct NestedStats {
+    pub loading_instructions: u64,
+    pub running_instructions: u64,
+    pub child_input: Vec<u8>,
+    program_bytes: usize, // Length of `nested_input`
+    consumed: usize,
+}
+
+impl NestedStats {
+    pub fn new(child: &str) -> NestedStats {
+        NestedStats {
+            loading_instructions: 0,
+            running_instructions: 0,
+            child_input: Vec::new(),
+            program_bytes: child.len() + 1,
+            consumed: 0,
+        }
+    }
+
+    /// Whether the host read past the `!`, i.e. got as far as running the child.
+    pub fn loaded(&self) -> bool {
+        self.consumed >= self.program_bytes
+    }
+}
+
+impl ExecutionObserver for NestedStats {
+    fn on_instruction(&mut self, _: usize, _: &Memory, _: usize) {
+        if self.loaded() {
+            self.running_instructions += 1;
+        } else {
+            self.loading_instructions += 1;
+        }
+    }
+
+    fn on_input(&mut self, byte: Option<u8>) {
+        if let Some(byte) = byte {
+            if self.loaded() {
+                self.child_input.push(byte);
+            }
+            self.consumed += 1;
+        }
+    }
+}
+
+/// Instructions the child takes when run directly over `input`, or `None`
+/// if it doesn't halt within `max_steps` (or doesn't run at all).
+pub fn direct_instructions(child: &str, input: &[u8], max_steps: u64) -> Option<u64> {
+    let mut interpreter = Interpreter::new(child, Extensions::default()).ok()?;
+    let mut input = input.iter().copied();
+    let mut instructions = 0;
+    while instructions < max_steps {
+        match interpreter.step().ok()? {
+            StepResult::NeedsInput => {
+                interpreter.provide_input(input.next());
+                continue;
+            }
+            StepResult::Halted => return Some(instructions),
+            StepResult::Continue | StepResult::Output(_) => instructions += 1,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run, ProgramInput};
+
+    #[test]
+    fn test_nested_stats() {
+        // Stands in for a self-interpreter: skips the program up to the `!`,
+        // then echoes two bytes
+        let skip = "-".repeat(b'!' as usize);
+        let host = format!(",{}[,{}],.,.", skip, skip);
+        let child = ",.,.";
+        let mut input = nested_input(child);
+        input.extend(b"hi");
+        let mut stats = NestedStats::new(child);
+        let mut output = Vec::new();
+        let mut input = ProgramInput::new(&input);
+        run(
+            &host,
+            Extensions::default(),
+            &mut input,
+            &mut output,
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(output, b"hi");
+        assert!(stats.loaded());
+        assert_eq!(stats.child_input, b"hi");
+        // Everything after the `,` that read the `!`
+        assert_eq!(stats.running_instructions, 33 + 1 + 4);
+        assert_eq!(direct_instructions(child, b"hi", 100), Some(4));
+        assert_eq!(direct_instructions("+[]", b"", 100), None);
+    }
+}
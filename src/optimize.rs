@@ -0,0 +1,301 @@
+// Optimization passes over the IR. Every pass takes a program with balanced
+// brackets and returns an equivalent one.
+
+use crate::analysis::{self, Fact};
+use crate::ir::{matching_loop_end, Instruction, Program};
+use crate::MEMORY_SIZE;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// What the passes changed, reported by `--verbose`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    pub cancelled_commands: usize, // Commands undone by others, e.g. the `>` and `<` of `>+<`
+    pub dead_instructions: usize,  // Instructions inside loops that can never run
+    pub folded_loops: usize,       // Loops replaced by the adds they always amount to
+    pub removed_moves: usize,      // Moves made unnecessary by addressing cells by offset
+}
+
+pub fn optimize(program: &[Instruction]) -> (Program, Stats) {
+    let mut stats = Stats::default();
+    let (program, cancelled_commands) = cancel_opposites(program);
+    let (program, dead_instructions) = remove_dead_loops(&program);
+    let (program, more_dead_instructions, folded_loops) = fold_constants(&program);
+    let (program, removed_moves) = offset_adds(&program);
+    stats.cancelled_commands = cancelled_commands;
+    stats.dead_instructions = dead_instructions + more_dead_instructions;
+    stats.folded_loops = folded_loops;
+    stats.removed_moves = removed_moves;
+    (program, stats)
+}
+
+/// Rewrites every straight run of adds and moves as its net effect: one add
+/// per cell it changes, in the order they're first touched, and the moves
+/// between them. Anything that cancels out goes, even when the commands
+/// aren't next to each other (`>+<+>-<-` does nothing at all). The result is
+/// already folded, and folding first makes no difference. Returns the
+/// program and the number of commands saved.
+pub fn cancel_opposites(program: &[Instruction]) -> (Program, usize) {
+    let optimized = rewrite_runs(program, |optimized, adds, offset| {
+        let mut pointer = 0;
+        for (at, total) in adds {
+            push_folded(optimized, Instruction::Move(at - pointer));
+            push_folded(optimized, Instruction::Add(*total));
+            pointer = *at;
+        }
+        push_folded(optimized, Instruction::Move(offset - pointer));
+    });
+    let saved = run_commands(program) - run_commands(&optimized);
+    (optimized, saved)
+}
+
+/// Addresses the cells each straight run of adds and moves changes by their
+/// offset from the pointer, leaving at most one move per run: `>+>++<<`
+/// becomes two `AddAt`s and no moves at all. Returns the program and the
+/// number of moves removed.
+pub fn offset_adds(program: &[Instruction]) -> (Program, usize) {
+    let optimized = rewrite_runs(program, |optimized, adds, offset| {
+        for (at, total) in adds {
+            optimized.push(match at {
+                0 => Instruction::Add(*total),
+                at => Instruction::AddAt(*at, *total),
+            });
+        }
+        if offset != 0 {
+            optimized.push(Instruction::Move(offset));
+        }
+    });
+    let moves = |program: &[Instruction]| {
+        program
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::Move(_)))
+            .count()
+    };
+    let removed = moves(program) - moves(&optimized);
+    (optimized, removed)
+}
+
+// Copies the program, handing `emit` the net effect of each straight run of
+// adds and moves instead: what it adds at each offset it changes, in the
+// order they're first touched, and where it leaves the pointer
+fn rewrite_runs(
+    program: &[Instruction],
+    mut emit: impl FnMut(&mut Program, &[(isize, u8)], isize),
+) -> Program {
+    let mut rewritten = Program::new();
+    let mut run: Vec<(isize, u8)> = Vec::new(); // Total added at each offset touched
+    let mut offset: isize = 0;
+    for instruction in program.iter().copied().chain([Instruction::Output]) {
+        match instruction {
+            Instruction::Add(amount) => {
+                // Offsets a whole tape apart are the same cell
+                let cell = offset.rem_euclid(MEMORY_SIZE as isize);
+                match run
+                    .iter_mut()
+                    .find(|(at, _)| at.rem_euclid(MEMORY_SIZE as isize) == cell)
+                {
+                    Some((_, total)) => *total = total.wrapping_add(amount),
+                    None => run.push((offset, amount)),
+                }
+            }
+            Instruction::Move(amount) => offset += amount,
+            _ => {
+                run.retain(|(_, total)| *total != 0);
+                emit(&mut rewritten, &run, offset);
+                run.clear();
+                offset = 0;
+                rewritten.push(instruction);
+            }
+        }
+    }
+    rewritten.pop(); // The `Output` that ended the last run
+    rewritten
+}
+
+// How many commands the program's adds and moves take to write out
+fn run_commands(program: &[Instruction]) -> usize {
+    program
+        .iter()
+        .map(|instruction| match *instruction {
+            Instruction::Add(amount) => core::cmp::min(amount, 0u8.wrapping_sub(amount)) as usize,
+            Instruction::Move(amount) => amount.unsigned_abs(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Merges consecutive adds and moves into one instruction each, dropping any
+/// that cancel out completely (e.g. `+-` or `<>`).
+pub fn fold_runs(program: &[Instruction]) -> Program {
+    let mut folded = Program::new();
+    for instruction in program {
+        push_folded(&mut folded, *instruction);
+    }
+    folded
+}
+
+/// Appends one instruction the way `fold_runs` would.
+pub fn push_folded(folded: &mut Program, instruction: Instruction) {
+    match (folded.last_mut(), instruction) {
+        (Some(Instruction::Add(total)), Instruction::Add(amount)) => {
+            *total = total.wrapping_add(amount)
+        }
+        (Some(Instruction::Move(total)), Instruction::Move(amount)) => *total += amount,
+        (_, instruction) => folded.push(instruction),
+    }
+    if matches!(
+        folded.last(),
+        Some(Instruction::Add(0) | Instruction::Move(0))
+    ) {
+        folded.pop();
+    }
+}
+
+// What we statically know about the tape at a given instruction
+#[derive(Clone, Copy, PartialEq)]
+enum Knowledge {
+    AllZero,     // Nothing has been written yet
+    CurrentZero, // We've just left a loop, so the current cell is zero
+    Unknown,
+}
+
+/// Removes loops that can never run because the current cell is known to be
+/// zero: those before any cell has been changed (every cell starts at zero),
+/// and those directly after another loop closes. Returns the program and the
+/// number of instructions removed.
+pub fn remove_dead_loops(program: &[Instruction]) -> (Program, usize) {
+    let mut optimized = Program::new();
+    let mut knowledge = Knowledge::AllZero;
+    let mut removed = 0;
+    let mut index = 0;
+    while index < program.len() {
+        let instruction = program[index];
+        match instruction {
+            Instruction::LoopStart if knowledge != Knowledge::Unknown => {
+                let loop_end = matching_loop_end(program, index);
+                removed += loop_end + 1 - index;
+                index = loop_end + 1;
+                continue;
+            }
+            Instruction::LoopEnd => knowledge = Knowledge::CurrentZero,
+            Instruction::Move(_) if knowledge == Knowledge::AllZero => {}
+            Instruction::Output | Instruction::Debug => {}
+            _ => knowledge = Knowledge::Unknown,
+        }
+        optimized.push(instruction);
+        index += 1;
+    }
+    (optimized, removed)
+}
+
+/// Acts on what `analysis` can prove: removes loops whose cell is always
+/// zero when they're reached, and replaces counting loops with a known trip
+/// count by the adds they amount to, so `++[>+++<-]` becomes `>++++++<--`.
+/// Returns the program, the number of instructions removed with dead loops
+/// and the number of loops folded.
+pub fn fold_constants(program: &[Instruction]) -> (Program, usize, usize) {
+    let facts: BTreeMap<usize, Fact> = analysis::analyze(program).into_iter().collect();
+    let mut optimized = Program::new();
+    let (mut removed, mut folded) = (0, 0);
+    let mut index = 0;
+    while index < program.len() {
+        let instruction = program[index];
+        if instruction == Instruction::LoopStart {
+            let loop_end = matching_loop_end(program, index);
+            match (
+                facts.get(&index),
+                analysis::simple_loop(&program[index + 1..loop_end]),
+            ) {
+                (Some(Fact::NeverRuns), _) => {
+                    removed += loop_end + 1 - index;
+                    index = loop_end + 1;
+                    continue;
+                }
+                (Some(Fact::RunsTimes(times)), Some((step, adds))) => {
+                    let mut pointer = 0;
+                    for (at, amount) in adds {
+                        push_folded(&mut optimized, Instruction::Move(at - pointer));
+                        push_folded(
+                            &mut optimized,
+                            Instruction::Add(amount.wrapping_mul(*times as u8)),
+                        );
+                        pointer = at;
+                    }
+                    push_folded(&mut optimized, Instruction::Move(-pointer));
+                    push_folded(
+                        &mut optimized,
+                        Instruction::Add(step.wrapping_mul(*times as u8)),
+                    );
+                    folded += 1;
+                    index = loop_end + 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        push_folded(&mut optimized, instruction);
+        index += 1;
+    }
+    (optimized, removed, folded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{parse, to_source};
+
+    #[test]
+    fn test_optimize() {
+        assert_eq!(to_source(&fold_runs(&parse("+++-->><<<.+-"))), "+<.");
+        assert_eq!(to_source(&optimize(&parse(">[-[+]]<>.+[-]")).0), ">.");
+        assert_eq!(to_source(&optimize(&parse(">[-[+]]<>.,+[-]")).0), ">.,+[-]");
+        assert_eq!(to_source(&optimize(&parse(",[.,]")).0), ",[.,]");
+    }
+
+    #[test]
+    fn test_cancel_opposites() {
+        let (program, saved) = cancel_opposites(&parse(">+<+>-<-.+>-<[>+<+-<>]"));
+        assert_eq!(to_source(&program), ".+>-<[>+<]");
+        assert_eq!(saved, 12);
+
+        // Composes with folding either way round
+        let source = parse("++>>+<<-->[<+>-]<<+>");
+        let (cancelled, _) = cancel_opposites(&source);
+        assert_eq!(cancel_opposites(&fold_runs(&source)).0, cancelled);
+        assert_eq!(fold_runs(&cancelled), cancelled);
+        assert_eq!(to_source(&cancelled), ">>+<[<+>-]<<+>");
+    }
+
+    #[test]
+    fn test_offset_adds() {
+        let (program, removed) = offset_adds(&parse(">+>++<<[->>+<<]>"));
+        assert_eq!(
+            program,
+            [
+                Instruction::AddAt(1, 1),
+                Instruction::AddAt(2, 2),
+                Instruction::LoopStart,
+                Instruction::Add(255),
+                Instruction::AddAt(2, 1),
+                Instruction::LoopEnd,
+                Instruction::Move(1),
+            ]
+        );
+        assert_eq!(removed, 8);
+        assert_eq!(to_source(&program), ">+>++<<[->>+<<]>");
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let (program, removed, folded) = fold_constants(&parse("++[>+++<-],[-]>>[<+>-]<<."));
+        assert_eq!(to_source(&program), "++>++++++<--,[-].");
+        assert_eq!((removed, folded), (6, 1));
+    }
+
+    #[test]
+    fn test_remove_dead_loops() {
+        let (program, removed) = remove_dead_loops(&parse("+[-][>+<-].[+]>[-]"));
+        assert_eq!(to_source(&program), "+[-].>[-]");
+        assert_eq!(removed, 9);
+    }
+}
@@ -0,0 +1,185 @@
+// Program output for the CLI. Writes go through a `BufWriter` rather than
+// stdout's line buffering, flushed every `flush_every` bytes (if set),
+// whenever the program reads input and at the end of the run. Bytes are
+// turned into text according to the chosen `Encoding` on the way through;
+// a copy (for `--output-file`) gets them exactly as they were printed.
+
+use std::io::{self, BufWriter, Write};
+
+/// How the bytes a program prints are written out.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Latin1, // Each byte is printed as the character with that code point
+    Utf8, // Multi-byte sequences are decoded, invalid ones replaced with U+FFFD
+    Raw,  // Bytes are written verbatim, for binary output
+}
+
+impl Encoding {
+    pub fn by_name(name: &str) -> Option<Encoding> {
+        match name {
+            "latin1" => Some(Encoding::Latin1),
+            "utf8" => Some(Encoding::Utf8),
+            "raw" => Some(Encoding::Raw),
+            _ => None,
+        }
+    }
+}
+
+pub struct Output<W: Write> {
+    writer: BufWriter<W>,
+    flush_every: Option<usize>, // `Some(1)` is unbuffered
+    since_flush: usize,
+    encoding: Encoding,
+    pending: Vec<u8>, // Start of a UTF-8 sequence still waiting for its other bytes
+    copy: Option<BufWriter<Box<dyn Write>>>,
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(writer: W, flush_every: Option<usize>, encoding: Encoding) -> Output<W> {
+        Output {
+            writer: BufWriter::new(writer),
+            flush_every,
+            since_flush: 0,
+            encoding,
+            pending: Vec::new(),
+            copy: None,
+        }
+    }
+
+    /// Also writes every byte to `copy` as it is, before any encoding, e.g. so
+    /// binary output survives in a file while the terminal gets text.
+    pub fn with_copy(mut self, copy: Box<dyn Write>) -> Output<W> {
+        self.copy = Some(BufWriter::new(copy));
+        self
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.get_ref()
+    }
+
+    /// Flushes at the end of the run. A UTF-8 sequence the program never
+    /// finished is written as a replacement character.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.writer
+                .write_all(char::REPLACEMENT_CHARACTER.to_string().as_bytes())?;
+        }
+        self.flush()
+    }
+
+    // Writes out whatever `pending` can be decoded into so far
+    fn decode_pending(&mut self) -> io::Result<()> {
+        loop {
+            let error = match std::str::from_utf8(&self.pending) {
+                Ok(text) => {
+                    self.writer.write_all(text.as_bytes())?;
+                    self.pending.clear();
+                    return Ok(());
+                }
+                Err(error) => error,
+            };
+            let valid = error.valid_up_to();
+            self.writer.write_all(&self.pending[..valid])?;
+            match error.error_len() {
+                None => {
+                    self.pending.drain(..valid);
+                    return Ok(());
+                }
+                Some(invalid) => {
+                    self.writer
+                        .write_all(char::REPLACEMENT_CHARACTER.to_string().as_bytes())?;
+                    self.pending.drain(..valid + invalid);
+                }
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if let Some(copy) = &mut self.copy {
+            copy.write_all(bytes)?;
+        }
+        match self.encoding {
+            Encoding::Latin1 => {
+                for byte in bytes {
+                    let mut encoded = [0; 2];
+                    let encoded = (*byte as char).encode_utf8(&mut encoded);
+                    self.writer.write_all(encoded.as_bytes())?;
+                }
+            }
+            Encoding::Utf8 => {
+                self.pending.extend_from_slice(bytes);
+                self.decode_pending()?;
+            }
+            Encoding::Raw => self.writer.write_all(bytes)?,
+        }
+        self.since_flush += bytes.len();
+        if self
+            .flush_every
+            .is_some_and(|every| self.since_flush >= every)
+        {
+            self.flush()?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.since_flush = 0;
+        if let Some(copy) = &mut self.copy {
+            copy.flush()?;
+        }
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_every() {
+        let mut output = Output::new(Vec::new(), Some(3), Encoding::Raw);
+        output.write_all(b"ab").unwrap();
+        assert!(output.get_ref().is_empty());
+        output.write_all(b"c").unwrap();
+        assert_eq!(output.get_ref(), b"abc");
+
+        let mut output = Output::new(Vec::new(), None, Encoding::Raw);
+        output.write_all(&[b'x'; 100]).unwrap();
+        assert!(output.get_ref().is_empty());
+        output.flush().unwrap();
+        assert_eq!(output.get_ref().len(), 100);
+    }
+
+    #[test]
+    fn test_encodings() {
+        let bytes = "é€".as_bytes();
+        let mut output = Output::new(Vec::new(), Some(1), Encoding::Utf8);
+        output.write_all(&bytes[..3]).unwrap();
+        assert_eq!(output.get_ref(), "é".as_bytes());
+        output.write_all(&bytes[3..]).unwrap();
+        output.write_all(b"\xffa\xe2\x82").unwrap();
+        output.finish().unwrap();
+        assert_eq!(output.get_ref(), "é€\u{fffd}a\u{fffd}".as_bytes());
+
+        let mut output = Output::new(Vec::new(), None, Encoding::Latin1);
+        output.write_all(b"\xe9").unwrap();
+        output.finish().unwrap();
+        assert_eq!(output.get_ref(), "é".as_bytes());
+    }
+
+    #[test]
+    fn test_copy() {
+        let path = std::env::temp_dir().join(format!("bf-output-{}", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        let mut output = Output::new(Vec::new(), None, Encoding::Latin1).with_copy(Box::new(file));
+        output.write_all(b"a\xe9").unwrap();
+        output.finish().unwrap();
+        assert_eq!(output.get_ref(), "aé".as_bytes());
+        assert_eq!(std::fs::read(&path).unwrap(), b"a\xe9");
+        std::fs::remove_file(path).unwrap();
+    }
+}
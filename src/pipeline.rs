@@ -0,0 +1,162 @@
+// `brainfuck-rs pipe`: several programs run at once, each in its own thread,
+// with the bytes one prints becoming the input of the next. Stages are
+// connected by bounded channels, so a fast producer blocks rather than
+// buffering without limit ahead of a slow consumer.
+
+use crate::{run_interpreter, Error, Extensions, Interpreter, ProgramInput};
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+
+/// Bytes that may be in flight between two stages.
+pub const CHANNEL_CAPACITY: usize = 4096;
+
+/// Why a pipeline failed, and which program (counting from 0) failed.
+#[derive(Debug, PartialEq)]
+pub struct StageError {
+    pub stage: usize,
+    pub error: Error,
+}
+
+impl std::fmt::Display for StageError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "program {}: {}", self.stage + 1, self.error)
+    }
+}
+
+// The sending end of a channel, so a stage can print into it
+struct ChannelWriter(SyncSender<u8>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        for byte in bytes {
+            // The next stage halted without reading everything
+            self.0
+                .send(*byte)
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// The receiving end; ends once the previous stage has halted
+struct ChannelReader(Receiver<u8>);
+
+impl Read for ChannelReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let Some(first) = buffer.first_mut() else {
+            return Ok(0);
+        };
+        match self.0.recv() {
+            Ok(byte) => *first = byte,
+            Err(_) => return Ok(0),
+        }
+        // Take whatever else has already arrived, without waiting for more
+        let mut read = 1;
+        while read < buffer.len() {
+            match self.0.try_recv() {
+                Ok(byte) => buffer[read] = byte,
+                Err(_) => break,
+            }
+            read += 1;
+        }
+        Ok(read)
+    }
+}
+
+/// Runs sanitized programs as a pipeline: `input` feeds the first, and the
+/// last one writes to `output`. Every program's brackets are checked before
+/// any of them starts. A program whose reader halts early just stops, like
+/// a shell pipeline; any other error ends the whole pipeline.
+pub fn run_pipeline(
+    sources: &[String],
+    extensions: Extensions,
+    input: ProgramInput,
+    output: &mut dyn Write,
+    capacity: usize,
+) -> Result<(), StageError> {
+    let mut interpreters = Vec::new();
+    for (stage, source) in sources.iter().enumerate() {
+        let interpreter =
+            Interpreter::new(source, extensions).map_err(|error| StageError { stage, error })?;
+        interpreters.push(interpreter);
+    }
+    let Some(last) = interpreters.pop() else {
+        return Ok(());
+    };
+    let last_stage = interpreters.len();
+
+    std::thread::scope(|scope| {
+        let mut input = input;
+        let mut stages = Vec::new();
+        for (stage, interpreter) in interpreters.into_iter().enumerate() {
+            let (sender, receiver) = sync_channel(capacity);
+            let mut stage_input = std::mem::replace(
+                &mut input,
+                ProgramInput::from_reader(Box::new(ChannelReader(receiver))),
+            );
+            stages.push(scope.spawn(move || {
+                let mut writer = ChannelWriter(sender);
+                match run_interpreter(interpreter, &mut stage_input, &mut writer, &mut ()) {
+                    Err(Error::Io(io::ErrorKind::BrokenPipe)) => Ok(()),
+                    result => result.map_err(|error| StageError { stage, error }),
+                }
+            }));
+        }
+        let result =
+            run_interpreter(last, &mut input, output, &mut ()).map_err(|error| StageError {
+                stage: last_stage,
+                error,
+            });
+        // Unblock any stage still waiting to send to the last one
+        drop(input);
+        let mut first_error = None;
+        for stage in stages {
+            let stage_result = stage.join().expect("pipeline stage panicked");
+            first_error = first_error.or(stage_result.err());
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => result,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline() {
+        // Each stage stops at a NUL, passing it on so the next one stops too
+        // (and the first never falls back to stdin)
+        let sources = [
+            ",[+.,].".to_string(), // Adds one to every byte
+            ",[.,].".to_string(),  // Passes bytes through
+            ",[+.,]".to_string(),
+        ];
+        let input = ProgramInput::new(b"abc\0");
+        let mut output = Vec::new();
+        run_pipeline(&sources, Extensions::default(), input, &mut output, 2).unwrap();
+        assert_eq!(output, b"cde");
+
+        let sources = ["+.".to_string(), "[".to_string()];
+        let error = run_pipeline(
+            &sources,
+            Extensions::default(),
+            ProgramInput::new(&[]),
+            &mut output,
+            2,
+        );
+        assert_eq!(
+            error,
+            Err(StageError {
+                stage: 1,
+                error: Error::MismatchedBrackets(0)
+            })
+        );
+    }
+}
@@ -0,0 +1,175 @@
+// Source excerpts for the terminal, shared by runtime errors, `check` and
+// `explain --analysis`: the line something is about, cut down to a window
+// around it when the line is long (minified programs are often one line),
+// with a caret under the spot. With color, brackets are colored by how deeply
+// they nest, so the pair that's off stands out, and the spot is highlighted
+// in the color of what's being said about it.
+
+use std::io::IsTerminal;
+
+/// `--color`: whether to use ANSI colors.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorChoice {
+    #[default]
+    Auto, // When writing to a terminal, unless NO_COLOR is set
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(name: &str) -> Result<ColorChoice, String> {
+        match name {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(format!("unknown color choice '{}'", other)),
+        }
+    }
+
+    /// A renderer for excerpts written to `stream`.
+    pub fn render(self, stream: &impl IsTerminal) -> Render {
+        let color = match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                stream.is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+            }
+        };
+        Render { color }
+    }
+}
+
+/// What's being pointed at, which picks the highlight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tone {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Tone {
+    fn style(self) -> &'static str {
+        match self {
+            Tone::Error => "1;31",
+            Tone::Warning => "1;33",
+            Tone::Note => "1;36",
+        }
+    }
+}
+
+// Bracket colors by nesting depth, going round again past the last
+const DEPTH_STYLES: [&str; 5] = ["35", "36", "32", "34", "33"];
+
+// How much of a long line to show, in characters
+const WIDTH: usize = 72;
+
+pub struct Render {
+    color: bool,
+}
+
+impl Render {
+    /// `text` in `tone`'s color, e.g. the severity in a diagnostic.
+    pub fn paint(&self, text: &str, tone: Tone) -> String {
+        self.styled(text, tone.style())
+    }
+
+    fn styled(&self, text: &str, style: &str) -> String {
+        match self.color {
+            true => format!("\x1b[{}m{}\x1b[0m", style, text),
+            false => text.to_string(),
+        }
+    }
+
+    /// Line `line` of `text`, and a line with a caret under `column` (both
+    /// one-based, in characters), without the newlines.
+    pub fn snippet(&self, text: &str, line: usize, column: usize, tone: Tone) -> (String, String) {
+        // Brackets before the line still count towards its depths
+        let mut depth = 0;
+        let mut characters = Vec::new(); // With the style of each
+        for (number, row) in text.split('\n').enumerate() {
+            for character in row.chars() {
+                let style = match character {
+                    '[' => {
+                        depth += 1;
+                        Some(DEPTH_STYLES[(depth - 1) % DEPTH_STYLES.len()])
+                    }
+                    ']' => {
+                        let style = DEPTH_STYLES[depth.saturating_sub(1) % DEPTH_STYLES.len()];
+                        depth = depth.saturating_sub(1);
+                        Some(style)
+                    }
+                    _ => None,
+                };
+                if number + 1 == line {
+                    characters.push((character, style));
+                }
+            }
+            if number + 1 == line {
+                break;
+            }
+        }
+        let at = column.saturating_sub(1);
+        let start = at
+            .saturating_sub(WIDTH / 2)
+            .min(characters.len().saturating_sub(WIDTH));
+        let end = characters.len().min(start + WIDTH);
+        let mut code = String::new();
+        let mut caret = String::new();
+        if start > 0 {
+            code.push_str("...");
+            caret.push_str("   ");
+        }
+        for (index, (character, style)) in characters[start..end].iter().enumerate() {
+            // Tabs would push the caret out of line
+            let character = match character {
+                '\t' => " ".to_string(),
+                other => other.to_string(),
+            };
+            match (start + index == at, style) {
+                (true, _) => code.push_str(&self.paint(&character, tone)),
+                (false, Some(style)) => code.push_str(&self.styled(&character, style)),
+                (false, None) => code.push_str(&character),
+            }
+            if start + index < at {
+                caret.push(' ');
+            }
+        }
+        if end < characters.len() {
+            code.push_str("...");
+        }
+        caret.push_str(&self.paint("^", tone));
+        (code, caret)
+    }
+
+    /// `snippet` with the line number down the side, as a block of lines.
+    pub fn excerpt(&self, text: &str, line: usize, column: usize, tone: Tone) -> String {
+        let (code, caret) = self.snippet(text, line, column, tone);
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!(" {} | {}\n {} | {}\n", number, code, gutter, caret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excerpt() {
+        let plain = Render { color: false };
+        assert_eq!(
+            plain.excerpt("+\n\t[-]]\n", 2, 5, Tone::Error),
+            " 2 |  [-]]\n   |     ^\n"
+        );
+        // Long lines are cut down around the column
+        let (code, caret) = plain.snippet(&"+".repeat(200), 1, 100, Tone::Error);
+        assert_eq!(code.len(), 3 + WIDTH + 3);
+        assert_eq!(caret.find('^'), Some(3 + WIDTH / 2));
+
+        let color = Render { color: true };
+        let (code, _) = color.snippet("[[", 1, 1, Tone::Warning);
+        assert_eq!(code, "\x1b[1;33m[\x1b[0m\x1b[36m[\x1b[0m");
+    }
+}
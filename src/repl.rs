@@ -0,0 +1,69 @@
+// `brainfuck-rs repl`: brainfuck typed in a line at a time, all run on one
+// machine. Each entry is appended to the session with `Interpreter::append`,
+// so only the new code is parsed and has its brackets matched, and the tape,
+// the pointer and any procedures carry over however long the session gets.
+// An entry that leaves a loop open waits for the lines that close it. The
+// program's own input is read from stdin too, a line at a time.
+
+use brainfuck_rs::{
+    execute, ir, sanitize_input, ExecutionObserver, Extensions, Interpreter, ProgramInput,
+};
+use std::io::{self, BufRead, Write};
+
+// Whether the last thing printed ended its line, so prompts start on their own
+#[derive(Default)]
+struct LastOutput(Option<u8>);
+
+impl ExecutionObserver for LastOutput {
+    fn on_output(&mut self, byte: u8) {
+        self.0 = Some(byte);
+    }
+}
+
+// How many more loops (and procedures) `commands` opens than it closes
+fn depth(commands: &str) -> isize {
+    commands
+        .chars()
+        .map(|command| match command {
+            '[' | '(' => 1,
+            ']' | ')' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+pub fn repl(extensions: Extensions) {
+    let mut interpreter = Interpreter::from_program(Vec::new(), extensions)
+        .expect("an empty program has no brackets");
+    let mut input = ProgramInput::new(&[]);
+    let mut last = LastOutput::default();
+    let mut entry = String::new(); // Commands typed since the last run
+    let stdin = io::stdin();
+    loop {
+        print!("{}", if entry.is_empty() { "bf> " } else { "..> " });
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            println!();
+            break;
+        }
+        entry.push_str(&sanitize_input(&line, extensions));
+        if depth(&entry) > 0 {
+            continue;
+        }
+        let commands = std::mem::take(&mut entry);
+        if let Err(error) = interpreter.append(&ir::parse(&commands)) {
+            // Nothing was added, so the session carries on without it
+            println!("error: {}", error);
+            continue;
+        }
+        let result = execute(&mut interpreter, &mut input, &mut io::stdout(), &mut last);
+        if !matches!(last.0.take(), None | Some(b'\n')) {
+            println!();
+        }
+        if let Err(error) = result {
+            println!("error: {}", error);
+            interpreter.halt();
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// Machine-readable results for `--format json`. Every command reports the
+// same envelope (which command ran, whether it succeeded and the exit status
+// the process ends with) followed by fields of its own, so tools can handle
+// any command's report the same way.
+
+use crate::json;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    command: &'static str,
+    exit_code: i32,
+    error: Option<String>,
+    fields: Vec<(&'static str, String)>, // Already encoded
+}
+
+impl Report {
+    pub fn new(command: &'static str) -> Report {
+        Report {
+            command,
+            exit_code: 0,
+            error: None,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    /// Adds a field; `value` must already be encoded as JSON.
+    pub fn field(&mut self, name: &'static str, value: String) {
+        self.fields.push((name, value));
+    }
+
+    /// Adds what the program printed: as `output` if it's valid UTF-8, and
+    /// base64-encoded as `output_base64` otherwise.
+    pub fn output(&mut self, bytes: &[u8]) {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => self.field("output", json::string(text)),
+            Err(_) => self.field("output_base64", json::string(&base64(bytes))),
+        }
+    }
+
+    /// Marks the command as failed.
+    pub fn fail(&mut self, exit_code: i32, error: Option<String>) {
+        self.exit_code = exit_code;
+        self.error = error;
+    }
+
+    pub fn to_json(&self) -> String {
+        let success = self.exit_code == 0 && self.error.is_none();
+        let mut fields = vec![
+            ("command", json::string(self.command)),
+            ("success", success.to_string()),
+            ("exit_code", self.exit_code.to_string()),
+        ];
+        if let Some(error) = &self.error {
+            fields.push(("error", json::string(error)));
+        }
+        fields.extend(self.fields.iter().cloned());
+        json::object(&fields)
+    }
+}
+
+/// Standard, padded base64.
+pub fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (*byte as u32) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0x00]), "/wA=");
+
+        let mut report = Report::new("run");
+        report.output(b"hi\n");
+        assert_eq!(
+            report.to_json(),
+            r#"{"command":"run","success":true,"exit_code":0,"output":"hi\n"}"#
+        );
+        let mut report = Report::new("run");
+        report.output(&[0xff]);
+        report.fail(1, Some("oops".to_string()));
+        assert_eq!(
+            report.to_json(),
+            r#"{"command":"run","success":false,"exit_code":1,"error":"oops","output_base64":"/w=="}"#
+        );
+    }
+}
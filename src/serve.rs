@@ -0,0 +1,278 @@
+// `brainfuck-rs serve`: a tiny HTTP playground backend. Programs are POSTed
+// to `/run` as `{"source": "...", "input": "..."}` and run under a `Sandbox`
+// with the server's limits, with the output, any error and some statistics
+// returned as JSON. Every connection gets its own thread, so requests are
+// read with a timeout and bounded line lengths to keep a slow or oversized
+// client from holding one forever, and past `MAX_CONNECTIONS` at once new
+// ones are turned away with a 503 rather than given a thread.
+
+use brainfuck_rs::json::{self, Value};
+use brainfuck_rs::limits::{Limits, Sandbox};
+use brainfuck_rs::{run, sanitize_input, Extensions, ProgramInput};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const MAX_BODY_BYTES: usize = 1 << 20;
+const MAX_LINE_BYTES: usize = 8 << 10; // The request line and each header
+const MAX_HEADERS: usize = 100;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_CONNECTIONS: usize = 64; // Handled at once
+
+/// The limits used unless `serve` is given others.
+pub fn default_limits() -> Limits {
+    Limits {
+        max_steps: Some(10_000_000),
+        max_tape_bytes: None,
+        max_output_bytes: Some(1 << 20),
+    }
+}
+
+pub fn serve(address: &str, limits: Limits) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    eprintln!("listening on http://{}", listener.local_addr()?);
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let Some(connection) = admit(&connections) else {
+            // Answered here, so a client that doesn't read can't stall the loop
+            let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+            let _ = write_response(stream, 503, &error_body("too many connections"));
+            continue;
+        };
+        std::thread::spawn(move || {
+            if let Err(error) = handle_connection(stream, limits) {
+                eprintln!("request failed: {}", error);
+            }
+            drop(connection);
+        });
+    }
+    Ok(())
+}
+
+// A connection being handled; it stops counting once dropped, even if its
+// handler panics
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Counts in a new connection, unless there are already `MAX_CONNECTIONS`
+fn admit(connections: &Arc<AtomicUsize>) -> Option<Connection> {
+    connections
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+            (count < MAX_CONNECTIONS).then_some(count + 1)
+        })
+        .ok()
+        .map(|_| Connection(Arc::clone(connections)))
+}
+
+fn handle_connection(stream: TcpStream, limits: Limits) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request_line = read_line(&mut reader)?;
+    let mut content_length = 0;
+    for headers in 0.. {
+        if headers == MAX_HEADERS {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "too many headers",
+            ));
+        }
+        let header = read_line(&mut reader)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut words = request_line.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("POST"), Some("/run")) if content_length > MAX_BODY_BYTES => {
+            (413, error_body("request body is too large"))
+        }
+        (Some("POST"), Some("/run")) => {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            handle_run(&String::from_utf8_lossy(&body), limits)
+        }
+        _ => (404, error_body("POST programs to /run")),
+    };
+    write_response(stream, status, &body)
+}
+
+// A line of the request head, refusing to buffer one longer than
+// MAX_LINE_BYTES
+fn read_line(reader: &mut impl BufRead) -> std::io::Result<String> {
+    let mut line = String::new();
+    reader
+        .take(MAX_LINE_BYTES as u64 + 1)
+        .read_line(&mut line)?;
+    match line.len() > MAX_LINE_BYTES {
+        true => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "request line or header is too long",
+        )),
+        false => Ok(line),
+    }
+}
+
+fn write_response(mut stream: TcpStream, status: u16, body: &str) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn error_body(message: &str) -> String {
+    json::object(&[("error", json::string(message))])
+}
+
+/// Runs the program described by a request body, returning the HTTP status
+/// and the JSON response.
+pub fn handle_run(body: &str, limits: Limits) -> (u16, String) {
+    let request = match json::parse(body) {
+        Ok(request) => request,
+        Err(message) => return (400, error_body(&format!("invalid JSON: {}", message))),
+    };
+    let Some(source) = request.get("source").and_then(Value::as_str) else {
+        return (400, error_body("the request needs a \"source\" string"));
+    };
+    let input = request.get("input").and_then(Value::as_str).unwrap_or("");
+    // Requests may ask for tighter limits, but never looser ones
+    let tighter = |name: &str, cap: Option<u64>| {
+        let requested = request.get(name).and_then(Value::as_u64);
+        match (requested, cap) {
+            (Some(requested), Some(cap)) => Some(requested.min(cap)),
+            (requested, cap) => requested.or(cap),
+        }
+    };
+    let limits = Limits {
+        max_steps: tighter("max_steps", limits.max_steps),
+        max_tape_bytes: tighter(
+            "max_tape_bytes",
+            limits.max_tape_bytes.map(|max| max as u64),
+        )
+        .map(|max| max as usize),
+        max_output_bytes: tighter(
+            "max_output_bytes",
+            limits.max_output_bytes.map(|max| max as u64),
+        )
+        .map(|max| max as usize),
+    };
+
+    let started = Instant::now();
+    let mut output = Vec::new();
+    let mut sandbox = Sandbox::new(limits);
+    // End of input once the request's input runs out, rather than stdin
+    let mut input = ProgramInput::new(input.as_bytes()).with_reader(Box::new(std::io::empty()));
+    let error = run(
+        &sanitize_input(source, Extensions::default()),
+        Extensions::default(),
+        &mut input,
+        &mut output,
+        &mut sandbox,
+    )
+    .err()
+    .map(|error| error.to_string());
+
+    let stats = json::object(&[
+        ("steps", sandbox.steps.to_string()),
+        ("tape_bytes", sandbox.tape_bytes.to_string()),
+        ("output_bytes", output.len().to_string()),
+        ("elapsed_micros", started.elapsed().as_micros().to_string()),
+    ]);
+    let response = json::object(&[
+        ("output", json::string(&String::from_utf8_lossy(&output))),
+        (
+            "error",
+            error.map_or("null".to_string(), |error| json::string(&error)),
+        ),
+        ("stats", stats),
+    ]);
+    (200, response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admit() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let mut admitted: Vec<Connection> = (0..MAX_CONNECTIONS)
+            .map(|_| admit(&connections).unwrap())
+            .collect();
+        assert!(admit(&connections).is_none());
+        admitted.pop();
+        assert!(admit(&connections).is_some());
+        drop(admitted);
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_handle_run() {
+        let (status, response) = handle_run(r#"{"source": ",+.", "input": "a"}"#, default_limits());
+        assert_eq!(status, 200);
+        let response = json::parse(&response).unwrap();
+        assert_eq!(response.get("output").and_then(Value::as_str), Some("b"));
+        assert_eq!(response.get("error"), Some(&Value::Null));
+        let steps = response.get("stats").and_then(|stats| stats.get("steps"));
+        assert_eq!(steps.and_then(Value::as_u64), Some(3));
+
+        let (_, response) = handle_run(r#"{"source": "+[]", "max_steps": 50}"#, default_limits());
+        let response = json::parse(&response).unwrap();
+        assert_eq!(
+            response.get("error").and_then(Value::as_str),
+            Some("step limit of 50 exceeded")
+        );
+
+        // The cap is exact, and requests can only tighten it
+        let (_, response) = handle_run(
+            r#"{"source": "+[.]", "max_output_bytes": 5, "max_tape_bytes": 9}"#,
+            default_limits(),
+        );
+        let response = json::parse(&response).unwrap();
+        assert_eq!(
+            response.get("output").and_then(Value::as_str),
+            Some("\u{1}".repeat(5).as_str())
+        );
+        assert_eq!(
+            response.get("error").and_then(Value::as_str),
+            Some("output limit of 5 bytes exceeded")
+        );
+
+        assert_eq!(handle_run("{}", default_limits()).0, 400);
+    }
+
+    #[test]
+    fn test_read_line() {
+        let head = format!("GET / HTTP/1.1\r\n{}\r\n", "x".repeat(MAX_LINE_BYTES));
+        let mut reader = head.as_bytes();
+        assert_eq!(read_line(&mut reader).unwrap(), "GET / HTTP/1.1\r\n");
+        assert!(read_line(&mut reader).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+// `brainfuck-rs stats`: the shape of a program, for golfing and for picking
+// interpreter settings. How often each command appears, how many loops
+// there are and how deep they nest, and roughly how much tape it needs.
+
+use crate::analysis::{self, TapeBound};
+use crate::{ir, is_command, json, sanitize_input, Extensions, MEMORY_SIZE};
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct ProgramStats {
+    pub source_bytes: usize,
+    pub commands: usize,               // What's left after sanitizing
+    pub histogram: Vec<(char, usize)>, // Every command the extensions allow
+    pub loops: usize,
+    pub max_depth: usize,
+    pub tape: TapeBound, // Only a lower bound unless it's `bounded`
+}
+
+// In the order they're listed, extensions last
+const COMMANDS: &str = "+-<>.,[]#():{}Y";
+
+/// Counts what's in `source`, which needn't have been sanitized.
+pub fn stats(source: &str, extensions: Extensions) -> ProgramStats {
+    let buffer = sanitize_input(source, extensions);
+    let histogram = COMMANDS
+        .chars()
+        .filter(|command| is_command(*command, extensions))
+        .map(|command| (command, buffer.matches(command).count()))
+        .collect();
+    let mut depth: usize = 0;
+    let mut max_depth = 0;
+    for character in buffer.chars() {
+        match character {
+            '[' => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            ']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    ProgramStats {
+        source_bytes: source.len(),
+        commands: buffer.chars().count(),
+        histogram,
+        loops: buffer.matches('[').count(),
+        max_depth,
+        tape: analysis::tape_bound(&ir::parse(&buffer)),
+    }
+}
+
+impl ProgramStats {
+    pub fn to_json(&self) -> String {
+        let names: Vec<String> = self
+            .histogram
+            .iter()
+            .map(|(command, _)| command.to_string())
+            .collect();
+        let histogram: Vec<(&str, String)> = names
+            .iter()
+            .zip(&self.histogram)
+            .map(|(name, (_, count))| (name.as_str(), count.to_string()))
+            .collect();
+        json::object(&[
+            ("source_bytes", self.source_bytes.to_string()),
+            ("commands", self.commands.to_string()),
+            ("histogram", json::object(&histogram)),
+            ("loops", self.loops.to_string()),
+            ("max_depth", self.max_depth.to_string()),
+            ("tape_span", self.tape.span().to_string()),
+            ("scans", (!self.tape.bounded).to_string()),
+            ("tape_bounded", self.tape.bounded.to_string()),
+        ])
+    }
+}
+
+impl fmt::Display for ProgramStats {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(formatter, "source bytes  {}", self.source_bytes)?;
+        writeln!(formatter, "commands      {}", self.commands)?;
+        for (command, count) in &self.histogram {
+            writeln!(formatter, "  {}           {}", command, count)?;
+        }
+        writeln!(formatter, "loops         {}", self.loops)?;
+        writeln!(formatter, "max depth     {}", self.max_depth)?;
+        let span = self.tape.span();
+        let cells = if span == 1 { "cell" } else { "cells" };
+        match (self.tape.bounded, span > MEMORY_SIZE) {
+            (false, _) => writeln!(
+                formatter,
+                "tape span     at least {} {} (the pointer moves by varying amounts)",
+                span, cells
+            ),
+            (true, false) => writeln!(formatter, "tape span     {} {}", span, cells),
+            (true, true) => writeln!(
+                formatter,
+                "tape span     {} cells (more than the {}-cell tape, so it wraps)",
+                span, MEMORY_SIZE
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let summary = stats("a+[>+[-<]>>]<. comment", Extensions::default());
+        assert_eq!(summary.source_bytes, 22);
+        assert_eq!(summary.commands, 13);
+        assert_eq!(summary.histogram[0], ('+', 2));
+        assert_eq!(summary.histogram.len(), 8);
+        assert_eq!((summary.loops, summary.max_depth), (2, 2));
+        // The inner loop moves left each time, so there's no bound
+        assert_eq!((summary.tape.span(), summary.tape.bounded), (2, false));
+
+        let summary = stats(">>[-<<+>>]<<<", Extensions::default());
+        assert_eq!((summary.tape.span(), summary.tape.bounded), (4, true));
+        assert!(summary.to_string().ends_with("tape span     4 cells\n"));
+        assert!(summary
+            .to_json()
+            .ends_with("\"scans\":false,\"tape_bounded\":true}"));
+    }
+}
@@ -0,0 +1,195 @@
+// Tapes kept in a file between runs, for `run --tape-file`: a run starts on
+// the cells the last one left and leaves its own for the next, so programs
+// can keep a crude database or hand results down a pipeline through the tape.
+// The file is the 256 cells as raw bytes; a missing or short one is padded
+// with zeros. The first thread's first tape is the one saved.
+//
+// Either way the interpreter runs on its own copy of the tape, filled from
+// the backend at the start and copied back at the end. `FileTape` reads the
+// file in and writes it back. With the `mmap` feature, `MappedTape` maps the
+// file instead, so its cells are the file's own pages and saving them is a
+// matter of asking the kernel to write them out. As with the terminal
+// handling, the libc calls are made directly and only wired up where their
+// constants are known.
+
+use crate::{Memory, MEMORY_SIZE};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where a tape lives between runs.
+pub trait TapeBackend {
+    /// The saved cells, to start a run with and to put its tape back into.
+    fn cells(&mut self) -> &mut Memory;
+    /// Makes sure the cells are stored.
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+// Files any longer than a tape aren't one
+fn check_length(length: u64) -> io::Result<()> {
+    match length > MEMORY_SIZE as u64 {
+        true => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} bytes is more than a {}-cell tape", length, MEMORY_SIZE),
+        )),
+        false => Ok(()),
+    }
+}
+
+/// A tape read from its file and written back whole.
+pub struct FileTape {
+    path: PathBuf,
+    cells: Memory,
+}
+
+impl FileTape {
+    pub fn open(path: &Path) -> io::Result<FileTape> {
+        let mut cells = [0; MEMORY_SIZE];
+        let saved = std::fs::read(path).or_else(|error| match error.kind() {
+            io::ErrorKind::NotFound => Ok(Vec::new()),
+            _ => Err(error),
+        })?;
+        check_length(saved.len() as u64)?;
+        cells[..saved.len()].copy_from_slice(&saved);
+        Ok(FileTape {
+            path: path.to_path_buf(),
+            cells,
+        })
+    }
+}
+
+impl TapeBackend for FileTape {
+    fn cells(&mut self) -> &mut Memory {
+        &mut self.cells
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        std::fs::write(&self.path, self.cells)
+    }
+}
+
+#[cfg(all(feature = "mmap", any(target_os = "linux", target_os = "macos")))]
+mod mapped {
+    use super::{check_length, TapeBackend};
+    use crate::{Memory, MEMORY_SIZE};
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+    use std::os::raw::{c_int, c_void};
+    use std::path::Path;
+
+    const PROT_READ: c_int = 1;
+    const PROT_WRITE: c_int = 2;
+    const MAP_SHARED: c_int = 1;
+    #[cfg(target_os = "linux")]
+    const MS_SYNC: c_int = 4;
+    #[cfg(target_os = "macos")]
+    const MS_SYNC: c_int = 0x10;
+
+    extern "C" {
+        fn mmap(
+            address: *mut c_void,
+            length: usize,
+            protection: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        fn msync(address: *mut c_void, length: usize, flags: c_int) -> c_int;
+        fn munmap(address: *mut c_void, length: usize) -> c_int;
+    }
+
+    /// A tape whose cells are the file, mapped into memory.
+    pub struct MappedTape {
+        cells: *mut Memory,
+        _file: File, // Kept open for as long as it's mapped
+    }
+
+    impl MappedTape {
+        pub fn open(path: &Path) -> io::Result<MappedTape> {
+            let file = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+            check_length(file.metadata()?.len())?;
+            // Mapping past the end of a file faults, so it's padded first
+            file.set_len(MEMORY_SIZE as u64)?;
+            let address = unsafe {
+                mmap(
+                    std::ptr::null_mut(),
+                    MEMORY_SIZE,
+                    PROT_READ | PROT_WRITE,
+                    MAP_SHARED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            // MAP_FAILED
+            if address as usize == usize::MAX {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(MappedTape {
+                cells: address as *mut Memory,
+                _file: file,
+            })
+        }
+    }
+
+    impl TapeBackend for MappedTape {
+        fn cells(&mut self) -> &mut Memory {
+            // The mapping is exactly one tape long and lives as long as self
+            unsafe { &mut *self.cells }
+        }
+
+        fn sync(&mut self) -> io::Result<()> {
+            match unsafe { msync(self.cells as *mut c_void, MEMORY_SIZE, MS_SYNC) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        }
+    }
+
+    impl Drop for MappedTape {
+        fn drop(&mut self) {
+            unsafe { munmap(self.cells as *mut c_void, MEMORY_SIZE) };
+        }
+    }
+}
+
+#[cfg(all(feature = "mmap", any(target_os = "linux", target_os = "macos")))]
+pub use mapped::MappedTape;
+
+/// The tape saved at `path`: mapped with the `mmap` feature where that's
+/// supported, read and written back otherwise.
+pub fn open(path: &Path) -> io::Result<Box<dyn TapeBackend>> {
+    #[cfg(all(feature = "mmap", any(target_os = "linux", target_os = "macos")))]
+    let tape = MappedTape::open(path)?;
+    #[cfg(not(all(feature = "mmap", any(target_os = "linux", target_os = "macos"))))]
+    let tape = FileTape::open(path)?;
+    Ok(Box::new(tape))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tape_file() {
+        let path = std::env::temp_dir().join(format!("bf-tape-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let mut tape = open(&path).unwrap();
+            assert_eq!(tape.cells(), &[0; MEMORY_SIZE]);
+            tape.cells()[3] = 7;
+            tape.sync().unwrap();
+        }
+        let saved = std::fs::read(&path).unwrap();
+        assert_eq!((saved.len(), saved[3]), (MEMORY_SIZE, 7));
+        assert_eq!(FileTape::open(&path).unwrap().cells()[3], 7);
+
+        std::fs::write(&path, [1; MEMORY_SIZE + 1]).unwrap();
+        assert!(open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+// `brainfuck-rs gen-text`: generates a program that prints given bytes.
+// Cell 1 holds the byte last printed; each new byte is reached from it
+// either directly or, for big jumps, with a multiplication loop that uses
+// cell 0 as its counter.
+
+use crate::ir::{self, Instruction, Program};
+use crate::optimize::fold_runs;
+
+const MAX_FACTOR: usize = 16;
+// `<`, `[`, `>`, `<`, `-`, `]` and `>`: what a loop costs beyond its adds
+const LOOP_OVERHEAD: usize = 7;
+
+fn adds(amount: i32) -> Instruction {
+    Instruction::Add(amount.rem_euclid(256) as u8)
+}
+
+/// Appends instructions changing cell 1 by `delta` (in -128..=127).
+fn change_by(program: &mut Program, delta: i32) {
+    let distance = delta.unsigned_abs() as usize;
+    let sign = delta.signum();
+    // Find the cheapest `factor * step + rest == distance`
+    let best = (1..=MAX_FACTOR)
+        .map(|factor| {
+            let step = (distance + factor / 2) / factor;
+            let rest = distance as i32 - (factor * step) as i32;
+            let cost = factor + step + rest.unsigned_abs() as usize + LOOP_OVERHEAD;
+            (cost, factor, step, rest)
+        })
+        .min();
+    match best {
+        Some((cost, factor, step, rest)) if cost < distance && step > 0 => {
+            program.extend([
+                Instruction::Move(-1),
+                adds(factor as i32),
+                Instruction::LoopStart,
+                Instruction::Move(1),
+                adds(sign * step as i32),
+                Instruction::Move(-1),
+                Instruction::Add(255),
+                Instruction::LoopEnd,
+                Instruction::Move(1),
+            ]);
+            if rest != 0 {
+                program.push(adds(sign * rest));
+            }
+        }
+        _ if delta != 0 => program.push(adds(delta)),
+        _ => {}
+    }
+}
+
+/// A program printing `text`, leaving the tape otherwise zeroed.
+pub fn generate(text: &[u8]) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let mut program = vec![Instruction::Move(1)];
+    let mut current: u8 = 0;
+    for byte in text {
+        // Go the short way round, since cells wrap
+        let delta = byte.wrapping_sub(current) as i8 as i32;
+        change_by(&mut program, delta);
+        program.push(Instruction::Output);
+        current = *byte;
+    }
+    ir::to_source(&fold_runs(&program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzz::execute;
+
+    #[test]
+    fn test_generate() {
+        for text in [&b"Hello, World!\n"[..], b"", b"\x00\xff\x80a", b"aaaa"] {
+            let source = generate(text);
+            let outcome = execute(&source, b"", 100_000).unwrap();
+            assert!(outcome.halted);
+            assert_eq!(outcome.output, text);
+        }
+        assert_eq!(generate(b"\x02\x01"), ">++.-.");
+        assert!(generate(b"A").len() < 65);
+    }
+}
@@ -0,0 +1,169 @@
+// `run --raw-tty`: takes the terminal on stdin out of line mode for the run,
+// so `,` gets each keypress as it's typed and nothing is echoed. Ctrl-C is
+// left alone, so it still interrupts. The old settings are put back from an
+// `atexit` handler rather than a destructor, since most ways out of `run`
+// end in `process::exit`, and a panic unwinding out of `main` goes through
+// `exit` too. As with the signal handling, this calls libc's termios
+// functions directly; they're only wired up where the struct layout is
+// known.
+
+use std::io;
+use std::sync::{Mutex, Once};
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod platform {
+    use std::os::raw::c_int;
+
+    #[cfg(target_os = "linux")]
+    mod layout {
+        pub type Flags = u32;
+        pub const ECHO: Flags = 0o10;
+        pub const ICANON: Flags = 0o2;
+        pub const VTIME: usize = 5;
+        pub const VMIN: usize = 6;
+
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        pub struct Termios {
+            pub iflag: Flags,
+            pub oflag: Flags,
+            pub cflag: Flags,
+            pub lflag: Flags,
+            pub line: u8,
+            pub cc: [u8; 32],
+            pub ispeed: u32,
+            pub ospeed: u32,
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    mod layout {
+        pub type Flags = u64;
+        pub const ECHO: Flags = 0x8;
+        pub const ICANON: Flags = 0x100;
+        pub const VMIN: usize = 16;
+        pub const VTIME: usize = 17;
+
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        pub struct Termios {
+            pub iflag: Flags,
+            pub oflag: Flags,
+            pub cflag: Flags,
+            pub lflag: Flags,
+            pub cc: [u8; 20],
+            pub ispeed: u64,
+            pub ospeed: u64,
+        }
+    }
+
+    pub use layout::Termios;
+    use layout::{ECHO, ICANON, VMIN, VTIME};
+
+    const STDIN: c_int = 0;
+    const TCSANOW: c_int = 0;
+
+    extern "C" {
+        fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        fn tcsetattr(fd: c_int, action: c_int, termios: *const Termios) -> c_int;
+        fn atexit(callback: extern "C" fn()) -> c_int;
+    }
+
+    pub fn get() -> std::io::Result<Termios> {
+        let mut termios = std::mem::MaybeUninit::<Termios>::uninit();
+        match unsafe { tcgetattr(STDIN, termios.as_mut_ptr()) } {
+            0 => Ok(unsafe { termios.assume_init() }),
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+
+    pub fn set(termios: &Termios) -> std::io::Result<()> {
+        match unsafe { tcsetattr(STDIN, TCSANOW, termios) } {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+
+    pub fn at_exit(callback: extern "C" fn()) {
+        unsafe { atexit(callback) };
+    }
+
+    // Bytes as soon as there's one, without echoing them
+    pub fn make_raw(mut termios: Termios) -> Termios {
+        termios.lflag &= !(ECHO | ICANON);
+        termios.cc[VMIN] = 1;
+        termios.cc[VTIME] = 0;
+        termios
+    }
+
+    #[cfg(test)]
+    pub fn is_raw(termios: &Termios) -> bool {
+        termios.lflag & (ECHO | ICANON) == 0 && termios.cc[VMIN] == 1
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    #[derive(Clone, Copy)]
+    pub struct Termios;
+
+    pub fn get() -> std::io::Result<Termios> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "not supported on this platform",
+        ))
+    }
+
+    pub fn set(_: &Termios) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    pub fn at_exit(_: extern "C" fn()) {}
+
+    pub fn make_raw(termios: Termios) -> Termios {
+        termios
+    }
+}
+
+// The settings to go back to, while the terminal is raw
+static SAVED: Mutex<Option<platform::Termios>> = Mutex::new(None);
+static AT_EXIT: Once = Once::new();
+
+extern "C" fn restore() {
+    let mut saved = SAVED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(original) = saved.take() {
+        let _ = platform::set(&original);
+    }
+}
+
+/// Puts the terminal stdin is attached to into raw mode until the process
+/// exits. Fails if stdin isn't a terminal.
+pub fn enable() -> io::Result<()> {
+    let original = platform::get()?;
+    AT_EXIT.call_once(|| platform::at_exit(restore));
+    let mut saved = SAVED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    platform::set(&platform::make_raw(original))?;
+    saved.get_or_insert(original);
+    Ok(())
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "macos")))]
+mod tests {
+    use super::platform::*;
+
+    #[test]
+    fn test_make_raw() {
+        // Whatever the terminal was doing, only line mode and echo go
+        let mut termios: Termios = unsafe { std::mem::zeroed() };
+        termios.lflag = !0;
+        termios.oflag = 0o1;
+        let raw = make_raw(termios);
+        assert!(is_raw(&raw));
+        assert_eq!(raw.oflag, 0o1);
+        assert_ne!(raw.lflag, 0);
+    }
+}
@@ -0,0 +1,131 @@
+// Terminal visualizer for `--visualize`. It only sees the program through
+// the `ExecutionObserver` hooks, redrawing the tape, the current instruction
+// and the output so far before every step.
+
+use brainfuck_rs::{ExecutionObserver, Memory, MEMORY_SIZE};
+use std::io::Write;
+use std::time::Duration;
+
+const VISIBLE_CELLS: usize = 16;
+const SOURCE_WINDOW: usize = 30; // Characters shown either side of the current instruction
+const OUTPUT_LINES: usize = 8;
+
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[J";
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+
+pub struct Visualizer {
+    source: Vec<char>,
+    delay: Option<Duration>,
+    output: Vec<u8>,
+    steps: u64,
+}
+
+impl Visualizer {
+    /// `speed` is the number of steps drawn per second, or 0 to run flat out.
+    pub fn new(source: &str, speed: u32) -> Visualizer {
+        print!("{}", HIDE_CURSOR);
+        Visualizer {
+            source: source.chars().collect(),
+            delay: (speed > 0).then(|| Duration::from_secs(1) / speed),
+            output: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    /// Restores the cursor once the run is over, leaving the last frame up.
+    pub fn finish(&self) {
+        println!("{}", SHOW_CURSOR);
+    }
+
+    fn render(&self, source_pointer: usize, memory: &Memory, memory_pointer: usize) -> String {
+        let mut frame = String::from(CLEAR_SCREEN);
+        frame.push_str(&format!(
+            "step {}, pointer {}\n\n",
+            self.steps, memory_pointer
+        ));
+
+        let first_cell = std::cmp::min(
+            memory_pointer.saturating_sub(VISIBLE_CELLS / 2),
+            MEMORY_SIZE - VISIBLE_CELLS,
+        );
+        let cells = first_cell..first_cell + VISIBLE_CELLS;
+        let border = format!("{}+\n", "+-----".repeat(VISIBLE_CELLS));
+        frame.push_str(&border);
+        for index in cells.clone() {
+            if index == memory_pointer {
+                frame.push_str(&format!("|{}{:^5}{}", HIGHLIGHT, memory[index], RESET));
+            } else {
+                frame.push_str(&format!("|{:^5}", memory[index]));
+            }
+        }
+        frame.push_str("|\n");
+        frame.push_str(&border);
+        for index in cells {
+            frame.push_str(&format!("{:^6}", index));
+        }
+        frame.push_str("\n\n");
+
+        let start = source_pointer.saturating_sub(SOURCE_WINDOW);
+        let end = std::cmp::min(self.source.len(), source_pointer + SOURCE_WINDOW + 1);
+        let before: String = self.source[start..source_pointer].iter().collect();
+        let after: String = self.source[source_pointer + 1..end].iter().collect();
+        frame.push_str(&format!(
+            "instruction {} of {}\n{}{}{}{}{}\n\n",
+            source_pointer + 1,
+            self.source.len(),
+            before,
+            HIGHLIGHT,
+            self.source[source_pointer],
+            RESET,
+            after
+        ));
+
+        frame.push_str("output\n");
+        let output = String::from_utf8_lossy(&self.output);
+        let lines: Vec<&str> = output.lines().collect();
+        for line in &lines[lines.len().saturating_sub(OUTPUT_LINES)..] {
+            frame.push_str(line);
+            frame.push('\n');
+        }
+        frame
+    }
+}
+
+impl ExecutionObserver for Visualizer {
+    fn on_instruction(&mut self, source_pointer: usize, memory: &Memory, memory_pointer: usize) {
+        self.steps += 1;
+        let frame = self.render(source_pointer, memory, memory_pointer);
+        let mut stdout = std::io::stdout();
+        // Drawing is best effort; a failed frame shouldn't stop the program
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+        if let Some(delay) = self.delay {
+            std::thread::sleep(delay);
+        }
+    }
+
+    fn on_output(&mut self, byte: u8) {
+        self.output.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut visualizer = Visualizer::new("+>.", 0);
+        visualizer.on_output(b'h');
+        let mut memory: Memory = [0; MEMORY_SIZE];
+        memory[1] = 72;
+
+        let frame = visualizer.render(1, &memory, 1);
+        assert!(frame.contains(&format!("|{} 72  {}|", HIGHLIGHT, RESET)));
+        assert!(frame.contains(&format!("+{}>{}.", HIGHLIGHT, RESET)));
+        assert!(frame.ends_with("output\nh\n"));
+    }
+}
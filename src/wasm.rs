@@ -0,0 +1,243 @@
+// WebAssembly exports for in-browser interpreters. These are plain
+// `extern "C"` functions over linear memory rather than wasm-bindgen glue, so
+// the crate builds for `wasm32-unknown-unknown` with no extra dependencies;
+// `wasm/brainfuck.js` wraps them in a friendlier JavaScript API.
+//
+// Strings are passed as (pointer, length) pairs into buffers the host gets
+// from `bf_wasm_alloc`. Any buffer handed back to the host must be released
+// with `bf_wasm_free`.
+
+use crate::{json, run, sanitize_input, Extensions, Interpreter, ProgramInput, StepResult, Yield};
+
+// Return codes of `bf_wasm_stepper_step` and `bf_wasm_stepper_run`; outputs
+// from a step are reported as 256 + the byte. A run that uses up its fuel
+// returns `STEP_CONTINUE`
+const STEP_ERROR: i32 = -1;
+const STEP_CONTINUE: i32 = 0;
+const STEP_NEEDS_INPUT: i32 = 1;
+const STEP_HALTED: i32 = 2;
+const STEP_OUTPUT: i32 = 256;
+
+#[no_mangle]
+pub extern "C" fn bf_wasm_alloc(len: usize) -> *mut u8 {
+    let mut buffer = vec![0u8; len].into_boxed_slice();
+    let pointer = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    pointer
+}
+
+/// # Safety
+///
+/// `pointer` and `len` must describe a buffer returned by this module that
+/// hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_free(pointer: *mut u8, len: usize) {
+    if !pointer.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            pointer, len,
+        )));
+    }
+}
+
+unsafe fn read_string(pointer: *const u8, len: usize) -> String {
+    if pointer.is_null() {
+        return String::new();
+    }
+    String::from_utf8_lossy(std::slice::from_raw_parts(pointer, len)).into_owned()
+}
+
+/// Runs a program to completion, returning `{"output": ..., "error": ...}`
+/// as a JSON buffer whose length is written to `result_len`.
+///
+/// # Safety
+///
+/// The source and input pairs must describe readable buffers, and
+/// `result_len` must be valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_run(
+    source: *const u8,
+    source_len: usize,
+    input: *const u8,
+    input_len: usize,
+    result_len: *mut usize,
+) -> *mut u8 {
+    let source = sanitize_input(&read_string(source, source_len), Extensions::default());
+    let input = read_string(input, input_len);
+    let mut output = Vec::new();
+    let result = run(
+        &source,
+        Extensions::default(),
+        // There's no stdin to fall back on in a page, so the input just ends
+        &mut ProgramInput::new(input.as_bytes()).with_reader(Box::new(std::io::empty())),
+        &mut output,
+        &mut (),
+    );
+    let error = match result {
+        Ok(()) => "null".to_string(),
+        Err(error) => json::string(&error.to_string()),
+    };
+    let result = json::object(&[
+        ("output", json::string(&String::from_utf8_lossy(&output))),
+        ("error", error),
+    ]);
+
+    let mut result = result.into_bytes().into_boxed_slice();
+    // The host may hand us any four bytes of memory, aligned or not
+    result_len.write_unaligned(result.len());
+    let pointer = result.as_mut_ptr();
+    std::mem::forget(result);
+    pointer
+}
+
+/// Creates a stepper for the given source, or returns null if its brackets
+/// don't match.
+///
+/// # Safety
+///
+/// `source` and `source_len` must describe a readable buffer.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_stepper_new(
+    source: *const u8,
+    source_len: usize,
+) -> *mut Interpreter {
+    let source = sanitize_input(&read_string(source, source_len), Extensions::default());
+    match Interpreter::new(&source, Extensions::default()) {
+        Ok(interpreter) => Box::into_raw(Box::new(interpreter)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `stepper` must come from `bf_wasm_stepper_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_stepper_free(stepper: *mut Interpreter) {
+    if !stepper.is_null() {
+        drop(Box::from_raw(stepper));
+    }
+}
+
+/// Executes one instruction; see the `STEP_*` codes above.
+///
+/// # Safety
+///
+/// `stepper` must come from `bf_wasm_stepper_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_stepper_step(stepper: *mut Interpreter) -> i32 {
+    match (*stepper).step() {
+        Ok(StepResult::Continue) => STEP_CONTINUE,
+        Ok(StepResult::NeedsInput) => STEP_NEEDS_INPUT,
+        Ok(StepResult::Output(byte)) => STEP_OUTPUT + byte as i32,
+        Ok(StepResult::Halted) => STEP_HALTED,
+        Err(_) => STEP_ERROR,
+    }
+}
+
+/// Runs at most `fuel` instructions, writing what they print to `output`
+/// and how many bytes that was to `output_len`.
+///
+/// # Safety
+///
+/// `stepper` must come from `bf_wasm_stepper_new` and not have been freed,
+/// `output` must have room for `fuel` bytes and `output_len` must be valid
+/// for a write.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_stepper_run(
+    stepper: *mut Interpreter,
+    fuel: usize,
+    output: *mut u8,
+    output_len: *mut usize,
+) -> i32 {
+    let mut bytes = Vec::new();
+    let result = (*stepper).run_with_fuel(fuel as u64, &mut bytes);
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), output, bytes.len());
+    output_len.write_unaligned(bytes.len());
+    match result {
+        Ok(Yield::OutOfFuel) => STEP_CONTINUE,
+        Ok(Yield::NeedsInput) => STEP_NEEDS_INPUT,
+        Ok(Yield::Halted) => STEP_HALTED,
+        Err(_) => STEP_ERROR,
+    }
+}
+
+/// Supplies the byte for a pending `,`; a negative value means end of input.
+///
+/// # Safety
+///
+/// `stepper` must come from `bf_wasm_stepper_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_stepper_provide_input(stepper: *mut Interpreter, byte: i32) {
+    (*stepper).provide_input(u8::try_from(byte).ok());
+}
+
+/// # Safety
+///
+/// `stepper` must come from `bf_wasm_stepper_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_stepper_memory_pointer(stepper: *const Interpreter) -> usize {
+    (*stepper).memory_pointer()
+}
+
+/// # Safety
+///
+/// `stepper` must come from `bf_wasm_stepper_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bf_wasm_stepper_cell(stepper: *const Interpreter, index: usize) -> u8 {
+    (*stepper).memory().get(index).copied().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_to_json(source: &[u8], input: &[u8]) -> String {
+        let mut result_len = 0;
+        let result = unsafe {
+            bf_wasm_run(
+                source.as_ptr(),
+                source.len(),
+                input.as_ptr(),
+                input.len(),
+                &mut result_len,
+            )
+        };
+        let text = unsafe {
+            String::from_utf8_lossy(std::slice::from_raw_parts(result, result_len)).into_owned()
+        };
+        unsafe { bf_wasm_free(result, result_len) };
+        text
+    }
+
+    #[test]
+    fn test_run_and_step() {
+        let source = b",+.";
+        assert_eq!(run_to_json(source, b"a"), r#"{"output":"b","error":null}"#);
+        // Reading past the input leaves the cell alone rather than waiting on stdin
+        assert_eq!(
+            run_to_json(b",.,.", b"a"),
+            r#"{"output":"aa","error":null}"#
+        );
+
+        unsafe {
+            let stepper = bf_wasm_stepper_new(source.as_ptr(), source.len());
+            assert_eq!(bf_wasm_stepper_step(stepper), STEP_NEEDS_INPUT);
+            bf_wasm_stepper_provide_input(stepper, b'a' as i32);
+            assert_eq!(bf_wasm_stepper_step(stepper), STEP_CONTINUE);
+            assert_eq!(bf_wasm_stepper_step(stepper), STEP_CONTINUE);
+            assert_eq!(bf_wasm_stepper_cell(stepper, 0), b'b');
+            assert_eq!(bf_wasm_stepper_step(stepper), STEP_OUTPUT + b'b' as i32);
+            assert_eq!(bf_wasm_stepper_step(stepper), STEP_HALTED);
+            bf_wasm_stepper_free(stepper);
+            assert!(bf_wasm_stepper_new(b"[".as_ptr(), 1).is_null());
+
+            let source = b"+++.+.";
+            let stepper = bf_wasm_stepper_new(source.as_ptr(), source.len());
+            let (mut output, mut output_len) = ([0; 4], 0);
+            let run = bf_wasm_stepper_run(stepper, 4, output.as_mut_ptr(), &mut output_len);
+            assert_eq!((run, &output[..output_len]), (STEP_CONTINUE, &[3][..]));
+            let run = bf_wasm_stepper_run(stepper, 4, output.as_mut_ptr(), &mut output_len);
+            assert_eq!((run, &output[..output_len]), (STEP_HALTED, &[4][..]));
+            bf_wasm_stepper_free(stepper);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Thin JavaScript wrapper over the `wasm` feature's exports. Build with
+//
+//     cargo build --release --lib --target wasm32-unknown-unknown --features wasm
+//
+// and pass the resulting `brainfuck_rs.wasm` bytes to `load`.
+
+const STEP_ERROR = -1;
+const STEP_CONTINUE = 0;
+const STEP_NEEDS_INPUT = 1;
+const STEP_HALTED = 2;
+const STEP_OUTPUT = 256;
+
+export async function load(wasmBytes) {
+  const { instance } = await WebAssembly.instantiate(wasmBytes, {});
+  const bf = instance.exports;
+  const encoder = new TextEncoder();
+  const decoder = new TextDecoder();
+
+  // Copies a string into wasm memory; the caller frees the returned buffer
+  function pass(text) {
+    const bytes = encoder.encode(text);
+    const pointer = bf.bf_wasm_alloc(bytes.length);
+    new Uint8Array(bf.memory.buffer, pointer, bytes.length).set(bytes);
+    return [pointer, bytes.length];
+  }
+
+  // Runs a program to completion, resolving to `{ output, error }`
+  function run(source, input = "") {
+    const [sourcePointer, sourceLength] = pass(source);
+    const [inputPointer, inputLength] = pass(input);
+    const lengthPointer = bf.bf_wasm_alloc(4);
+    const resultPointer = bf.bf_wasm_run(
+      sourcePointer, sourceLength, inputPointer, inputLength, lengthPointer);
+    const resultLength = new Uint32Array(bf.memory.buffer, lengthPointer, 1)[0];
+    const result = JSON.parse(
+      decoder.decode(new Uint8Array(bf.memory.buffer, resultPointer, resultLength)));
+    bf.bf_wasm_free(resultPointer, resultLength);
+    bf.bf_wasm_free(lengthPointer, 4);
+    bf.bf_wasm_free(inputPointer, inputLength);
+    bf.bf_wasm_free(sourcePointer, sourceLength);
+    return result;
+  }
+
+  // Cooperative execution; call `free()` when done
+  class Stepper {
+    constructor(source) {
+      const [pointer, length] = pass(source);
+      this.handle = bf.bf_wasm_stepper_new(pointer, length);
+      bf.bf_wasm_free(pointer, length);
+      if (this.handle === 0) {
+        throw new Error("mismatched brackets");
+      }
+    }
+
+    // Returns { kind: "continue" | "needs-input" | "halted" | "output", byte? }
+    step() {
+      const code = bf.bf_wasm_stepper_step(this.handle);
+      if (code >= STEP_OUTPUT) return { kind: "output", byte: code - STEP_OUTPUT };
+      if (code === STEP_CONTINUE) return { kind: "continue" };
+      if (code === STEP_NEEDS_INPUT) return { kind: "needs-input" };
+      if (code === STEP_HALTED) return { kind: "halted" };
+      if (code === STEP_ERROR) throw new Error("runtime error");
+      throw new Error(`unknown step result ${code}`);
+    }
+
+    // Runs at most `fuel` instructions, returning
+    // { kind: "out-of-fuel" | "needs-input" | "halted", output }
+    run(fuel) {
+      const outputPointer = bf.bf_wasm_alloc(fuel);
+      const lengthPointer = bf.bf_wasm_alloc(4);
+      const code = bf.bf_wasm_stepper_run(this.handle, fuel, outputPointer, lengthPointer);
+      const length = new Uint32Array(bf.memory.buffer, lengthPointer, 1)[0];
+      const output = new Uint8Array(bf.memory.buffer, outputPointer, length).slice();
+      bf.bf_wasm_free(lengthPointer, 4);
+      bf.bf_wasm_free(outputPointer, fuel);
+      if (code === STEP_CONTINUE) return { kind: "out-of-fuel", output };
+      if (code === STEP_NEEDS_INPUT) return { kind: "needs-input", output };
+      if (code === STEP_HALTED) return { kind: "halted", output };
+      if (code === STEP_ERROR) throw new Error("runtime error");
+      throw new Error(`unknown run result ${code}`);
+    }
+
+    // `byte` of null or undefined means end of input
+    provideInput(byte) {
+      bf.bf_wasm_stepper_provide_input(this.handle, byte ?? -1);
+    }
+
+    get pointer() {
+      return bf.bf_wasm_stepper_memory_pointer(this.handle);
+    }
+
+    cell(index) {
+      return bf.bf_wasm_stepper_cell(this.handle, index);
+    }
+
+    free() {
+      bf.bf_wasm_stepper_free(this.handle);
+      this.handle = 0;
+    }
+  }
+
+  return { run, Stepper };
+}